@@ -0,0 +1,171 @@
+// actions.rs
+//
+// parsergen actions <grammar.pglsf> [<out.rs>]
+//
+// Semantic actions: a rule may end with a block of Rust code,
+//
+//     number = +digit => { values.into_iter().fold(0, |n, digit| n * 10 + digit) };
+//
+// that computes a value for the rule from the values of its children, in yacc fashion. A Reducer
+// runs an Actions implementation over the symbols returned by a ParseMachine: a symbol gets its
+// value as soon as it is returned, and a rule once its Exit is, so every action runs as soon as
+// its rule is complete and the machine is down to one branch. No tree is built along the way. The
+// values of @hidden rules are left out, and @inline rules pass their children's values on to the
+// rule around them, so neither may have an action.
+//
+// parsergen actions writes out the Actions implementation for the blocks of a grammar, to be
+// included in the crate that uses them. The blocks see the values of the children of their rule as
+// values: Vec<Value>, where Value is a type the including module defines. Symbols become values
+// through Value::from(u32), and a rule without a block passes on the value of its first child, or
+// Value::default() if it has none.
+//
+
+use std::fs;
+use std::process::ExitCode;
+
+use crate::grammar::program::{Node, Program, ProgramSymbolOrRule};
+use crate::grammar::{self, Grammar, Visibility};
+use crate::parse_machine::SymbolOrRule;
+use crate::tester::{parse, Outcome};
+
+pub trait Actions {
+    type Value;
+
+    // The value of a symbol that was read.
+    fn symbol(&mut self, symbol: u32) -> Self::Value;
+
+    // The value of the rule with the given index in the grammar, from the values of its children.
+    fn rule(&mut self, rule: usize, values: Vec<Self::Value>) -> Self::Value;
+}
+
+pub struct Reducer<'a, A: Actions> {
+    program: &'a Program,
+    actions: A,
+
+    // The values of the children of every rule that has been entered and not yet left.
+    open: Vec<Vec<A::Value>>,
+
+    // The value of the root rule, once it has been left.
+    result: Option<A::Value>
+}
+
+impl<'a, A: Actions> Reducer<'a, A> {
+    pub fn new(program: &'a Program, actions: A) -> Self {
+        Reducer { program, actions, open: Vec::new(), result: None }
+    }
+
+    // Runs the actions for symbols returned by the parse machine, which may come in any number of
+    // pieces, as chains or as slices. Returns false if the calls and exits do not pair up.
+    pub fn feed<'s, 'p: 's>(&mut self, symbols: impl IntoIterator<Item = &'s ProgramSymbolOrRule<'p>>) -> bool {
+        for item in symbols {
+            let value = match item {
+                SymbolOrRule::Symbol(symbol) => self.actions.symbol(*symbol),
+                SymbolOrRule::Rule(rule) => match self.program.nodes()[rule.node() as usize] {
+                    Node::Call(_) => {
+                        self.open.push(Vec::new());
+                        continue
+                    },
+                    Node::Exit(i) => {
+                        let Some(values) = self.open.pop() else { return false };
+                        if self.program.rules()[i].visibility == Visibility::Hidden {
+                            continue
+                        }
+                        self.actions.rule(i, values)
+                    },
+                    _ => continue
+                }
+            };
+
+            match self.open.last_mut() {
+                Some(parent) => parent.push(value),
+                None if self.result.is_none() && matches!(item, SymbolOrRule::Rule(_)) => self.result = Some(value),
+                None => return false
+            }
+        }
+        true
+    }
+
+    // The value of the root rule, if it has been reduced.
+    pub fn finish(self) -> Option<A::Value> {
+        match self.open.is_empty() {
+            true => self.result,
+            false => None
+        }
+    }
+}
+
+// Parses the symbols and reduces them with the actions. Returns the outcome instead when the input
+// is not accepted.
+pub fn run<A: Actions>(program: &Program, input: &[u32], actions: A) -> Result<A::Value, Outcome> {
+    let parsed = parse(program, input)?;
+    let mut reducer = Reducer::new(program, actions);
+    let balanced = reducer.feed(&parsed);
+    match reducer.finish() {
+        Some(value) if balanced => Ok(value),
+        _ => panic!("the parse machine returned unbalanced rule calls")
+    }
+}
+
+// The Rust source of the Actions implementation for the blocks of the grammar. title names the
+// grammar in the header.
+pub fn generate(grammar: &Grammar, title: &str) -> String {
+    let mut out = format!("// Generated by parsergen from {}. Do not edit.\n\n", title);
+    out.push_str("struct GrammarActions;\n\n");
+    out.push_str("impl parsergen::actions::Actions for GrammarActions {\n");
+    out.push_str("    type Value = Value;\n\n");
+    out.push_str("    fn symbol(&mut self, symbol: u32) -> Value {\n");
+    out.push_str("        Value::from(symbol)\n");
+    out.push_str("    }\n\n");
+    out.push_str("    #[allow(unused_mut, unused_variables)]\n");
+    out.push_str("    fn rule(&mut self, rule: usize, mut values: Vec<Value>) -> Value {\n");
+    out.push_str("        match rule {\n");
+    for (i, rule) in grammar.rules.iter().enumerate() {
+        if let Some(action) = &rule.action {
+            out.push_str(&format!("            // {}\n", rule.name));
+            out.push_str(&format!("            {} => {},\n", i, action.code));
+        }
+    }
+    out.push_str("            _ => values.into_iter().next().unwrap_or_default()\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    out
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let (grammar_path, out_path) = match args {
+        [grammar_path] => (grammar_path.as_str(), None),
+        [grammar_path, out_path] => (grammar_path.as_str(), Some(out_path.as_str())),
+        _ => {
+            eprintln!("usage: parsergen actions <grammar.pglsf> [<out.rs>]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let grammar = match grammar::load(grammar_path) {
+        Ok((grammar, _)) => grammar,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let title = std::path::Path::new(grammar_path).file_name().map_or(grammar_path.to_string(), |name| name.to_string_lossy().to_string());
+    let source = generate(&grammar, &title);
+    match out_path {
+        None => {
+            print!("{}", source);
+            ExitCode::SUCCESS
+        },
+        Some(out_path) => match fs::write(out_path, source) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("{}: {}", out_path, error);
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
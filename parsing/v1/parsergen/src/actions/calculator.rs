@@ -0,0 +1,22 @@
+// Generated by parsergen from calculator.pglsf. Do not edit.
+
+struct GrammarActions;
+
+impl parsergen::actions::Actions for GrammarActions {
+    type Value = Value;
+
+    fn symbol(&mut self, symbol: u32) -> Value {
+        Value::from(symbol)
+    }
+
+    #[allow(unused_mut, unused_variables)]
+    fn rule(&mut self, rule: usize, mut values: Vec<Value>) -> Value {
+        match rule {
+            // sum
+            0 => { values.into_iter().step_by(2).sum() },
+            // product
+            1 => { values.into_iter().step_by(2).product() },
+            _ => values.into_iter().next().unwrap_or_default()
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::actions::*;
+use crate::grammar::read;
+use crate::parse_machine::ReadResult;
+use crate::tester::input_symbols;
+
+const CALCULATOR: &str = "\
+symbols ONE, TWO, THREE, PLUS, TIMES;
+grammar
+	sum = product *(PLUS product) => { values.into_iter().step_by(2).sum() };
+	product = number *(TIMES number) => { values.into_iter().step_by(2).product() };
+	number = ONE | TWO | THREE;
+";
+
+// Evaluates the calculator the way the generated code for its actions would.
+struct Calculator<'a> {
+    program: &'a Program,
+    reduced: Vec<String>
+}
+
+impl Actions for Calculator<'_> {
+    type Value = i64;
+
+    fn symbol(&mut self, symbol: u32) -> i64 {
+        symbol as i64 + 1
+    }
+
+    fn rule(&mut self, rule: usize, values: Vec<i64>) -> i64 {
+        self.reduced.push(self.program.rules()[rule].name.clone());
+        match rule {
+            0 => values.into_iter().step_by(2).sum(),
+            1 => values.into_iter().step_by(2).product(),
+            _ => values[0]
+        }
+    }
+}
+
+#[test]
+fn test_run() {
+    let program = Program::compile(&read(CALCULATOR).unwrap()).unwrap();
+    let input = input_symbols(&program, "TWO TIMES THREE PLUS ONE").unwrap();
+    assert_eq!(run(&program, &input, Calculator { program: &program, reduced: Vec::new() }), Ok(7));
+
+    let input = input_symbols(&program, "TWO PLUS").unwrap();
+    assert_eq!(run(&program, &input, Calculator { program: &program, reduced: Vec::new() }), Err(Outcome::Reject(2)));
+}
+
+#[test]
+fn test_reducer_runs_actions_as_rules_complete() {
+    let program = Program::compile(&read(CALCULATOR).unwrap()).unwrap();
+    let mut machine = program.machine();
+    let mut reducer = Reducer::new(&program, Calculator { program: &program, reduced: Vec::new() });
+
+    // The rules reduced after each symbol is read.
+    let mut reduced = Vec::new();
+    for name in ["TWO", "TIMES", "THREE", "PLUS"] {
+        match machine.read(program.symbol(name).unwrap()) {
+            ReadResult::Processed { symbols, .. } => assert!(reducer.feed(&symbols)),
+            ReadResult::Rejected { .. } => panic!()
+        }
+        reduced.push(reducer.actions.reduced.join(" "));
+    }
+    assert_eq!(reduced, vec!["number", "number", "number number", "number number product"]);
+}
+
+#[test]
+fn test_generate() {
+    let grammar = read(CALCULATOR).unwrap();
+    let source = generate(&grammar, "calculator.pglsf");
+    assert!(source.starts_with("// Generated by parsergen from calculator.pglsf. Do not edit.\n"));
+    assert!(source.contains("            // sum\n            0 => { values.into_iter().step_by(2).sum() },\n"));
+    assert!(source.contains("            1 => { values.into_iter().step_by(2).product() },\n"));
+    assert!(!source.contains("// number"));
+    assert!(source.contains("            _ => values.into_iter().next().unwrap_or_default()\n"));
+}
+
+// The code parsergen actions writes for the calculator, compiled as a crate that includes it would.
+mod generated {
+    use crate as parsergen;
+
+    // Symbols are worth their index, so ONE is 0 and THREE is 2.
+    pub type Value = i64;
+
+    include!("calculator.rs");
+
+    pub fn actions() -> impl parsergen::actions::Actions<Value = Value> {
+        GrammarActions
+    }
+}
+
+#[test]
+fn test_generated_code_runs() {
+    let grammar = read(CALCULATOR).unwrap();
+    assert_eq!(generate(&grammar, "calculator.pglsf"), include_str!("calculator.rs"));
+
+    let program = Program::compile(&grammar).unwrap();
+    let input = input_symbols(&program, "THREE TIMES THREE PLUS TWO").unwrap();
+    assert_eq!(run(&program, &input, generated::actions()), Ok(5));
+}
+
+#[test]
+fn test_actions_of_hidden_and_inline_rules() {
+    let error = |annotation: &str| read(&format!("symbols A;\ngrammar\n\troot = item;\n\t{} item = A => {{ 1 }};\n", annotation)).unwrap_err().message;
+    assert_eq!(error("@hidden"), "rule 'item' is @hidden, so its action would never run");
+    assert_eq!(error("@inline"), "rule 'item' is @inline, so its action would never run");
+}
@@ -0,0 +1,127 @@
+// arena.rs
+//
+// use arena::ArenaList::*
+//
+// Implements a persistent list whose nodes live in an arena instead of behind an Rc each.
+//
+// An arena is a Vec of nodes, and an arena list is the index of its first node, so consing is a
+// push onto the Vec and copying a list copies an index. Nodes are never freed one by one: all of
+// them go at once when the arena is cleared, which keeps the memory of the Vec for the nodes that
+// come next. Lists from before a clear must not be used after it; they would read whatever nodes
+// took their place. A list is only meaningful with the arena it was made in.
+//
+
+use std::marker::PhantomData;
+
+struct ArenaNode<T> {
+    head: T,
+
+    // The index of the next node, or None at the end of the list.
+    tail: Option<u32>
+}
+
+pub struct Arena<T> {
+    nodes: Vec<ArenaNode<T>>
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Arena { nodes: Vec::new() }
+    }
+
+    // The number of nodes allocated since the arena was made or last cleared.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Frees every node at once, and with them every list made in the arena.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena::new()
+    }
+}
+
+pub enum ArenaListState<'a, T> {
+    NonEmptyArenaList(&'a T, ArenaList<T>),
+    EmptyArenaList
+}
+
+pub use ArenaListState::*;
+
+pub struct ArenaList<T> {
+    root: Option<u32>,
+
+    // The list only holds an index; the items belong to the arena.
+    phantom: PhantomData<fn() -> T>
+}
+
+impl<T> ArenaList<T> {
+    pub const EMPTY: ArenaList<T> = ArenaList { root: None, phantom: PhantomData };
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn state<'a>(&self, arena: &'a Arena<T>) -> ArenaListState<'a, T> {
+        match self.root {
+            Some(index) => {
+                let node = &arena.nodes[index as usize];
+                NonEmptyArenaList(&node.head, ArenaList { root: node.tail, phantom: PhantomData })
+            },
+            None => EmptyArenaList
+        }
+    }
+
+    pub fn cons(arena: &mut Arena<T>, item: T, tail: ArenaList<T>) -> ArenaList<T> {
+        let index = u32::try_from(arena.nodes.len()).expect("an arena holds at most u32::MAX nodes");
+        arena.nodes.push(ArenaNode { head: item, tail: tail.root });
+        ArenaList { root: Some(index), phantom: PhantomData }
+    }
+
+    pub fn iter<'a>(&self, arena: &'a Arena<T>) -> Iter<'a, T> {
+        Iter { arena, node: self.root }
+    }
+}
+
+// Lists are indices, so they are copied whatever their items are.
+impl<T> Clone for ArenaList<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArenaList<T> {}
+
+impl<T> Default for ArenaList<T> {
+    fn default() -> Self {
+        ArenaList::EMPTY
+    }
+}
+
+// Borrows the items of a list, from the head to the end.
+pub struct Iter<'a, T> {
+    arena: &'a Arena<T>,
+    node: Option<u32>
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = &self.arena.nodes[self.node? as usize];
+        self.node = node.tail;
+        Some(&node.head)
+    }
+}
+
+#[cfg(test)]
+mod tests;
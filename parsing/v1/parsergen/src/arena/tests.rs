@@ -0,0 +1,38 @@
+use crate::arena::*;
+
+#[test]
+fn test_cons_arena_list() {
+    let mut arena = Arena::new();
+    let end = ArenaList::cons(&mut arena, 4, ArenaList::EMPTY);
+    let tail = ArenaList::cons(&mut arena, 2, end);
+    let first = ArenaList::cons(&mut arena, 1, tail);
+    let second = ArenaList::cons(&mut arena, 3, tail);
+    assert_eq!(first.iter(&arena).copied().collect::<Vec<_>>(), [1, 2, 4]);
+    assert_eq!(second.iter(&arena).copied().collect::<Vec<_>>(), [3, 2, 4]);
+
+    // The two lists share their tail, so it was allocated once.
+    assert_eq!(arena.len(), 4);
+
+    match first.state(&arena) {
+        NonEmptyArenaList(&head, rest) => {
+            assert_eq!(head, 1);
+            assert_eq!(rest.iter(&arena).count(), 2);
+        },
+        EmptyArenaList => panic!()
+    }
+    assert!(matches!(ArenaList::<i32>::EMPTY.state(&arena), EmptyArenaList));
+}
+
+#[test]
+fn test_clear_arena() {
+    let mut arena = Arena::new();
+    let list = (0..100).fold(ArenaList::EMPTY, |list, i| ArenaList::cons(&mut arena, i, list));
+    assert_eq!(list.iter(&arena).count(), 100);
+
+    // Clearing keeps the memory, so the nodes that follow need no allocation.
+    arena.clear();
+    assert!(arena.is_empty());
+    let list = ArenaList::cons(&mut arena, 7, ArenaList::EMPTY);
+    assert_eq!(list.iter(&arena).copied().collect::<Vec<_>>(), [7]);
+    assert_eq!(arena.len(), 1);
+}
@@ -0,0 +1,145 @@
+// bench.rs
+//
+// parsergen bench <grammar.pglsf> [--count N] [--depth D] [--seed S]
+//
+// Times the ParseMachine on random sentences of a grammar, generated as parsergen fuzz generates
+// them, once with its stacks on the heap, an Rc per node, and once with them in an arena. A new
+// heap machine is made for every sentence, while the arena machine is reset in between, so that
+// its nodes are freed all at once and their memory is used again. Prints the time each took and
+// how many stack nodes the sentences needed, which is the same for both. The machines must parse
+// every sentence the same way, returning the same symbols and rules or rejecting it at the same
+// place; a sentence where they do not is an error.
+//
+// The seed is 0 unless given, so that runs can be compared. Build with --release for times that
+// mean anything:
+//
+//     cargo run --release --bin parsergen -- bench ../languages/pglsf.pglsf
+//
+
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use crate::fuzzer::{format_sentence, SentenceGenerator};
+use crate::grammar::program::Program;
+use crate::grammar;
+use crate::tester::parse_with;
+
+pub struct Timings {
+    pub heap: Duration,
+    pub arena: Duration,
+
+    // The stack nodes allocated over all sentences.
+    pub nodes: usize
+}
+
+// Parses every sentence with both kinds of machine. Returns the first sentence the two disagree on
+// instead, if any.
+pub fn compare(program: &Program, sentences: &[Vec<u32>]) -> Result<Timings, Vec<u32>> {
+    let start = Instant::now();
+    let mut heap_outcomes = Vec::new();
+    for sentence in sentences {
+        heap_outcomes.push(parse_with(program.machine(), sentence));
+    }
+    let heap = start.elapsed();
+
+    let start = Instant::now();
+    let mut machine = program.arena_machine();
+    let mut arena_outcomes = Vec::new();
+    let mut nodes = 0;
+    for sentence in sentences {
+        arena_outcomes.push(parse_with(&mut machine, sentence));
+        nodes += machine.stacks().len();
+        machine.reset();
+    }
+    let arena = start.elapsed();
+
+    match heap_outcomes.iter().zip(&arena_outcomes).position(|(heap, arena)| heap != arena) {
+        Some(i) => Err(sentences[i].clone()),
+        None => Ok(Timings { heap, arena, nodes })
+    }
+}
+
+struct Options {
+    grammar_path: String,
+    count: usize,
+    depth: usize,
+    seed: u64
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options { grammar_path: String::new(), count: 20, depth: 4, seed: 0 };
+    let mut grammar_path = None;
+    let mut args = args.iter();
+
+    fn number<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+        value.and_then(|value| value.parse().ok()).ok_or_else(|| format!("{} needs a number", flag))
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" => options.count = number("--count", args.next())?,
+            "--depth" => options.depth = number("--depth", args.next())?,
+            "--seed" => options.seed = number("--seed", args.next())?,
+            _ if grammar_path.is_none() && !arg.starts_with("--") => grammar_path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument '{}'", arg))
+        }
+    }
+
+    options.grammar_path = grammar_path.ok_or("missing grammar")?;
+    Ok(options)
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("usage: parsergen bench <grammar.pglsf> [--count N] [--depth D] [--seed S]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let (grammar, source) = match grammar::load(&options.grammar_path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+    let program = match Program::compile(&grammar) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}", error.describe(&options.grammar_path, &source));
+            return ExitCode::FAILURE
+        }
+    };
+
+    let mut generator = SentenceGenerator::new(&grammar, options.depth, options.seed);
+    let mut sentences = Vec::new();
+    while sentences.len() < options.count {
+        match generator.sentence() {
+            Some(sentence) => sentences.push(sentence),
+            None => {
+                eprintln!("the grammar has no finite sentences");
+                return ExitCode::FAILURE
+            }
+        }
+    }
+    let symbols: usize = sentences.iter().map(Vec::len).sum();
+
+    match compare(&program, &sentences) {
+        Ok(timings) => {
+            println!("{} sentences, {} symbols, {} stack nodes", sentences.len(), symbols, timings.nodes);
+            println!("heap   {:>10.3} ms", timings.heap.as_secs_f64() * 1000.0);
+            println!("arena  {:>10.3} ms", timings.arena.as_secs_f64() * 1000.0);
+            ExitCode::SUCCESS
+        },
+        Err(sentence) => {
+            eprintln!("the heap and arena machines disagree on: {}", format_sentence(&grammar, &sentence));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
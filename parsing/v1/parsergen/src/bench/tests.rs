@@ -0,0 +1,27 @@
+use crate::bench::*;
+use crate::fuzzer::SentenceGenerator;
+use crate::grammar::read;
+use crate::tester::input_symbols;
+
+const PAIRS: &str = "\
+symbols A, B, EQUALS, SEMICOLON;
+grammar
+	pairs = +(pair SEMICOLON);
+	pair = name EQUALS name | name;
+	name = +(A | B);
+";
+
+#[test]
+fn test_compare_machines() {
+    let grammar = read(PAIRS).unwrap();
+    let program = Program::compile(&grammar).unwrap();
+    let mut generator = SentenceGenerator::new(&grammar, 6, 0);
+    let mut sentences: Vec<Vec<u32>> = (0..20).map(|_| generator.sentence().unwrap()).collect();
+
+    // Rejected and ambiguous sentences have to come out the same too.
+    sentences.push(input_symbols(&program, "A EQUALS SEMICOLON").unwrap());
+    sentences.push(input_symbols(&program, "A B").unwrap());
+
+    let timings = compare(&program, &sentences).unwrap();
+    assert!(timings.nodes > 0);
+}
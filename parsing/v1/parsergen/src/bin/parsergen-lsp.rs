@@ -0,0 +1,10 @@
+// parsergen-lsp.rs
+//
+// A Language Server Protocol server for .pglsf files, over stdin and stdout. See lsp.rs.
+//
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    parsergen::lsp::main()
+}
@@ -4,63 +4,159 @@
 //
 // Implements a persistent list data structure with slicing and linking.
 //
+// A chain is a rope: a binary tree with the items in its leaves, in order, kept balanced the way an
+// AVL tree is, so that the heights of the two sides of a branch differ by at most one. Every branch
+// knows how many items are under it. concat, slice, split_at and get take O(log n) steps, as does
+// cons, and the nodes of a chain are shared by the chains made from it rather than copied. iter
+// walks the leaves without allocating a node per item; state, which hands out the tail as a chain
+// of its own, splits off the head on every step.
+//
+// As with a list, the nodes are shared through Arc instead of Rc with the sync feature.
+//
 
+use std::cmp::Ordering;
+use std::fmt;
+#[cfg(not(feature = "sync"))]
 use std::rc::Rc;
+#[cfg(feature = "sync")]
+use std::sync::Arc as Rc;
 
 enum ChainNode<T> {
-    NonFinal(T, Rc<ChainNode<T>>),
-    Final(T),
-    Slice(Rc<ChainNode<T>>, u32),
-    Link(Rc<ChainNode<T>>, Rc<ChainNode<T>>)
+    Leaf(T),
+
+    // The items of the first node, then those of the second, with the number of items and the
+    // height of the node.
+    Branch(Rc<ChainNode<T>>, Rc<ChainNode<T>>, u32, u8)
 }
 
 use ChainNode::*;
 
 impl <T> ChainNode<T> {
-    fn is_final(&self) -> bool {
-        match &self {
-            NonFinal(_, _) => false,
-            Final(_) => true,
-            Link(first, second) => false,
-            Slice(_, 1) => true,
-            Slice(_, _) => false
+    fn size(&self) -> u32 {
+        match self {
+            Leaf(_) => 1,
+            Branch(_, _, size, _) => *size
         }
     }
 
-    fn head(&self) -> &T {
-        match &self {
-            NonFinal(head, _) => head,
-            Final(head) => head,
-            Link(first, _) => first.head(),
-            Slice(start, _) => start.head()
+    fn height(&self) -> u8 {
+        match self {
+            Leaf(_) => 1,
+            Branch(_, _, _, height) => *height
         }
     }
 
-    fn tail(&self) -> Rc<ChainNode<T>> {
-        match &self {
-            NonFinal(_, tail) => tail.clone(),
-            Final(_) => panic!(),
-            Link(start, end) =>
-                if start.is_final() {
-                    end.clone()
+    fn get(&self, mut index: u32) -> &T {
+        let mut node = self;
+        loop {
+            match node {
+                Leaf(item) => return item,
+                Branch(first, second, _, _) => if index < first.size() {
+                    node = first;
                 } else {
-                    Rc::new(Link(start.tail(), end.clone()))
-                },
-            Slice(start, size) => Rc::new(Slice(start.tail(), size - 1))
+                    index -= first.size();
+                    node = second;
+                }
+            }
         }
     }
 }
 
-impl <T> std::cmp::PartialEq for ChainNode<T> where T: PartialEq {    
-    fn eq(&self, other: &Self) -> bool {
-        match (self.is_final(), other.is_final()) {
-            (true, true) => self.head() == other.head(),
-            (false, false) => if self.head() == other.head() {
-                self.tail().eq(&other.tail())
-            } else {
-                false
-            },
-            _ => false
+// A branch over the two nodes, which must be balanced against each other.
+fn branch<T>(first: Rc<ChainNode<T>>, second: Rc<ChainNode<T>>) -> Rc<ChainNode<T>> {
+    let size = first.size() + second.size();
+    let height = first.height().max(second.height()) + 1;
+    Rc::new(Branch(first, second, size, height))
+}
+
+// The sides of a branch.
+fn sides<T>(node: &Rc<ChainNode<T>>) -> (Rc<ChainNode<T>>, Rc<ChainNode<T>>) {
+    match node.as_ref() {
+        Branch(first, second, _, _) => (first.clone(), second.clone()),
+        Leaf(_) => unreachable!("a node taller than another is a branch")
+    }
+}
+
+// A branch over nodes whose heights differ by at most two, rotated back into balance.
+fn balance<T>(first: Rc<ChainNode<T>>, second: Rc<ChainNode<T>>) -> Rc<ChainNode<T>> {
+    if first.height() > second.height() + 1 {
+        let (first_first, first_second) = sides(&first);
+        if first_first.height() >= first_second.height() {
+            branch(first_first, branch(first_second, second))
+        } else {
+            let (middle_first, middle_second) = sides(&first_second);
+            branch(branch(first_first, middle_first), branch(middle_second, second))
+        }
+    } else if second.height() > first.height() + 1 {
+        let (second_first, second_second) = sides(&second);
+        if second_second.height() >= second_first.height() {
+            branch(branch(first, second_first), second_second)
+        } else {
+            let (middle_first, middle_second) = sides(&second_first);
+            branch(branch(first, middle_first), branch(middle_second, second_second))
+        }
+    } else {
+        branch(first, second)
+    }
+}
+
+// The items of the first node followed by those of the second. Goes down the side of the taller
+// node until it meets a part as tall as the other node, so it takes as many steps as their heights
+// differ.
+fn join<T>(first: Rc<ChainNode<T>>, second: Rc<ChainNode<T>>) -> Rc<ChainNode<T>> {
+    if first.height() > second.height() + 1 {
+        let (first_first, first_second) = sides(&first);
+        balance(first_first, join(first_second, second))
+    } else if second.height() > first.height() + 1 {
+        let (second_first, second_second) = sides(&second);
+        balance(join(first, second_first), second_second)
+    } else {
+        branch(first, second)
+    }
+}
+
+// A node, or None where there are no items.
+type Part<T> = Option<Rc<ChainNode<T>>>;
+
+fn join_parts<T>(first: Part<T>, second: Part<T>) -> Part<T> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(join(first, second)),
+        (first, None) => first,
+        (None, second) => second
+    }
+}
+
+// The first index items of the node, and the rest.
+fn split<T>(node: &Rc<ChainNode<T>>, index: u32) -> (Part<T>, Part<T>) {
+    if index == 0 {
+        return (None, Some(node.clone()))
+    }
+    if index >= node.size() {
+        return (Some(node.clone()), None)
+    }
+
+    let (first, second) = sides(node);
+    match index.cmp(&first.size()) {
+        Ordering::Equal => (Some(first), Some(second)),
+        Ordering::Less => {
+            let (before, after) = split(&first, index);
+            (before, join_parts(after, Some(second)))
+        },
+        Ordering::Greater => {
+            let (before, after) = split(&second, index - first.size());
+            (join_parts(Some(first), before), after)
+        }
+    }
+}
+
+// A balanced node over the items, which must not be empty.
+fn build<T>(items: &mut std::vec::IntoIter<T>, size: usize) -> Rc<ChainNode<T>> {
+    match size {
+        1 => Rc::new(Leaf(items.next().unwrap())),
+        _ => {
+            let first = build(items, size / 2);
+            let second = build(items, size - size / 2);
+            branch(first, second)
         }
     }
 }
@@ -72,76 +168,150 @@ pub enum ChainState<'a, T> {
 
 pub use ChainState::*;
 
-#[derive(Clone)]
 pub struct Chain<T> {
-    root: Option<Rc<ChainNode<T>>>,
-    size: u32
+    root: Part<T>
 }
 
 impl <T> std::cmp::PartialEq for Chain<T> where T: PartialEq {
     fn eq(&self, other: &Self) -> bool {
-        self.size == other.size &&
         match (&self.root, &other.root) {
-            (Some(node_ref), Some(other_node_ref)) => node_ref.as_ref() == other_node_ref.as_ref(),
-            (None, None) => true,
-            _ => false
+            (Some(node_ref), Some(other_node_ref)) if Rc::ptr_eq(node_ref, other_node_ref) => true,
+            _ => self.size() == other.size() && self.iter().eq(other.iter())
         }
     }
 }
 
 impl <T> Chain<T> {
-    pub const EMPTY: Chain<T> = Chain::<T> { root: None, size: 0 };
+    pub const EMPTY: Chain<T> = Chain::<T> { root: None };
 
     pub fn size(&self) -> u32 {
-        self.size
+        self.root.as_ref().map_or(0, |node_ref| node_ref.size())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
     }
 
-    pub fn state(&self) -> ChainState<T> {
+    pub fn state(&self) -> ChainState<'_, T> {
         match &self.root {
-            Some(node_ref) => if node_ref.is_final() {
-                NonEmptyChain(node_ref.head(), Chain::EMPTY)
-            } else {
-                NonEmptyChain(node_ref.head(), Chain { root: Some(node_ref.tail()), size: self.size - 1 })
-            },
+            Some(node_ref) => NonEmptyChain(node_ref.get(0), Chain { root: split(node_ref, 1).1 }),
             None => ChainState::EmptyChain
         }
     }
 
-    pub fn cons(item: T, tail: Chain<T>) -> Chain<T> {
-        match tail.root {
-            Some(node_ref) => Chain { root: Some(Rc::new(NonFinal(item, node_ref))), size: tail.size + 1 },
-            None => Chain { root: Some(Rc::new(Final(item))), size: 1 }
+    // The item at the index, counting from 0 at the head.
+    pub fn get(&self, index: u32) -> Option<&T> {
+        match &self.root {
+            Some(node_ref) if index < node_ref.size() => Some(node_ref.get(index)),
+            _ => None
         }
     }
 
-    pub fn slice(start: Chain<T>, size: u32) -> Chain<T> {
-        if start.size < size {
+    // The first index items, and the rest. Panics if the chain is shorter than index.
+    pub fn split_at(&self, index: u32) -> (Chain<T>, Chain<T>) {
+        if self.size() < index {
             panic!()
         }
-        
-        match start.root {
-            Some(start_node_ref) => 
-                Chain {
-                    root: Some(Rc::new(Slice(start_node_ref.clone(), size))),
-                    size: size
-                },
-            None => Chain::EMPTY
+
+        match &self.root {
+            Some(node_ref) => {
+                let (first, second) = split(node_ref, index);
+                (Chain { root: first }, Chain { root: second })
+            },
+            None => (Chain::EMPTY, Chain::EMPTY)
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { pending: self.root.as_deref().into_iter().collect() }
+    }
+
+    pub fn cons(item: T, tail: Chain<T>) -> Chain<T> {
+        Chain::concat(Chain { root: Some(Rc::new(Leaf(item))) }, tail)
+    }
+
+    // The first size items of start. Panics if start is shorter than that.
+    pub fn slice(start: Chain<T>, size: u32) -> Chain<T> {
+        start.split_at(size).0
+    }
+
+    pub fn concat(mut first: Chain<T>, mut second: Chain<T>) -> Chain<T> {
+        Chain { root: join_parts(first.root.take(), second.root.take()) }
+    }
+}
+
+impl <T> Clone for Chain<T> {
+    fn clone(&self) -> Self {
+        Chain { root: self.root.clone() }
+    }
+}
+
+impl <T> Default for Chain<T> {
+    fn default() -> Self {
+        Chain::EMPTY
+    }
+}
+
+impl <T> Drop for Chain<T> {
+    // Frees the nodes no other chain shares in a loop. The tree is balanced, so recursion would
+    // do too, but the items may hold chains of their own. As for List, into_inner rather than
+    // try_unwrap hands each node to the last handle dropped, whichever thread drops it.
+    fn drop(&mut self) {
+        let mut pending: Vec<Rc<ChainNode<T>>> = self.root.take().into_iter().collect();
+        while let Some(node_ref) = pending.pop() {
+            if let Some(Branch(first, second, _, _)) = Rc::into_inner(node_ref) {
+                pending.extend([first, second]);
+            }
+        }
+    }
+}
+
+impl <T> fmt::Debug for Chain<T> where T: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl <T> FromIterator<T> for Chain<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items: Vec<T> = iter.into_iter().collect();
+        match items.len() {
+            0 => Chain::EMPTY,
+            size => Chain { root: Some(build(&mut items.into_iter(), size)) }
         }
     }
+}
+
+// Borrows the items of a chain, from the head to the end.
+pub struct Iter<'a, T> {
+    // The nodes still to be walked, the next one last.
+    pending: Vec<&'a ChainNode<T>>
+}
+
+impl <'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
 
-    pub fn concat(first: Chain<T>, second: Chain<T>) -> Chain<T> {
-        match (&first.root, &second.root) {
-            (Some(first_node_ref), Some(second_node_ref)) =>
-                Chain {
-                    root: Some(Rc::new(Link(first_node_ref.clone(), second_node_ref.clone()))),
-                    size: first.size + second.size
-                },
-            (Some(_), None) => first,
-            (None, Some(_)) => second,
-            (None, None) => Chain::EMPTY
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.pending.pop()? {
+                Leaf(item) => return Some(item),
+                Branch(first, second, _, _) => {
+                    self.pending.push(second);
+                    self.pending.push(first);
+                }
+            }
         }
     }
 }
 
+impl <'a, T> IntoIterator for &'a Chain<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
 #[cfg(test)]
 mod tests;
@@ -1,4 +1,5 @@
 use crate::chain::*;
+use crate::rng::Rng;
 
 fn sum_chain_internal(chain: Chain<i32>, acc: i32) -> i32 {
     match chain.state() {
@@ -37,4 +38,171 @@ fn test_sum_slice_chain() {
     } else {
         panic!();
     }
-}
\ No newline at end of file
+}
+
+fn long_chain(size: u32) -> Chain<u32> {
+    (0..size).collect()
+}
+
+fn height<T>(chain: &Chain<T>) -> u8 {
+    chain.root.as_ref().map_or(0, |node_ref| node_ref.height())
+}
+
+#[test]
+fn test_long_chain() {
+    // Long chains are compared and dropped without recursing, which would overflow the stack.
+    let chain = long_chain(1_000_000);
+    assert!(chain == long_chain(1_000_000));
+    assert!(chain != Chain::concat(long_chain(999_999), Chain::cons(7, Chain::EMPTY)));
+    assert!(chain != long_chain(999_999));
+
+    let linked = Chain::concat(long_chain(1_000_000), chain.clone());
+    let sliced = Chain::slice(linked.clone(), 1_500_000);
+    assert_eq!(sliced.size(), 1_500_000);
+    assert!(sliced != linked);
+    drop(chain);
+    drop(linked);
+    drop(sliced);
+}
+
+#[test]
+fn test_index_chain() {
+    let chain: Chain<u32> = (0..10).collect();
+    assert_eq!(chain.get(0), Some(&0));
+    assert_eq!(chain.get(7), Some(&7));
+    assert_eq!(chain.get(10), None);
+    assert_eq!(format!("{:?}", chain), "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]");
+
+    for index in 0..=10 {
+        let (first, second) = chain.split_at(index);
+        assert_eq!(first.iter().copied().collect::<Vec<_>>(), (0..index).collect::<Vec<_>>());
+        assert_eq!(second.iter().copied().collect::<Vec<_>>(), (index..10).collect::<Vec<_>>());
+        assert!(Chain::concat(first, second) == chain);
+    }
+    assert_eq!(Chain::slice(chain.clone(), 3).size(), 3);
+    assert!(Chain::slice(chain.clone(), 1) == Chain::cons(0, Chain::EMPTY));
+
+    let mut rest = chain.clone();
+    for i in 0..10 {
+        let NonEmptyChain(&head, tail) = rest.state() else { panic!() };
+        assert_eq!(head, i);
+        rest = tail;
+    }
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn test_balance_chain() {
+    // However a chain is put together, it stays about as shallow as a balanced tree of its items.
+    let mut consed = Chain::EMPTY;
+    let mut appended = Chain::EMPTY;
+    for i in 0..1024 {
+        consed = Chain::cons(i, consed);
+        appended = Chain::concat(appended, Chain::cons(i, Chain::EMPTY));
+    }
+    assert!(height(&consed) <= 15);
+    assert!(height(&appended) <= 15);
+    assert_eq!(appended.get(1000), Some(&1000));
+    assert_eq!(consed.get(1000), Some(&23));
+
+    // Rotating a chain by splitting and joining it keeps it balanced too.
+    let mut rotated = appended.clone();
+    for i in 0..1000 {
+        let (first, second) = rotated.split_at(i % 1024);
+        assert!(height(&first) <= height(&rotated) && height(&second) <= height(&rotated));
+        rotated = Chain::concat(second, first);
+        assert!(height(&rotated) <= 15);
+    }
+    assert_eq!(rotated.size(), 1024);
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn test_share_chain() {
+    // Threads slice the same chain at once.
+    let chain: Chain<u32> = (0..100).collect();
+    let sums: Vec<u32> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..4).map(|i| {
+            let chain = &chain;
+            scope.spawn(move || chain.split_at(i * 25).1.iter().sum())
+        }).collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    });
+    assert_eq!(sums, [4950, 4650, 3725, 2175]);
+}
+
+// A random chain of at most 40 items, made the ways chains are made, with the items it should hold.
+// Items are numbered as they are made, so that every item is different.
+fn random_chain(rng: &mut Rng, next: &mut u32, depth: usize) -> (Chain<u32>, Vec<u32>) {
+    match rng.below(if depth == 0 { 2 } else { 6 }) {
+        0 => (Chain::EMPTY, Vec::new()),
+        1 => {
+            *next += 1;
+            (Chain::cons(*next, Chain::EMPTY), vec![*next])
+        },
+        2 => {
+            let (tail, mut model) = random_chain(rng, next, depth - 1);
+            *next += 1;
+            model.insert(0, *next);
+            (Chain::cons(*next, tail), model)
+        },
+        3 | 4 => {
+            let (first, mut model) = random_chain(rng, next, depth - 1);
+            let (second, second_model) = random_chain(rng, next, depth - 1);
+            model.extend(second_model);
+            (Chain::concat(first, second), model)
+        },
+        _ => {
+            let (chain, mut model) = random_chain(rng, next, depth - 1);
+            let size = rng.below(model.len() + 1);
+            model.truncate(size);
+            (Chain::slice(chain, size as u32), model)
+        }
+    }
+}
+
+// Checks everything a chain says about its items against the items it should hold.
+fn check_chain(chain: &Chain<u32>, model: &[u32]) {
+    assert_eq!(chain.size() as usize, model.len());
+    assert_eq!(chain.is_empty(), model.is_empty());
+    assert_eq!(chain.iter().copied().collect::<Vec<_>>(), model);
+    for (i, item) in model.iter().enumerate() {
+        assert_eq!(chain.get(i as u32), Some(item));
+    }
+    assert_eq!(chain.get(model.len() as u32), None);
+    assert!(height(chain) as usize <= 2 * (usize::BITS - model.len().leading_zeros()) as usize + 1);
+
+    // Walking the chain with state visits every item once, and ends exactly at the end.
+    let mut rest = chain.clone();
+    for (i, item) in model.iter().enumerate() {
+        rest = match rest.state() {
+            NonEmptyChain(head, tail) => {
+                assert_eq!(head, item);
+                assert_eq!(tail.size() as usize, model.len() - i - 1);
+                tail
+            },
+            EmptyChain => panic!("the chain ended after {} of {} items", i, model.len())
+        };
+    }
+    assert!(matches!(rest.state(), EmptyChain));
+}
+
+#[test]
+fn test_chain_properties() {
+    let mut rng = Rng::new(0);
+    let mut next = 0;
+    for _ in 0..500 {
+        let (chain, model) = random_chain(&mut rng, &mut next, 6);
+        check_chain(&chain, &model);
+
+        // Every split and every slice, including those of size 0, 1 and the whole chain.
+        for index in 0..=model.len() {
+            let (first, second) = chain.split_at(index as u32);
+            check_chain(&first, &model[..index]);
+            check_chain(&second, &model[index..]);
+            check_chain(&Chain::slice(chain.clone(), index as u32), &model[..index]);
+            check_chain(&Chain::concat(second, first), &[&model[index..], &model[..index]].concat());
+        }
+        assert!(chain == model.iter().copied().collect());
+    }
+}
@@ -0,0 +1,315 @@
+// coverage.rs
+//
+// parsergen coverage <grammar.pglsf> <corpus> [--html <report.html>]
+//
+// Runs a corpus through a ParseMachine built from a grammar and reports which branches of the
+// grammar took part in accepted parses: the rules, the alternatives of each union, and whether each
+// optional and repetition was both taken and left out. <corpus> is a file or a directory of them.
+// Files written in the tester's format contribute the input of each case; any other file is one
+// input.
+//
+// The text report lists every branch that was never exercised, underlined in the grammar. The
+// HTML report shows the whole grammar with exercised branches in green and the rest in red.
+//
+
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::grammar::program::{Node, NodeId, Program, ProgramSymbolOrRule};
+use crate::grammar::{self, Span};
+use crate::parse_machine::SymbolOrRule;
+use crate::tester::{input_symbols, parse, test_files, TestFile};
+
+#[derive(Clone, Debug)]
+pub struct Branch {
+    // The rule the branch belongs to.
+    pub rule: usize,
+    pub span: Span,
+
+    // What kind of branch it is, e.g. "alternative", and which way it went, e.g. "taken".
+    pub kind: &'static str,
+    pub label: &'static str,
+
+    // How many times the branch went this way in accepted parses.
+    pub count: u64
+}
+
+impl Branch {
+    // E.g. "alternative never taken" or "rule 'digit' used 3 times".
+    pub fn describe(&self, program: &Program) -> String {
+        let subject = match self.kind {
+            "rule" => format!("rule '{}'", program.rules()[self.rule].name),
+            kind => kind.to_string()
+        };
+
+        match self.count {
+            0 => format!("{} never {}", subject, self.label),
+            1 => format!("{} {} once", subject, self.label),
+            n => format!("{} {} {} times", subject, self.label, n)
+        }
+    }
+}
+
+pub struct Coverage<'a> {
+    program: &'a Program,
+
+    // How many times each node was executed in accepted parses.
+    counts: Vec<u64>,
+
+    pub accepted: usize,
+    pub rejected: usize
+}
+
+impl<'a> Coverage<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Coverage { program, counts: vec![0; program.nodes().len()], accepted: 0, rejected: 0 }
+    }
+
+    // Records the symbols of an accepted parse.
+    pub fn record(&mut self, parsed: &[ProgramSymbolOrRule]) {
+        for item in parsed {
+            if let SymbolOrRule::Rule(rule) = item {
+                self.counts[rule.node() as usize] += 1;
+            }
+        }
+        self.accepted += 1;
+    }
+
+    // Runs an input and records it if it is accepted.
+    pub fn run(&mut self, input: &[u32]) {
+        match parse(self.program, input) {
+            Ok(parsed) => self.record(&parsed),
+            Err(_) => self.rejected += 1
+        }
+    }
+
+    // Every branch of the grammar, rule by rule. The branches inside a rule are only listed if the
+    // rule itself was used.
+    pub fn branches(&self) -> Vec<Branch> {
+        let mut result = Vec::new();
+        for (i, rule) in self.program.rules().iter().enumerate() {
+            let count = self.count(rule.body);
+            result.push(Branch { rule: i, span: self.program.span(rule.call), kind: "rule", label: "used", count });
+            if count > 0 {
+                self.branches_of(i, rule.body, &mut result);
+            }
+        }
+        result
+    }
+
+    fn count(&self, node: NodeId) -> u64 {
+        self.counts[node as usize]
+    }
+
+    fn branches_of(&self, rule: usize, node: NodeId, result: &mut Vec<Branch>) {
+        let branch = |node: NodeId, kind, label, count| Branch { rule, span: self.program.span(node), kind, label, count };
+
+        match &self.program.nodes()[node as usize] {
+            Node::Seq(items) => for &item in items {
+                self.branches_of(rule, item, result);
+            },
+            Node::Union(alternatives) => for &alternative in alternatives {
+                result.push(branch(alternative, "alternative", "taken", self.count(alternative)));
+                self.branches_of(rule, alternative, result);
+            },
+            Node::Opt(body, skip) => {
+                result.push(branch(node, "optional", "present", self.count(*body)));
+                result.push(branch(node, "optional", "absent", self.count(*skip)));
+                self.branches_of(rule, *body, result);
+            },
+            Node::Star(plus, skip) => {
+                result.push(branch(node, "repetition", "taken", self.count(*plus)));
+                result.push(branch(node, "repetition", "empty", self.count(*skip)));
+                self.branches_of(rule, *plus, result);
+            },
+            Node::Plus(body, _) => {
+                // Every run of the body after the first is a repeat.
+                let repeats = self.count(*body) - self.count(node);
+                result.push(branch(node, "repetition", "repeated", repeats));
+                self.branches_of(rule, *body, result);
+            },
+            Node::Call(_) | Node::Exit(_) | Node::Label(_) | Node::Symbol(_) | Node::Range(_) | Node::Repeat(_) | Node::Skip => ()
+        }
+    }
+
+    // The text report: a summary, then every branch that was never exercised.
+    pub fn text_report(&self, path: &str, source: &str) -> String {
+        let branches = self.branches();
+        let missed: Vec<&Branch> = branches.iter().filter(|branch| branch.count == 0).collect();
+
+        let mut report = format!(
+            "{}: {} inputs accepted, {} not accepted\n{} of {} branches exercised\n",
+            path, self.accepted, self.rejected, branches.len() - missed.len(), branches.len()
+        );
+
+        for branch in missed {
+            let (line, col) = branch.span.line_col(source);
+            let line_start = source[..branch.span.start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = source[branch.span.start..].find('\n').map_or(source.len(), |i| branch.span.start + i);
+            let text = &source[line_start..line_end];
+
+            // Tabs are kept under the text so that the carets line up with it.
+            let padding: String = text[..branch.span.start - line_start].chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let width = source[branch.span.start..branch.span.end.min(line_end)].chars().count().max(1);
+
+            report.push_str(&format!("\n{}:{}:{}: {}\n", path, line, col, branch.describe(self.program)));
+            report.push_str(&format!("{}\n{}{}\n", text, padding, "^".repeat(width)));
+        }
+
+        report
+    }
+
+    // The HTML report: the grammar with every branch highlighted, and its counts shown on hover.
+    pub fn html_report(&self, path: &str, source: &str) -> String {
+        let branches = self.branches();
+
+        // Branches on the same span are shown together.
+        let mut spans: Vec<(Span, bool, String)> = Vec::new();
+        for branch in &branches {
+            let description = branch.describe(self.program);
+            match spans.iter_mut().find(|(span, _, _)| *span == branch.span) {
+                Some((_, missed, title)) => {
+                    *missed |= branch.count == 0;
+                    title.push('\n');
+                    title.push_str(&description);
+                },
+                None => spans.push((branch.span, branch.count == 0, description))
+            }
+        }
+        spans.sort_by_key(|(span, _, _)| (span.start, std::cmp::Reverse(span.end)));
+
+        let mut body = String::new();
+        let mut pos = 0;
+        let mut open: Vec<usize> = Vec::new();
+        for (span, missed, title) in &spans {
+            while let Some(&end) = open.last().filter(|&&end| end <= span.start) {
+                body.push_str(&escape(&source[pos..end]));
+                body.push_str("</span>");
+                pos = end;
+                open.pop();
+            }
+
+            body.push_str(&escape(&source[pos..span.start]));
+            pos = span.start;
+
+            let class = if *missed { "missed" } else { "hit" };
+            body.push_str(&format!("<span class=\"{}\" title=\"{}\">", class, escape(title)));
+            open.push(open.last().map_or(span.end, |&end| span.end.min(end)));
+        }
+        while let Some(end) = open.pop() {
+            body.push_str(&escape(&source[pos..end]));
+            body.push_str("</span>");
+            pos = end;
+        }
+        body.push_str(&escape(&source[pos..]));
+
+        let exercised = branches.iter().filter(|branch| branch.count > 0).count();
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Coverage of {path}</title>\n<style>\n\
+             body {{ font-family: sans-serif; }}\n\
+             pre {{ tab-size: 4; }}\n\
+             .hit {{ background: #dfd; }}\n\
+             .missed {{ background: #fcc; }}\n\
+             </style>\n</head>\n<body>\n<h1>Coverage of {path}</h1>\n\
+             <p>{accepted} inputs accepted, {rejected} not accepted. {exercised} of {total} branches exercised.</p>\n\
+             <pre>{body}</pre>\n</body>\n</html>\n",
+            path = escape(path), accepted = self.accepted, rejected = self.rejected,
+            exercised = exercised, total = branches.len(), body = body
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// The inputs in a corpus file: the cases of a test file, or else the whole file.
+fn corpus_inputs(program: &Program, path: &Path) -> Result<Vec<Vec<u32>>, String> {
+    let bytes = fs::read(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    if text.lines().any(|line| line.starts_with("=== ")) {
+        let file = TestFile::parse(&text).map_err(|message| format!("{}: {}", path.display(), message))?;
+        file.cases.iter()
+            .map(|case| input_symbols(program, &case.input).map_err(|message| format!("{}: {}: {}", path.display(), case.name, message)))
+            .collect()
+    } else if program.is_binary() {
+        Ok(vec![bytes.iter().map(|&byte| byte as u32).collect()])
+    } else {
+        input_symbols(program, &text).map(|input| vec![input]).map_err(|message| format!("{}: {}", path.display(), message))
+    }
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let mut html_path = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--html" => html_path = args.next(),
+            _ => paths.push(arg)
+        }
+    }
+
+    let (grammar_path, corpus_path) = match paths.as_slice() {
+        [grammar_path, corpus_path] => (grammar_path.as_str(), Path::new(corpus_path.as_str())),
+        _ => {
+            eprintln!("usage: parsergen coverage <grammar.pglsf> <corpus> [--html <report.html>]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let (grammar, source) = match grammar::load(grammar_path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let program = match Program::compile(&grammar) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}", error.describe(grammar_path, &source));
+            return ExitCode::FAILURE
+        }
+    };
+
+    let mut coverage = Coverage::new(&program);
+    let files = match test_files(corpus_path) {
+        Ok(files) => files,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    for path in files {
+        match corpus_inputs(&program, &path) {
+            Ok(inputs) => for input in inputs {
+                coverage.run(&input);
+            },
+            Err(message) => {
+                eprintln!("{}", message);
+                return ExitCode::FAILURE
+            }
+        }
+    }
+
+    print!("{}", coverage.text_report(grammar_path, &source));
+
+    if let Some(html_path) = html_path {
+        if let Err(error) = fs::write(html_path, coverage.html_report(grammar_path, &source)) {
+            eprintln!("{}: {}", html_path, error);
+            return ExitCode::FAILURE
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,81 @@
+use crate::coverage::*;
+use crate::grammar::read;
+use crate::grammar::program::Program;
+use crate::tester::input_symbols;
+
+const SOURCE: &str = "\
+symbols A, B, C, D;
+grammar
+	root = +item ?D;
+	item = A | B | C;
+	unused = D;
+";
+
+fn missed(coverage: &Coverage, program: &Program) -> Vec<String> {
+    coverage.branches().iter()
+        .filter(|branch| branch.count == 0)
+        .map(|branch| branch.describe(program))
+        .collect()
+}
+
+#[test]
+fn test_branches() {
+    let program = Program::compile(&read(SOURCE).unwrap()).unwrap();
+    let mut coverage = Coverage::new(&program);
+    coverage.run(&input_symbols(&program, "A").unwrap());
+    coverage.run(&input_symbols(&program, "B D").unwrap());
+    coverage.run(&input_symbols(&program, "D").unwrap());
+
+    assert_eq!((coverage.accepted, coverage.rejected), (2, 1));
+    assert_eq!(missed(&coverage, &program), vec![
+        "repetition never repeated",
+        "alternative never taken",
+        "rule 'unused' never used"
+    ]);
+
+    coverage.run(&input_symbols(&program, "C A").unwrap());
+    assert_eq!(missed(&coverage, &program), vec!["rule 'unused' never used"]);
+}
+
+#[test]
+fn test_reports() {
+    let program = Program::compile(&read(SOURCE).unwrap()).unwrap();
+    let mut coverage = Coverage::new(&program);
+    coverage.run(&input_symbols(&program, "A B").unwrap());
+
+    let text = coverage.text_report("test.pglsf", SOURCE);
+    assert!(text.contains("test.pglsf:4:17: alternative never taken\n\titem = A | B | C;\n\t               ^\n"), "{}", text);
+    assert!(text.contains("test.pglsf:3:15: optional never present\n"), "{}", text);
+
+    let html = coverage.html_report("test.pglsf", SOURCE);
+    assert!(html.contains("<span class=\"missed\" title=\"alternative never taken\">C</span>"), "{}", html);
+    assert!(html.contains("<span class=\"hit\" title=\"alternative taken once\">A</span>"), "{}", html);
+}
+
+// symbols
+//     A;
+// grammar
+//     root = A;
+// as the symbol names pglsf.pglsf gives its characters.
+const PGLSF_SENTENCE: &str = "\
+LETTER_S LETTER_Y LETTER_M LETTER_B LETTER_O LETTER_L LETTER_S NEWLINE
+TAB ULETTER_A SEMICOLON NEWLINE
+LETTER_G LETTER_R LETTER_A LETTER_M LETTER_M LETTER_A LETTER_R NEWLINE
+TAB LETTER_R LETTER_O LETTER_O LETTER_T SPACE EQUAL_SIGN SPACE ULETTER_A SEMICOLON NEWLINE";
+
+#[test]
+fn test_pglsf_smoke() {
+    let (grammar, _) = crate::grammar::load("../languages/pglsf.pglsf").unwrap();
+    let program = Program::compile(&grammar).unwrap();
+    let accepted = input_symbols(&program, PGLSF_SENTENCE).unwrap();
+    let rejected = input_symbols(&program, PGLSF_SENTENCE.trim_end_matches(" SEMICOLON NEWLINE")).unwrap();
+
+    let mut coverage = Coverage::new(&program);
+    coverage.run(&accepted);
+    coverage.run(&rejected);
+    assert_eq!((coverage.accepted, coverage.rejected), (1, 1));
+
+    // One small grammar leaves most of pglsf.pglsf unexercised, but not all of it.
+    let missed = missed(&coverage, &program);
+    assert!(!missed.is_empty() && missed.len() < coverage.branches().len());
+}
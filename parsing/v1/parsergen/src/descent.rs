@@ -0,0 +1,380 @@
+// descent.rs
+//
+// parsergen descent <grammar.pglsf> [<out.rs>]
+//
+// Writes out a recursive descent parser for a grammar as plain Rust, with one function per rule
+// and nothing from parsergen needed to run it. Choices that one symbol of lookahead decides (see
+// lookahead.rs) become a match on the next symbol. The others are tried in order, going back to
+// where they started when a way fails, and the first way that matches is kept, so grammars that
+// are close to LL work best. Where the ParseMachine would find an input ambiguous, the generated
+// parser takes the first reading; and an input the machine accepts may be rejected if an earlier
+// way matched a prefix of it and the rest then fails.
+//
+// The generated parse() takes the symbols as u32s, with a constant for each declared symbol, and
+// returns a tree like the ones Program::tree builds, or the offset of the first symbol it could
+// not read.
+//
+
+use std::fs;
+use std::process::ExitCode;
+
+use crate::grammar::lookahead::{first_sets, Decision, SymbolSet};
+use crate::grammar::program::{Node, NodeId, Program};
+use crate::grammar::{self, Visibility};
+
+struct Generator<'a> {
+    program: &'a Program,
+
+    // For every node, its choice, if it makes one, and whether it can match nothing.
+    decisions: Vec<Option<&'a Decision>>,
+    nullable: Vec<bool>,
+
+    // The name of each rule's function.
+    functions: Vec<String>
+}
+
+impl<'a> Generator<'a> {
+    fn new(program: &'a Program) -> Self {
+        let mut decisions = vec![None; program.nodes().len()];
+        for decision in program.decisions() {
+            decisions[decision.node as usize] = Some(decision);
+        }
+        let bodies: Vec<NodeId> = program.rules().iter().map(|rule| rule.body).collect();
+        let (_, nullable) = first_sets(program.nodes(), &bodies);
+
+        // Rule names may have dots and template arguments in them, which are made into underscores.
+        let mut functions: Vec<String> = Vec::new();
+        for (i, rule) in program.rules().iter().enumerate() {
+            let name: String = rule.name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+            let name = format!("parse_{}", name.trim_end_matches('_'));
+            match functions.contains(&name) {
+                true => functions.push(format!("{}_{}", name, i)),
+                false => functions.push(name)
+            }
+        }
+
+        Generator { program, decisions, nullable, functions }
+    }
+
+    fn symbol(&self, symbol: u32) -> String {
+        match self.program.is_binary() {
+            true => format!("0x{:02X}", symbol),
+            false => self.program.symbol_name(symbol)
+        }
+    }
+
+    // A pattern matching the symbols, with runs of three or more written as ranges.
+    fn pattern(&self, symbols: &SymbolSet) -> String {
+        let symbols: Vec<u32> = symbols.iter().collect();
+        let mut parts = Vec::new();
+        let mut i = 0;
+        while i < symbols.len() {
+            let mut j = i;
+            while j + 1 < symbols.len() && symbols[j + 1] == symbols[j] + 1 {
+                j += 1;
+            }
+            match j - i {
+                0 => parts.push(self.symbol(symbols[i])),
+                1 => parts.extend([self.symbol(symbols[i]), self.symbol(symbols[j])]),
+                _ => parts.push(format!("{}..={}", self.symbol(symbols[i]), self.symbol(symbols[j])))
+            }
+            i = j + 1;
+        }
+        parts.join(" | ")
+    }
+
+    // The code matching the node, a bool expression. Lines after the first are indented by indent.
+    fn expr(&self, node: NodeId, indent: &str) -> String {
+        let inner = format!("{}    ", indent);
+        match &self.program.nodes()[node as usize] {
+            Node::Call(rule) => format!("self.{}(out)", self.functions[*rule]),
+            Node::Exit(_) | Node::Label(_) | Node::Skip => "true".to_string(),
+            Node::Symbol(symbol) => format!("self.symbol({}, out)", self.symbol(*symbol)),
+            Node::Range(ways) => {
+                let symbol = |way: NodeId| match self.program.nodes()[way as usize] {
+                    Node::Symbol(symbol) => symbol,
+                    _ => unreachable!("ranges are made of symbols")
+                };
+                format!("self.range({}, {}, out)", self.symbol(symbol(ways[0])), self.symbol(symbol(ways[ways.len() - 1])))
+            },
+            Node::Seq(items) => {
+                let mut parts = Vec::new();
+                let mut label = None;
+                for &item in items {
+                    match &self.program.nodes()[item as usize] {
+                        Node::Label(name) => label = Some(name.clone()),
+                        _ => match label.take() {
+                            Some(name) => parts.push(format!(
+                                "{{\n{inner}    let at = out.len();\n{inner}    {} && self.label(at, \"{}\", out)\n{inner}}}",
+                                self.expr(item, &format!("{}    ", inner)), name, inner = inner
+                            )),
+                            None => parts.push(self.expr(item, &inner))
+                        }
+                    }
+                }
+                match parts.len() {
+                    0 => "true".to_string(),
+                    1 => parts.pop().unwrap().replace(&format!("\n{}", inner), &format!("\n{}", indent)),
+                    _ => format!("(\n{inner}{}\n{indent})", parts.join(&format!("\n{}&& ", inner)), inner = inner, indent = indent)
+                }
+            },
+            Node::Union(ways) => self.choice(node, ways, indent),
+            Node::Opt(body, skip) | Node::Star(body, skip) => self.choice(node, &[*body, *skip], indent),
+            Node::Plus(body, repeat) => format!(
+                "(\n{inner}{}\n{inner}&& {}\n{indent})",
+                self.expr(*body, &inner), self.expr(*repeat, &inner), inner = inner, indent = indent
+            ),
+            Node::Repeat(body) => match self.decisions[node as usize].filter(|decision| decision.is_deterministic()) {
+                Some(decision) => {
+                    let body = self.expr(*body, &format!("{}    ", inner));
+                    let mut arms = Vec::new();
+                    if !decision.alternatives[0].is_empty() {
+                        arms.push(format!("Some({}) => if !{} {{ break false }},", self.pattern(&decision.alternatives[0]), body));
+                    }
+                    match decision.alternatives[1].is_empty() {
+                        true => arms.push("None => break true,".to_string()),
+                        false => arms.push(format!("Some({}) | None => break true,", self.pattern(&decision.alternatives[1])))
+                    }
+                    arms.push("_ => break self.fail()".to_string());
+                    format!(
+                        "loop {{\n{inner}match self.peek() {{\n{inner}    {}\n{inner}}}\n{indent}}}",
+                        arms.join(&format!("\n{}    ", inner)), inner = inner, indent = indent
+                    )
+                },
+                None => format!(
+                    "loop {{\n{inner}let (pos, len) = (self.pos, out.len());\n{inner}if !{} {{ break self.back(pos, len, out) }}\n{indent}}}",
+                    self.expr(*body, &inner), inner = inner, indent = indent
+                )
+            }
+        }
+    }
+
+    // The code for a choice between the ways. A choice that the next symbol decides becomes a match;
+    // the others try each way in turn.
+    fn choice(&self, node: NodeId, ways: &[NodeId], indent: &str) -> String {
+        let inner = format!("{}    ", indent);
+        match self.decisions[node as usize].filter(|decision| decision.is_deterministic()) {
+            Some(decision) => {
+                let mut arms = Vec::new();
+                for (&way, symbols) in ways.iter().zip(&decision.alternatives) {
+                    if !symbols.is_empty() {
+                        arms.push(format!("Some({}) => {},", self.pattern(symbols), self.expr(way, &inner)));
+                    }
+                }
+
+                // At the end of the input, the first way that can match nothing.
+                if let Some(&way) = ways.iter().find(|&&way| self.nullable[way as usize]) {
+                    arms.push(format!("None => {},", self.expr(way, &inner)));
+                }
+                arms.push("_ => self.fail()".to_string());
+                format!("match self.peek() {{\n{inner}{}\n{indent}}}", arms.join(&format!("\n{}", inner)), inner = inner, indent = indent)
+            },
+            None => {
+                let mut code = self.expr(ways[0], &inner);
+                for &way in &ways[1..] {
+                    code.push_str(&format!("\n{}|| self.back(pos, len, out)", inner));
+                    match self.expr(way, &inner) {
+                        way if way == "true" => (),
+                        way => code.push_str(&format!(" && {}", way))
+                    }
+                }
+                format!("{{\n{inner}let (pos, len) = (self.pos, out.len());\n{inner}{}\n{indent}}}", code, inner = inner, indent = indent)
+            }
+        }
+    }
+
+    fn rule(&self, i: usize) -> String {
+        let rule = &self.program.rules()[i];
+        let name = &self.functions[i];
+        let body = self.expr(rule.body, "        ");
+        let function = match rule.visibility {
+            Visibility::Visible => format!(
+                "    fn {}(&mut self, parent: &mut Vec<Tree>) -> bool {{\n        \
+                 let out = &mut Vec::<Tree>::new();\n        \
+                 let matched = {};\n        \
+                 if matched {{\n            \
+                 parent.push(Tree::Node({:?}, std::mem::take(out)));\n        \
+                 }}\n        \
+                 matched\n    \
+                 }}\n",
+                name, body, rule.name
+            ),
+            Visibility::Hidden => format!(
+                "    fn {}(&mut self, _parent: &mut Vec<Tree>) -> bool {{\n        \
+                 let out = &mut Vec::<Tree>::new();\n        \
+                 {}\n    \
+                 }}\n",
+                name, body
+            ),
+            Visibility::Inline => format!(
+                "    fn {}(&mut self, out: &mut Vec<Tree>) -> bool {{\n        \
+                 {}\n    \
+                 }}\n",
+                name, body
+            )
+        };
+        format!("    // {}\n{}", rule.name, function)
+    }
+}
+
+const RUNTIME: &str = "\
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Tree {
+    Node(&'static str, Vec<Tree>),
+    Leaf(u32),
+
+    // A labelled child.
+    Field(&'static str, Box<Tree>)
+}
+
+// Writes the tree as an S-expression, the way parsergen does.
+impl std::fmt::Display for Tree {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Tree::Node(name, children) => {
+                write!(f, \"({}\", name)?;
+                for child in children {
+                    write!(f, \" {}\", child)?;
+                }
+                write!(f, \")\")
+            },
+            Tree::Leaf(symbol) => write!(f, \"{}\", symbol_name(*symbol)),
+            Tree::Field(label, tree) => write!(f, \"{}:{}\", label, tree)
+        }
+    }
+}
+
+// Parses the symbols. Returns the tree, or the offset of the first symbol that could not be read.
+pub fn parse(input: &[u32]) -> Result<Tree, usize> {
+    let mut parser = Parser { input, pos: 0, furthest: 0 };
+    let mut out = Vec::new();
+    if parser.ROOT(&mut out) && parser.pos == input.len() {
+        return Ok(out.pop().unwrap())
+    }
+    Err(parser.furthest.max(parser.pos))
+}
+
+struct Parser<'i> {
+    input: &'i [u32],
+    pos: usize,
+
+    // The furthest a way got before failing, where the error is reported.
+    furthest: usize
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u32> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn fail(&mut self) -> bool {
+        self.furthest = self.furthest.max(self.pos);
+        false
+    }
+
+    fn symbol(&mut self, symbol: u32, out: &mut Vec<Tree>) -> bool {
+        match self.peek() == Some(symbol) {
+            true => {
+                out.push(Tree::Leaf(symbol));
+                self.pos += 1;
+                true
+            },
+            false => self.fail()
+        }
+    }
+
+    fn range(&mut self, first: u32, last: u32, out: &mut Vec<Tree>) -> bool {
+        match self.peek() {
+            Some(symbol) if (first..=last).contains(&symbol) => self.symbol(symbol, out),
+            _ => self.fail()
+        }
+    }
+
+    // Goes back to where a way started, to try the next one.
+    fn back(&mut self, pos: usize, len: usize, out: &mut Vec<Tree>) -> bool {
+        self.pos = pos;
+        out.truncate(len);
+        true
+    }
+
+    // Labels the child added since at, if there is one.
+    fn label(&mut self, at: usize, label: &'static str, out: &mut Vec<Tree>) -> bool {
+        if out.len() == at + 1 {
+            let child = out.pop().unwrap();
+            out.push(Tree::Field(label, Box::new(child)));
+        }
+        true
+    }
+";
+
+// The Rust source of the parser. title names the grammar in the header.
+pub fn generate(program: &Program, title: &str) -> String {
+    let generator = Generator::new(program);
+    let mut out = format!("// Generated by parsergen from {}. Do not edit.\n\n", title);
+
+    if !program.is_binary() {
+        for symbol in 0..program.symbol_count() {
+            out.push_str(&format!("pub const {}: u32 = {};\n", program.symbol_name(symbol), symbol));
+        }
+        out.push('\n');
+        let names: Vec<String> = (0..program.symbol_count()).map(|symbol| format!("{:?}", program.symbol_name(symbol))).collect();
+        out.push_str(&format!("const SYMBOL_NAMES: [&str; {}] = [{}];\n\n", names.len(), names.join(", ")));
+        out.push_str("fn symbol_name(symbol: u32) -> String {\n    SYMBOL_NAMES[symbol as usize].to_string()\n}\n\n");
+    } else {
+        out.push_str("fn symbol_name(symbol: u32) -> String {\n    format!(\"0x{:02X}\", symbol)\n}\n\n");
+    }
+
+    out.push_str(&RUNTIME.replace("ROOT", &generator.functions[program.root_index()]));
+    for i in 0..program.rules().len() {
+        out.push('\n');
+        out.push_str(&generator.rule(i));
+    }
+    out.push_str("}\n");
+    out
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let (grammar_path, out_path) = match args {
+        [grammar_path] => (grammar_path.as_str(), None),
+        [grammar_path, out_path] => (grammar_path.as_str(), Some(out_path.as_str())),
+        _ => {
+            eprintln!("usage: parsergen descent <grammar.pglsf> [<out.rs>]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let (grammar, source) = match grammar::load(grammar_path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let program = match Program::compile(&grammar) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}", error.describe(grammar_path, &source));
+            return ExitCode::FAILURE
+        }
+    };
+
+    let title = std::path::Path::new(grammar_path).file_name().map_or(grammar_path.to_string(), |name| name.to_string_lossy().to_string());
+    let source = generate(&program, &title);
+    match out_path {
+        None => {
+            print!("{}", source);
+            ExitCode::SUCCESS
+        },
+        Some(out_path) => match fs::write(out_path, source) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("{}: {}", out_path, error);
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,84 @@
+use crate::descent::*;
+use crate::grammar::read;
+
+#[test]
+fn test_generate() {
+    let source = "\
+symbols A, B, C;
+grammar
+	root = list(item, C);
+	item = A B | A C | B;
+	list(x, sep) = x *(sep x);
+";
+    let program = Program::compile(&read(source).unwrap()).unwrap();
+    let code = generate(&program, "list.pglsf");
+    assert!(code.starts_with("// Generated by parsergen from list.pglsf. Do not edit.\n\npub const A: u32 = 0;\n"));
+    assert!(code.contains("    if parser.parse_root(&mut out) && parser.pos == input.len() {\n"));
+
+    // The template instance gets a function named after it.
+    assert!(code.contains("    // list<item,C>\n    fn parse_list_item_C(&mut self, parent: &mut Vec<Tree>) -> bool {\n"));
+
+    // The repetition is decided by the next symbol, the union is not.
+    assert!(code.contains("                Some(C) => if !(\n"));
+    assert!(code.contains("\
+            (
+                self.symbol(A, out)
+                && self.symbol(B, out)
+            )
+            || self.back(pos, len, out) && (
+"));
+}
+
+#[test]
+fn test_generate_annotated() {
+    let source = "\
+symbols binary;
+grammar
+	number = first:0x30...0x39 *digit;
+	@inline digit = 0x30...0x39;
+	@hidden unused = 0x20;
+";
+    let program = Program::compile(&read(source).unwrap()).unwrap();
+    let code = generate(&program, "number.pglsf");
+    assert!(!code.contains("pub const"));
+    assert!(code.contains("let at = out.len();\n"));
+    assert!(code.contains("self.range(0x30, 0x39, out) && self.label(at, \"first\", out)\n"));
+    assert!(code.contains("    fn parse_digit(&mut self, out: &mut Vec<Tree>) -> bool {\n        self.range(0x30, 0x39, out)\n    }\n"));
+    assert!(code.contains("    fn parse_unused(&mut self, _parent: &mut Vec<Tree>) -> bool {\n"));
+}
+
+// Compiles the code as a library with rustc. Returns the errors, if it does not compile.
+fn compile(code: &str, name: &str) -> Result<(), String> {
+    let dir = std::env::temp_dir().join(format!("parsergen-descent-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.rs", name));
+    std::fs::write(&path, code).unwrap();
+
+    let output = std::process::Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+        .args(["--crate-type", "lib", "--edition", "2021", "--emit", "metadata", "--out-dir"])
+        .arg(&dir)
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+#[test]
+fn test_generated_code_compiles() {
+    let sources = [
+        ("repeat", "symbols A, B, C;\ngrammar\n\troot = A *(B | C);\n"),
+        ("pglsf", include_str!("../../../languages/pglsf.pglsf"))
+    ];
+
+    for (name, source) in sources {
+        let program = Program::compile(&read(source).unwrap()).unwrap();
+        let code = generate(&program, &format!("{}.pglsf", name));
+        if let Err(errors) = compile(&code, name) {
+            panic!("the parser for {}.pglsf does not compile:\n{}", name, errors);
+        }
+    }
+}
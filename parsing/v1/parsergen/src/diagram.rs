@@ -0,0 +1,315 @@
+// diagram.rs
+//
+// parsergen diagram <grammar.pglsf> <out_dir>
+//
+// Draws every rule of a grammar as an SVG railroad diagram, <out_dir>/<rule>.svg, and writes
+// <out_dir>/index.html, a reference page with each rule's text and diagram in grammar order. Rule
+// references in the page link to the rule's own diagram.
+//
+// Also writes <out_dir>/rules.dot, a Graphviz graph of which rules refer to which. Rules that are
+// recursive, alone or through other rules, are filled and their cycles drawn in red.
+//
+
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::grammar::{self, analysis, Expanded, Expr, Grammar};
+
+const CHAR_WIDTH: usize = 8;
+const BOX_HEIGHT: usize = 22;
+const GAP: usize = 10;
+const ARC: usize = 10;
+const ROW_GAP: usize = 8;
+const MARGIN: usize = 20;
+
+// A laid out piece of a diagram. Lines enter on the left and leave on the right, both at the
+// baseline, which is up above the bottom edge and down below the top edge.
+enum Diagram {
+    Terminal(String),
+    NonTerminal(String),
+    Skip,
+    Seq(Vec<Diagram>),
+
+    // The first alternative is drawn on the baseline, the others stacked below it.
+    Choice(Vec<Diagram>),
+
+    // One or more, with the way back drawn below.
+    Loop(Box<Diagram>)
+}
+
+use Diagram::*;
+
+impl Diagram {
+    fn from_expr(expr: &Expr) -> Diagram {
+        match expr.expanded() {
+            Expanded::Symbol(name) => Terminal(name.clone()),
+            Expanded::Byte(byte) => Terminal(byte_text(byte)),
+            Expanded::Range(first, last) => match (first.expanded(), last.expanded()) {
+                (Expanded::Byte(first), Expanded::Byte(last)) => Terminal(format!("{} ... {}", byte_text(first), byte_text(last))),
+                (Expanded::Symbol(first), Expanded::Symbol(last)) => Terminal(format!("{} ... {}", first, last)),
+                _ => panic!()
+            },
+            Expanded::Rule(name) => NonTerminal(name.clone()),
+            Expanded::Seq(items) => Seq(items.iter().map(Diagram::from_expr).collect()),
+            Expanded::Union(items) => Choice(items.iter().map(Diagram::from_expr).collect()),
+            Expanded::Opt(inner) => Choice(vec![Diagram::from_expr(inner), Skip]),
+            Expanded::Star(inner) => Choice(vec![Loop(Box::new(Diagram::from_expr(inner))), Skip]),
+            Expanded::Plus(inner) => Loop(Box::new(Diagram::from_expr(inner)))
+        }
+    }
+
+    fn width(&self) -> usize {
+        match self {
+            Terminal(text) | NonTerminal(text) => text.chars().count() * CHAR_WIDTH + 2 * GAP,
+            Skip => 0,
+            Seq(items) => items.iter().map(Diagram::width).sum::<usize>() + GAP * items.len().saturating_sub(1),
+            Choice(items) => items.iter().map(Diagram::width).max().unwrap_or(0) + 4 * ARC,
+            Loop(inner) => inner.width() + 4 * ARC
+        }
+    }
+
+    fn up(&self) -> usize {
+        match self {
+            Terminal(_) | NonTerminal(_) => BOX_HEIGHT / 2,
+            Skip => 0,
+            Seq(items) => items.iter().map(Diagram::up).max().unwrap_or(0),
+            Choice(items) => items.first().map_or(0, Diagram::up),
+            Loop(inner) => inner.up()
+        }
+    }
+
+    fn down(&self) -> usize {
+        match self {
+            Terminal(_) | NonTerminal(_) => BOX_HEIGHT / 2,
+            Skip => 0,
+            Seq(items) => items.iter().map(Diagram::down).max().unwrap_or(0),
+            Choice(items) => {
+                let steps: usize = items.windows(2).map(|pair| row_step(&pair[0], &pair[1])).sum();
+                steps + items.last().map_or(0, Diagram::down)
+            },
+            Loop(inner) => (inner.down() + ROW_GAP + ARC).max(2 * ARC)
+        }
+    }
+
+    // Draws the diagram with its entry at (x, y).
+    fn render(&self, x: usize, y: usize, out: &mut String) {
+        match self {
+            Terminal(text) | NonTerminal(text) => {
+                let width = self.width();
+                let (class, radius, link) = match self {
+                    Terminal(_) => ("terminal", ARC, None),
+                    _ => ("nonterminal", 0, Some(text))
+                };
+
+                if let Some(name) = link {
+                    out.push_str(&format!("<a href=\"#{}\">", escape(name)));
+                }
+                out.push_str(&format!(
+                    "<g class=\"{}\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/><text x=\"{}\" y=\"{}\">{}</text></g>",
+                    class, x, y - BOX_HEIGHT / 2, width, BOX_HEIGHT, radius, x + width / 2, y + 4, escape(text)
+                ));
+                if link.is_some() {
+                    out.push_str("</a>");
+                }
+                out.push('\n');
+            },
+            Skip => (),
+            Seq(items) => {
+                let mut item_x = x;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        line(out, item_x, y, item_x + GAP, y);
+                        item_x += GAP;
+                    }
+                    item.render(item_x, y, out);
+                    item_x += item.width();
+                }
+            },
+            Choice(items) => {
+                let width = self.width();
+                let inner_width = width - 4 * ARC;
+                let right = x + width;
+                let mut row_y = y;
+
+                for (i, item) in items.iter().enumerate() {
+                    if i == 0 {
+                        line(out, x, y, x + 2 * ARC, y);
+                        line(out, right - 2 * ARC, y, right, y);
+                    } else {
+                        row_y += row_step(&items[i - 1], item);
+
+                        // Down from the entry on the left, and back up to the exit on the right.
+                        out.push_str(&format!(
+                            "<path d=\"M{} {} q{} 0 {} {} v{} q0 {} {} {}\"/>\n",
+                            x, y, ARC, ARC, ARC, row_y - y - 2 * ARC, ARC, ARC, ARC
+                        ));
+                        out.push_str(&format!(
+                            "<path d=\"M{} {} q{} 0 {} -{} v-{} q0 -{} {} -{}\"/>\n",
+                            right - 2 * ARC, row_y, ARC, ARC, ARC, row_y - y - 2 * ARC, ARC, ARC, ARC
+                        ));
+                    }
+
+                    item.render(x + 2 * ARC, row_y, out);
+                    line(out, x + 2 * ARC + item.width(), row_y, x + 2 * ARC + inner_width, row_y);
+                }
+            },
+            Loop(inner) => {
+                let width = self.width();
+                let right = x + width;
+                let bottom = y + self.down();
+
+                line(out, x, y, x + 2 * ARC, y);
+                inner.render(x + 2 * ARC, y, out);
+                line(out, x + 2 * ARC + inner.width(), y, right, y);
+
+                // The way back, from just before the exit to just after the entry.
+                out.push_str(&format!(
+                    "<path d=\"M{} {} q{} 0 {} {} v{} q0 {} -{} {} H{} q-{} 0 -{} -{} v-{} q0 -{} {} -{}\"/>\n",
+                    right - 2 * ARC, y, ARC, ARC, ARC, bottom - y - 2 * ARC, ARC, ARC, ARC,
+                    x + 2 * ARC, ARC, ARC, ARC, bottom - y - 2 * ARC, ARC, ARC, ARC
+                ));
+            }
+        }
+    }
+}
+
+// How far below one row of a choice the next row's baseline is. The rows must be far enough apart
+// for the arcs that lead to them.
+fn row_step(above: &Diagram, below: &Diagram) -> usize {
+    (above.down() + ROW_GAP + below.up()).max(2 * ARC)
+}
+
+fn line(out: &mut String, x1: usize, y1: usize, x2: usize, y2: usize) {
+    if (x1, y1) != (x2, y2) {
+        out.push_str(&format!("<path d=\"M{} {} L{} {}\"/>\n", x1, y1, x2, y2));
+    }
+}
+
+fn byte_text(byte: u8) -> String {
+    match byte {
+        b'\'' | b'\\' => format!("0x{:02X}", byte),
+        0x21..=0x7E => format!("'{}'", byte as char),
+        _ => format!("0x{:02X}", byte)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const STYLE: &str = "\
+path { stroke: #333; stroke-width: 2; fill: none; }
+rect { stroke: #333; stroke-width: 2; fill: #fff; }
+.terminal rect { fill: #eef; }
+text { font: 13px monospace; text-anchor: middle; }
+a text { fill: #04a; }";
+
+// The railroad diagram of a rule as a standalone SVG document.
+pub fn rule_svg(expr: &Expr) -> String {
+    let diagram = Diagram::from_expr(expr);
+    let width = diagram.width() + 2 * MARGIN;
+    let height = diagram.up() + diagram.down() + 2 * MARGIN;
+    let y = MARGIN + diagram.up();
+
+    let mut out = String::new();
+    out.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n<style>\n{}\n</style>\n",
+        width, height, width, height, STYLE
+    ));
+
+    // The start and end of the rule are marked by short bars.
+    out.push_str(&format!("<path d=\"M{} {} v{} M{} {} h{}\"/>\n", MARGIN / 2, y - 6, 12, MARGIN / 2, y, MARGIN / 2));
+    diagram.render(MARGIN, y, &mut out);
+    out.push_str(&format!("<path d=\"M{} {} h{} M{} {} v{}\"/>\n", MARGIN + diagram.width(), y, MARGIN / 2, width - MARGIN / 2, y - 6, 12));
+
+    out.push_str("</svg>\n");
+    out
+}
+
+// A Graphviz graph of the rules and the references between them.
+pub fn rules_dot(grammar: &Grammar) -> String {
+    let references = analysis::rule_references(grammar);
+    let components = analysis::rule_components(grammar);
+    let component_of = |i: usize| components.iter().position(|component| component.contains(&i)).unwrap();
+    let is_recursive = |i: usize| components[component_of(i)].len() > 1 || references[i].contains(&i);
+
+    let mut out = String::from("digraph rules {\n\tnode [shape=box, fontname=monospace];\n");
+    for (i, rule) in grammar.rules.iter().enumerate() {
+        if is_recursive(i) {
+            out.push_str(&format!("\t\"{}\" [style=filled, fillcolor=\"#fcc\"];\n", rule.name));
+        } else {
+            out.push_str(&format!("\t\"{}\";\n", rule.name));
+        }
+    }
+
+    for (i, targets) in references.iter().enumerate() {
+        for &j in targets {
+            let attributes = if component_of(i) == component_of(j) { " [color=red]" } else { "" };
+            out.push_str(&format!("\t\"{}\" -> \"{}\"{};\n", grammar.rules[i].name, grammar.rules[j].name, attributes));
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+// The reference page: every rule's text and diagram, in grammar order.
+pub fn index_html(grammar: &Grammar, source: &str, title: &str) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; }}\nh2 {{ font-family: monospace; }}\n</style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        title = escape(title)
+    );
+
+    for rule in &grammar.rules {
+        let text = &source[rule.name_span.start..rule.expr.span.end];
+        out.push_str(&format!("<h2 id=\"{}\">{}</h2>\n<pre>{};</pre>\n", escape(&rule.name), escape(&rule.name), escape(text)));
+        out.push_str(&rule_svg(&rule.expr));
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let (grammar_path, out_dir) = match args {
+        [grammar_path, out_dir] => (grammar_path.as_str(), Path::new(out_dir.as_str())),
+        _ => {
+            eprintln!("usage: parsergen diagram <grammar.pglsf> <out_dir>");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let (grammar, source) = match grammar::load(grammar_path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let title = Path::new(grammar_path).file_name().map_or(grammar_path.to_string(), |name| name.to_string_lossy().to_string());
+    let mut files = vec![
+        ("index.html".to_string(), index_html(&grammar, &source, &title)),
+        ("rules.dot".to_string(), rules_dot(&grammar))
+    ];
+    for rule in &grammar.rules {
+        files.push((format!("{}.svg", rule.name), rule_svg(&rule.expr)));
+    }
+
+    let written = fs::create_dir_all(out_dir).and_then(|_| {
+        files.iter().try_for_each(|(name, contents)| fs::write(out_dir.join(name), contents))
+    });
+
+    match written {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}: {}", out_dir.display(), error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,36 @@
+use crate::diagram::*;
+use crate::grammar::read;
+
+const PGLSF: &str = include_str!("../../../languages/pglsf.pglsf");
+
+#[test]
+fn test_rules_dot_marks_recursion() {
+    let grammar = read(PGLSF).unwrap();
+    let dot = rules_dot(&grammar);
+    assert!(dot.contains("\t\"rule_expr\" [style=filled, fillcolor=\"#fcc\"];\n"));
+    assert!(dot.contains("\t\"rule_expr\" -> \"rule_seq_expr\" [color=red];\n"));
+    assert!(dot.contains("\t\"rule_paren_expr\" -> \"rule_expr\" [color=red];\n"));
+    assert!(dot.contains("\t\"whitespace\" -> \"whitespace\" [color=red];\n"));
+    assert!(dot.contains("\t\"letter\";\n"));
+    assert!(dot.contains("\t\"letter\" -> \"lower_letter\";\n"));
+}
+
+#[test]
+fn test_rule_svg() {
+    let grammar = read("symbols A, B;\ngrammar\n\troot = ?A *item;\n\titem = A ... B | root;\n").unwrap();
+    let svg = rule_svg(&grammar.rules[0].expr);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.contains(">A</text>"));
+    assert!(svg.contains("<a href=\"#item\">"));
+
+    let svg = rule_svg(&grammar.rules[1].expr);
+    assert!(svg.contains(">A ... B</text>"));
+    assert!(svg.contains("<a href=\"#root\">"));
+}
+
+#[test]
+fn test_index_html() {
+    let source = "symbols A;\ngrammar\n\troot = +A;\n";
+    let html = index_html(&read(source).unwrap(), source, "a.pglsf");
+    assert!(html.contains("<h2 id=\"root\">root</h2>\n<pre>root = +A;</pre>\n<svg"));
+}
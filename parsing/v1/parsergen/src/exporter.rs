@@ -0,0 +1,178 @@
+// exporter.rs
+//
+// parsergen export --to tree-sitter|antlr4|ebnf <grammar.pglsf> [<out>]
+//
+// Writes a grammar for other tools: a grammar.js for tree-sitter, e.g. so that editors can
+// highlight the languages a .pglsf defines; a grammar for ANTLR 4; or the EBNF of the W3C, for
+// documents. The name of the exported grammar comes from the name of the file.
+//
+// What the target cannot say the way pglsf does is reported with where it is in the grammar, and
+// the export is written anyway, as it is still the closest the target gets. All three targets read
+// characters, so bytes past ASCII in a binary grammar become the characters with those code points.
+// Neither tree-sitter nor ANTLR can return more than one parse of an input, so an ambiguous grammar
+// is reported too. Whether a grammar is ambiguous cannot be decided in general; it is found out by
+// parsing random sentences of the grammar, as parsergen fuzz generates them.
+//
+
+pub mod tree_sitter;
+pub mod antlr;
+pub mod ebnf;
+
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::fuzzer::{format_sentence, SentenceGenerator};
+use crate::grammar::program::Program;
+use crate::grammar::{self, Expanded, Expr, ExprKind, Grammar, GrammarError, Span};
+use crate::tester::{run, Outcome};
+
+// How many random sentences are parsed to look for ambiguity, and how deep they go.
+const AMBIGUITY_SAMPLES: usize = 200;
+const AMBIGUITY_DEPTH: usize = 8;
+
+pub struct Export {
+    pub text: String,
+
+    // What the text does not say the way the grammar does.
+    pub issues: Vec<GrammarError>
+}
+
+// Exports the grammar to the target: tree-sitter, antlr4 or ebnf. name is the name the exported
+// grammar gets.
+pub fn export(grammar: &Grammar, to: &str, name: &str) -> Result<Export, GrammarError> {
+    match to {
+        "tree-sitter" => Ok(tree_sitter::export(grammar, name)),
+        "antlr4" => Ok(antlr::export(grammar, name)),
+        "ebnf" => Ok(ebnf::export(grammar)),
+        _ => Err(GrammarError::new(format!("unknown target '{}': expected tree-sitter, antlr4 or ebnf", to), Span::default()))
+    }
+}
+
+// A name the targets accept: letters, digits and '_'. Qualified names such as common.lower_name,
+// and template instances such as list<item,COMMA>, become common_lower_name and list_item_comma.
+pub fn identifier(name: &str) -> String {
+    let mut result = String::new();
+    for c in name.chars() {
+        match c {
+            'a'..='z' | '0'..='9' | '_' => result.push(c),
+            'A'..='Z' => result.push(c.to_ascii_lowercase()),
+            _ => if !result.ends_with('_') {
+                result.push('_')
+            }
+        }
+    }
+    result.trim_end_matches('_').to_string()
+}
+
+// Calls visit on the expression and everything in it.
+fn walk(expr: &Expr, visit: &mut dyn FnMut(&Expr)) {
+    visit(expr);
+    match &expr.kind {
+        ExprKind::Seq(items) | ExprKind::Union(items) => for item in items {
+            walk(item, visit);
+        },
+        ExprKind::Opt(inner) | ExprKind::Star(inner) | ExprKind::Plus(inner) => walk(inner, visit),
+        ExprKind::Range(first, last) => {
+            walk(first, visit);
+            walk(last, visit);
+        },
+        ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Rule(_) | ExprKind::Call(_, _) => ()
+    }
+}
+
+// The bytes past ASCII in a binary grammar, which the target reads as characters instead.
+fn non_ascii(grammar: &Grammar, target: &str) -> Vec<GrammarError> {
+    let mut issues = Vec::new();
+    for rule in &grammar.rules {
+        walk(&rule.expr, &mut |expr| match expr.kind {
+            ExprKind::Byte(byte) if byte >= 0x80 => issues.push(GrammarError::new(
+                format!("byte 0x{:02X} is past ASCII, and {} reads characters, so it becomes U+{:04X}", byte, target, byte),
+                expr.span
+            )),
+            _ => ()
+        });
+    }
+    issues
+}
+
+// Looks for an input with more than one parse, which the target would parse only one way. Grammars
+// that do not compile cannot be parsed, so that is reported instead.
+fn ambiguity(grammar: &Grammar, target: &str) -> Option<GrammarError> {
+    let program = match Program::compile(grammar) {
+        Ok(program) => program,
+        Err(error) => return Some(GrammarError::new(format!("{}, so it was not checked for ambiguity", error.message), error.span))
+    };
+    let mut generator = SentenceGenerator::new(grammar, AMBIGUITY_DEPTH, 0);
+    for _ in 0..AMBIGUITY_SAMPLES {
+        let sentence = generator.sentence()?;
+        if let Outcome::Ambiguous = run(&program, &sentence) {
+            let message = format!(
+                "the grammar is ambiguous, e.g. for \"{}\", and {} keeps only one of the parses",
+                format_sentence(grammar, &sentence),
+                target
+            );
+            return Some(GrammarError::new(message, grammar.root().unwrap().name_span))
+        }
+    }
+    None
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let usage = || {
+        eprintln!("usage: parsergen export --to tree-sitter|antlr4|ebnf <grammar.pglsf> [<out>]");
+        ExitCode::FAILURE
+    };
+    let mut to = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" => match args.next() {
+                Some(target) => to = Some(target.as_str()),
+                None => return usage()
+            },
+            _ => paths.push(arg.as_str())
+        }
+    }
+    let (Some(to), [path, out_path @ ..]) = (to, paths.as_slice()) else { return usage() };
+    if out_path.len() > 1 || !matches!(to, "tree-sitter" | "antlr4" | "ebnf") {
+        return usage()
+    }
+
+    let (grammar, source) = match grammar::load(path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+    let name = Path::new(path).file_stem().map_or("grammar".to_string(), |stem| identifier(&stem.to_string_lossy()));
+    let export = match export(&grammar, to, &name) {
+        Ok(export) => export,
+        Err(error) => {
+            eprintln!("{}", error.describe(path, &source));
+            return ExitCode::FAILURE
+        }
+    };
+    for issue in &export.issues {
+        eprintln!("{}", issue.describe(path, &source));
+    }
+
+    match out_path.first() {
+        Some(out_path) => match fs::write(out_path, export.text) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("{}: {}", out_path, error);
+                ExitCode::FAILURE
+            }
+        },
+        None => {
+            print!("{}", export.text);
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
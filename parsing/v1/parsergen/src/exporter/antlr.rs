@@ -0,0 +1,145 @@
+// antlr.rs
+//
+// Writes a grammar for ANTLR 4:
+//
+//     grammar pairs;
+//
+//     root_eof : root EOF ;
+//     root : ws? (pair SEMICOLON ws?)* ;
+//     pair : key=name ws? EQUAL_SIGN ws? value ;
+//     ...
+//     EQUAL_SIGN : '=' ;
+//
+// The bytes of a binary grammar become lexer rules of one character each, named as parsergen
+// import --symbols names them, so that the parser rules read the input the way pglsf does; ranges
+// become sets of those. A grammar with named symbols becomes a parser grammar with the symbols as
+// its tokens. The skip rule is written out where it was inserted, so that it is skipped as often
+// as pglsf skips it. The root is wrapped in a rule that ends with EOF, as ANTLR otherwise stops
+// wherever the root does. ANTLR has no @hidden or @inline, so those rules get nodes of their own.
+//
+// ANTLR decides between alternatives by looking ahead as far as it needs to, so any grammar that
+// pglsf reads works, but where the input is ambiguous it takes the first alternative that matches;
+// ambiguity is reported.
+//
+
+use super::*;
+use crate::grammar::analysis::left_recursion;
+use crate::grammar::Symbols;
+use crate::importer::symbol_name;
+
+// The words ANTLR keeps for itself, which rules are not named.
+const KEYWORDS: [&str; 14] = [
+    "catch", "channels", "finally", "fragment", "grammar", "import", "lexer", "locals", "mode", "options", "parser", "returns",
+    "throws", "tokens"
+];
+
+pub fn export(grammar: &Grammar, name: &str) -> Export {
+    let mut issues = non_ascii(grammar, "ANTLR");
+    if let Some(cycle) = left_recursion(grammar).filter(|cycle| cycle.len() > 2) {
+        let rule = grammar.rule(&cycle[0]).unwrap();
+        let message = format!("rule '{}' is left-recursive through other rules ({}), which ANTLR cannot do", rule.name, cycle.join(" -> "));
+        issues.push(GrammarError::new(message, rule.name_span));
+    }
+    issues.extend(ambiguity(grammar, "ANTLR"));
+
+    let mut text = match &grammar.symbols {
+        Symbols::Named(decls) => {
+            let tokens: Vec<&str> = decls.iter().map(|decl| decl.name.as_str()).collect();
+            format!("parser grammar {};\n\ntokens {{ {} }}\n\n", name, tokens.join(", "))
+        },
+        Symbols::Binary => format!("grammar {};\n\n", name)
+    };
+
+    let root = grammar.root().unwrap();
+    text += &format!("{}_eof : {} EOF ;\n", identifier(&root.name), rule_name(&root.name));
+    for rule in [root].into_iter().chain(grammar.rules.iter().filter(|rule| rule.name != root.name)) {
+        text += &format!("{} : {} ;\n", rule_name(&rule.name), expr_text(grammar, &rule.expr));
+    }
+
+    if grammar.is_binary() {
+        let mut used = [false; 256];
+        for rule in &grammar.rules {
+            walk(&rule.expr, &mut |expr| match &expr.kind {
+                ExprKind::Byte(byte) => used[*byte as usize] = true,
+                ExprKind::Range(first, last) => if let (ExprKind::Byte(first), ExprKind::Byte(last)) = (&first.kind, &last.kind) {
+                    used[*first as usize..=*last as usize].fill(true);
+                },
+                _ => ()
+            });
+        }
+        text += "\n";
+        for byte in (0..=255).filter(|&byte| used[byte as usize]) {
+            text += &format!("{} : '{}' ;\n", symbol_name(byte as char), literal(byte));
+        }
+    }
+
+    Export { text, issues }
+}
+
+fn rule_name(name: &str) -> String {
+    let name = identifier(name);
+    match KEYWORDS.contains(&name.as_str()) {
+        true => format!("{}_", name),
+        false => name
+    }
+}
+
+fn expr_text(grammar: &Grammar, expr: &Expr) -> String {
+    let text = match expr.expanded() {
+        Expanded::Symbol(name) => name.clone(),
+        Expanded::Byte(byte) => symbol_name(byte as char),
+        Expanded::Rule(name) => rule_name(name),
+        Expanded::Range(first, last) => {
+            let names: Vec<String> = match (first.expanded(), last.expanded()) {
+                (Expanded::Byte(first), Expanded::Byte(last)) => (first..=last).map(|byte| symbol_name(byte as char)).collect(),
+                (Expanded::Symbol(first), Expanded::Symbol(last)) => {
+                    let Symbols::Named(decls) = &grammar.symbols else { unreachable!() };
+                    let (first, last) = (grammar.symbol_index(first).unwrap(), grammar.symbol_index(last).unwrap());
+                    decls[first..=last].iter().map(|decl| decl.name.clone()).collect()
+                },
+                _ => unreachable!("ranges are checked when a grammar is read")
+            };
+            format!("({})", names.join(" | "))
+        },
+        Expanded::Seq(items) => items.iter()
+            .map(|item| match item.kind {
+                ExprKind::Seq(_) | ExprKind::Union(_) => format!("({})", expr_text(grammar, item)),
+                _ => expr_text(grammar, item)
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        Expanded::Union(items) => items.iter()
+            .map(|item| match item.kind {
+                ExprKind::Union(_) => format!("({})", expr_text(grammar, item)),
+                _ => expr_text(grammar, item)
+            })
+            .collect::<Vec<_>>()
+            .join(" | "),
+        Expanded::Opt(inner) => format!("{}?", operand_text(grammar, inner)),
+        Expanded::Star(inner) => format!("{}*", operand_text(grammar, inner)),
+        Expanded::Plus(inner) => format!("{}+", operand_text(grammar, inner))
+    };
+    match &expr.label {
+        // ANTLR does not allow a label with the name of a rule.
+        Some(label) if grammar.rules.iter().any(|rule| rule_name(&rule.name) == label.name) => format!("{}_={}", label.name, text),
+        Some(label) => format!("{}={}", label.name, text),
+        None => text
+    }
+}
+
+// The operand of '?', '*' or '+'.
+fn operand_text(grammar: &Grammar, expr: &Expr) -> String {
+    match (&expr.kind, &expr.label) {
+        (ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Rule(_) | ExprKind::Range(_, _), None) => expr_text(grammar, expr),
+        _ => format!("({})", expr_text(grammar, expr))
+    }
+}
+
+// A byte in an ANTLR string: printable ASCII as it is, anything else as \uNNNN.
+fn literal(byte: u8) -> String {
+    match byte {
+        b'\'' | b'\\' => format!("\\{}", byte as char),
+        0x20..=0x7E => (byte as char).to_string(),
+        _ => format!("\\u{:04X}", byte)
+    }
+}
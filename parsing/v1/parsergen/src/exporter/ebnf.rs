@@ -0,0 +1,87 @@
+// ebnf.rs
+//
+// Writes a grammar in the EBNF of the W3C, as parsergen import --from ebnf reads it:
+//
+//     root ::= ws? (pair ';' ws?)*
+//     pair ::= name ws? '=' ws? value
+//     name ::= letter+
+//
+// The rules are written as the grammar is read, with the skip rule where it was inserted, since
+// the EBNF has no skipping of its own; that is also where the grammar says what it skips. Bytes are
+// written as strings where they are printable ASCII and as #xNN otherwise. The EBNF has no symbols
+// that come from outside the grammar, so the names of declared symbols are listed in a comment
+// above the rules, and used as they are. Labels, @hidden, @inline and @token say nothing about
+// which inputs the grammar accepts, and are left out.
+//
+
+use super::*;
+use crate::grammar::Symbols;
+
+pub fn export(grammar: &Grammar) -> Export {
+    let mut text = String::new();
+    if let Symbols::Named(decls) = &grammar.symbols {
+        let names: Vec<&str> = decls.iter().map(|decl| decl.name.as_str()).collect();
+        text += &format!("/* The symbols, which come from outside the grammar: {} */\n\n", names.join(", "));
+    }
+
+    let root = grammar.root().unwrap();
+    for rule in [root].into_iter().chain(grammar.rules.iter().filter(|rule| rule.name != root.name)) {
+        text += &format!("{} ::= {}\n", identifier(&rule.name), expr_text(grammar, &rule.expr));
+    }
+
+    Export { text, issues: non_ascii(grammar, "the EBNF") }
+}
+
+fn expr_text(grammar: &Grammar, expr: &Expr) -> String {
+    match expr.expanded() {
+        Expanded::Symbol(name) => name.clone(),
+        Expanded::Byte(byte) => match byte {
+            b'\'' => "\"'\"".to_string(),
+            0x20..=0x7E => format!("'{}'", byte as char),
+            _ => format!("#x{:02X}", byte)
+        },
+        Expanded::Rule(name) => identifier(name),
+        Expanded::Range(first, last) => match (first.expanded(), last.expanded()) {
+            (Expanded::Byte(first), Expanded::Byte(last)) => format!("[{}-{}]", class_char(first), class_char(last)),
+            (Expanded::Symbol(first), Expanded::Symbol(last)) => {
+                let Symbols::Named(decls) = &grammar.symbols else { unreachable!() };
+                let (first, last) = (grammar.symbol_index(first).unwrap(), grammar.symbol_index(last).unwrap());
+                format!("({})", decls[first..=last].iter().map(|decl| decl.name.as_str()).collect::<Vec<_>>().join(" | "))
+            },
+            _ => unreachable!("ranges are checked when a grammar is read")
+        },
+        Expanded::Seq(items) => items.iter()
+            .map(|item| match item.kind {
+                ExprKind::Seq(_) | ExprKind::Union(_) => format!("({})", expr_text(grammar, item)),
+                _ => expr_text(grammar, item)
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        Expanded::Union(items) => items.iter()
+            .map(|item| match item.kind {
+                ExprKind::Union(_) => format!("({})", expr_text(grammar, item)),
+                _ => expr_text(grammar, item)
+            })
+            .collect::<Vec<_>>()
+            .join(" | "),
+        Expanded::Opt(inner) => format!("{}?", operand_text(grammar, inner)),
+        Expanded::Star(inner) => format!("{}*", operand_text(grammar, inner)),
+        Expanded::Plus(inner) => format!("{}+", operand_text(grammar, inner))
+    }
+}
+
+// The operand of '?', '*' or '+'.
+fn operand_text(grammar: &Grammar, expr: &Expr) -> String {
+    match expr.kind {
+        ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Rule(_) | ExprKind::Range(_, _) => expr_text(grammar, expr),
+        _ => format!("({})", expr_text(grammar, expr))
+    }
+}
+
+// A byte of a class: letters and digits as they are, anything else as #xNN.
+fn class_char(byte: u8) -> String {
+    match byte.is_ascii_alphanumeric() {
+        true => (byte as char).to_string(),
+        false => format!("#x{:02X}", byte)
+    }
+}
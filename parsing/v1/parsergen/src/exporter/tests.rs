@@ -0,0 +1,70 @@
+use crate::exporter::*;
+use crate::grammar::program::Program;
+use crate::importer::{self, Alphabet};
+use crate::tester::{input_symbols, run, Outcome};
+
+const PAIRS: &str = "symbols\n\tbinary;\n\ngrammar\n\t@skip ws;\n\troot = *(pair 0x3B);\n\tpair = key:name 0x3D value;\n\t@token name = +letter;\n\tletter = 0x61 ... 0x7A;\n\t@inline value = name | 0x30 ... 0x39;\n\t@hidden ws = +(0x20 | 0x0A);\n";
+
+// The messages of the issues, each with the line and column it is at.
+fn issues(source: &str, to: &str) -> Vec<String> {
+    export(&grammar::read(source).unwrap(), to, "test").unwrap()
+        .issues
+        .iter()
+        .map(|issue| issue.describe("in", source))
+        .collect()
+}
+
+#[test]
+fn test_export_tree_sitter() {
+    let export = export(&grammar::read(PAIRS).unwrap(), "tree-sitter", "pairs").unwrap();
+    assert_eq!(export.text, "// The tree-sitter grammar of pairs, exported by parsergen.\nmodule.exports = grammar({\n  name: 'pairs',\n\n  extras: $ => [$._ws],\n\n  rules: {\n    root: $ => repeat(seq($.pair, ';')),\n    pair: $ => seq(field('key', $.name), '=', $._value),\n    name: $ => token(repeat1(/[a-z]/)),\n    _value: $ => choice($.name, /[0-9]/),\n    _ws: $ => repeat1(choice('\\x20', '\\x0A')),\n  }\n});\n");
+    assert!(export.issues.is_empty());
+
+    // Named symbols come from an external scanner, and rules that are not LR(1) are conflicts.
+    let text = tree_sitter::export(&grammar::read("symbols\n\tA, B, C;\n\ngrammar\n\troot = a C | b C B;\n\ta = A;\n\tb = A ... B;\n").unwrap(), "test").text;
+    assert!(text.contains("\n  externals: $ => [$.A, $.B, $.C],\n\n  conflicts: $ => [\n    [$.a, $.b],\n  ],\n"));
+    assert!(text.contains("\n    b: $ => choice($.A, $.B),\n"));
+
+    let source = "symbols\n\tbinary;\n\ngrammar\n\troot = h opt 0x7A | word;\n\t@token word = 0x77 ?word;\n\topt = ?0x62;\n\t@hidden h = a 0xC3;\n\ta = 0x61;\n";
+    assert_eq!(issues(source, "tree-sitter"), [
+        "in:8:16: byte 0xC3 is past ASCII, and tree-sitter reads characters, so it becomes U+00C3",
+        "in:7:2: rule 'opt' can match nothing, which tree-sitter only allows of the root",
+        "in:8:10: rule 'h' is @hidden, but tree-sitter keeps the nodes of the rules it uses, such as 'a'",
+        "in:6:22: @token rule 'word' uses rule 'word' recursively, which a tree-sitter token cannot"
+    ]);
+}
+
+#[test]
+fn test_export_antlr() {
+    let text = export(&grammar::read(PAIRS).unwrap(), "antlr4", "pairs").unwrap().text;
+    assert!(text.starts_with("grammar pairs;\n\nroot_eof : root EOF ;\nroot : ws? (pair SEMICOLON ws?)* ;\npair : key=name ws? EQUAL_SIGN ws? value ;\nname : letter+ ;\nletter : (LETTER_A | LETTER_B | "));
+    assert!(text.contains("\nws : (SPACE | NEWLINE)+ ;\n\nNEWLINE : '\\u000A' ;\nSPACE : ' ' ;\nDIGIT_0 : '0' ;\n"));
+    assert!(text.ends_with("\nSEMICOLON : ';' ;\nEQUAL_SIGN : '=' ;\nLETTER_A : 'a' ;\nLETTER_B : 'b' ;\nLETTER_C : 'c' ;\nLETTER_D : 'd' ;\nLETTER_E : 'e' ;\nLETTER_F : 'f' ;\nLETTER_G : 'g' ;\nLETTER_H : 'h' ;\nLETTER_I : 'i' ;\nLETTER_J : 'j' ;\nLETTER_K : 'k' ;\nLETTER_L : 'l' ;\nLETTER_M : 'm' ;\nLETTER_N : 'n' ;\nLETTER_O : 'o' ;\nLETTER_P : 'p' ;\nLETTER_Q : 'q' ;\nLETTER_R : 'r' ;\nLETTER_S : 's' ;\nLETTER_T : 't' ;\nLETTER_U : 'u' ;\nLETTER_V : 'v' ;\nLETTER_W : 'w' ;\nLETTER_X : 'x' ;\nLETTER_Y : 'y' ;\nLETTER_Z : 'z' ;\n"));
+
+    // Rules named after keywords, and labels named after rules, are renamed.
+    let text = antlr::export(&grammar::read("symbols\n\tA, B, C, D;\n\ngrammar\n\troot = item:item *(B item);\n\tgrammar = A ... C;\n\titem = grammar | D;\n").unwrap(), "test").text;
+    assert_eq!(text, "parser grammar test;\n\ntokens { A, B, C, D }\n\nroot_eof : root EOF ;\nroot : item_=item (B item)* ;\ngrammar_ : (A | B | C) ;\nitem : grammar_ | D ;\n");
+
+    assert_eq!(issues("symbols\n\tbinary;\n\ngrammar\n\troot = +x;\n\tx = 0x61 | 0x61 0x61;\n", "antlr4"), [
+        "in:5:2: the grammar is ambiguous, e.g. for \"aaaa\", and ANTLR keeps only one of the parses"
+    ]);
+}
+
+#[test]
+fn test_export_ebnf() {
+    let export = export(&grammar::read(PAIRS).unwrap(), "ebnf", "pairs").unwrap();
+    assert_eq!(export.text, "root ::= ws? (pair ';' ws?)*\npair ::= name ws? '=' ws? value\nname ::= letter+\nletter ::= [a-z]\nvalue ::= name ws? | [0-9] ws?\nws ::= (' ' | #x0A)+\n");
+
+    // Reading the export back gives a grammar for the same inputs.
+    let text = importer::import(&export.text, "ebnf", Alphabet::Binary).unwrap();
+    let program = Program::compile(&grammar::read(&text).unwrap()).unwrap();
+    let accepts = |input: &str| matches!(run(&program, &input_symbols(&program, input).unwrap()), Outcome::Accept(_));
+    assert!(accepts(" a = b ;x=1;"));
+    assert!(!accepts("a = b"));
+
+    let text = ebnf::export(&grammar::read("symbols\n\tA, B, C;\n\ngrammar\n\troot = x:A ?(B ... C);\n").unwrap()).text;
+    assert_eq!(text, "/* The symbols, which come from outside the grammar: A, B, C */\n\nroot ::= A (B | C)?\n");
+    assert_eq!(issues("symbols\n\tbinary;\n\ngrammar\n\troot = 0x27 0xFF;\n", "ebnf"), [
+        "in:5:14: byte 0xFF is past ASCII, and the EBNF reads characters, so it becomes U+00FF"
+    ]);
+}
@@ -0,0 +1,251 @@
+// tree_sitter.rs
+//
+// Writes a grammar as the grammar.js of tree-sitter:
+//
+//     module.exports = grammar({
+//       name: 'pairs',
+//       extras: $ => [$._ws],
+//       rules: {
+//         root: $ => repeat(seq($.pair, ';')),
+//         pair: $ => seq(field('key', $.name), '=', $._value),
+//         name: $ => token(repeat1(/[a-z]/)),
+//         ...
+//
+// The @skip rule becomes the extras, which tree-sitter allows between any two tokens, as pglsf
+// skips after every byte and token. The @token rules of a binary grammar become tokens, with the
+// rules they use written into them, since a token cannot refer to rules; the rules that only tokens
+// use are left out. The symbols of a grammar with named symbols come from an external scanner.
+// @hidden and @inline rules get names starting with '_', which tree-sitter leaves out of trees.
+//
+// Where the grammar is not LR(1), tree-sitter needs the rules involved listed as conflicts, so
+// that it parses them by trying each way. They are taken from the conflicts of an LR(1) table,
+// which names the rules that could be reduced; tree-sitter may want other rules in a conflict
+// than those.
+//
+// Reported: rules other than the root that can match nothing, which tree-sitter does not allow;
+// recursion within a token; @hidden rules that use visible rules, whose nodes tree-sitter keeps;
+// tokens that extras may come into, where the symbols are named; and ambiguity.
+//
+
+use super::*;
+use crate::grammar::analysis::{nullable_rules, rule_references};
+use crate::grammar::{Symbols, Visibility};
+use crate::lr::{LrMode, LrTable};
+
+struct Writer<'a> {
+    grammar: &'a Grammar,
+
+    // The name of each rule in grammar.js.
+    names: Vec<String>,
+    issues: Vec<GrammarError>
+}
+
+pub fn export(grammar: &Grammar, name: &str) -> Export {
+    let mut issues = non_ascii(grammar, "tree-sitter");
+    issues.extend(ambiguity(grammar, "tree-sitter"));
+
+    let grammar = &without_skip(grammar);
+    let index = |name: &str| grammar.rules.iter().position(|rule| rule.name == name).unwrap();
+    let root = index(&grammar.root().unwrap().name);
+    let skip = grammar.skip.as_ref().map(|skip| index(&skip.rule));
+
+    // Tokens of a binary grammar are read by tree-sitter's lexer. Those of a grammar with named
+    // symbols cannot be, as the symbols come from the external scanner.
+    let lexed = |i: usize| grammar.is_binary() && grammar.rules[i].token;
+
+    // The rules that are written: those the root and the skip rule use, other than through tokens.
+    let references = rule_references(grammar);
+    let mut written = vec![false; grammar.rules.len()];
+    let mut pending: Vec<usize> = [Some(root), skip].into_iter().flatten().collect();
+    while let Some(i) = pending.pop() {
+        if !written[i] {
+            written[i] = true;
+            if !lexed(i) {
+                pending.extend(&references[i]);
+            }
+        }
+    }
+    let order: Vec<usize> = [root].into_iter()
+        .chain((0..grammar.rules.len()).filter(|&i| i != root && written[i]))
+        .collect();
+
+    let nullable = nullable_rules(grammar);
+    for &i in &order {
+        let rule = &grammar.rules[i];
+        if i != root && nullable[i] {
+            issues.push(GrammarError::new(format!("rule '{}' can match nothing, which tree-sitter only allows of the root", rule.name), rule.name_span));
+        }
+        if rule.visibility == Visibility::Hidden && !lexed(i) {
+            if let Some(&used) = references[i].iter().find(|&&j| grammar.rules[j].visibility == Visibility::Visible) {
+                let message = format!("rule '{}' is @hidden, but tree-sitter keeps the nodes of the rules it uses, such as '{}'", rule.name, grammar.rules[used].name);
+                issues.push(GrammarError::new(message, rule.name_span));
+            }
+        }
+        if rule.token && !grammar.is_binary() && skip.is_some() {
+            let message = format!("rule '{}' is @token, but tree-sitter cannot join external symbols into a token, so extras may come inside it", rule.name);
+            issues.push(GrammarError::new(message, rule.name_span));
+        }
+    }
+
+    let names = grammar.rules.iter()
+        .map(|rule| match rule.visibility {
+            Visibility::Visible => identifier(&rule.name),
+            Visibility::Hidden | Visibility::Inline => format!("_{}", identifier(&rule.name))
+        })
+        .collect();
+    let mut writer = Writer { grammar, names, issues };
+
+    let mut text = format!("// The tree-sitter grammar of {}, exported by parsergen.\nmodule.exports = grammar({{\n  name: '{}',\n", name, name);
+    let extras: Vec<String> = skip.into_iter().map(|i| format!("$.{}", writer.names[i])).collect();
+    text += &format!("\n  extras: $ => [{}],\n", extras.join(", "));
+    if let Symbols::Named(decls) = &grammar.symbols {
+        let externals: Vec<String> = decls.iter().map(|decl| format!("$.{}", decl.name)).collect();
+        text += &format!("\n  externals: $ => [{}],\n", externals.join(", "));
+    }
+
+    let conflicts = conflicts(grammar, &|i| written[i] && !lexed(i));
+    if !conflicts.is_empty() {
+        text += "\n  conflicts: $ => [\n";
+        for conflict in conflicts {
+            let rules: Vec<String> = conflict.iter().map(|&i| format!("$.{}", writer.names[i])).collect();
+            text += &format!("    [{}],\n", rules.join(", "));
+        }
+        text += "  ],\n";
+    }
+
+    text += "\n  rules: {\n";
+    for &i in &order {
+        let rule = &grammar.rules[i];
+        let body = match lexed(i) {
+            true => format!("token({})", writer.expr(&rule.expr, &mut vec![i])),
+            false => writer.expr(&rule.expr, &mut Vec::new())
+        };
+        text += &format!("    {}: $ => {},\n", writer.names[i], body);
+    }
+    text += "  }\n});\n";
+
+    Export { text, issues: writer.issues }
+}
+
+// The grammar without the uses of the skip rule that were inserted when it was read, as
+// tree-sitter puts the extras in by itself.
+fn without_skip(grammar: &Grammar) -> Grammar {
+    fn strip(skip: &str, expr: &Expr) -> Expr {
+        let is_skip = |expr: &Expr| match &expr.kind {
+            ExprKind::Opt(inner) => expr.label.is_none() && matches!(&inner.kind, ExprKind::Rule(name) if name == skip),
+            _ => false
+        };
+        let kind = match &expr.kind {
+            ExprKind::Seq(items) => {
+                let mut items: Vec<Expr> = items.iter().filter(|item| !is_skip(item)).map(|item| strip(skip, item)).collect();
+                if items.len() == 1 && expr.label.is_none() {
+                    return items.pop().unwrap()
+                }
+                ExprKind::Seq(items)
+            },
+            _ if is_skip(expr) => ExprKind::Seq(Vec::new()),
+            ExprKind::Union(items) => ExprKind::Union(items.iter().map(|item| strip(skip, item)).collect()),
+            ExprKind::Opt(inner) => ExprKind::Opt(Box::new(strip(skip, inner))),
+            ExprKind::Star(inner) => ExprKind::Star(Box::new(strip(skip, inner))),
+            ExprKind::Plus(inner) => ExprKind::Plus(Box::new(strip(skip, inner))),
+            kind => kind.clone()
+        };
+        Expr { kind, ..expr.clone() }
+    }
+
+    let mut grammar = grammar.clone();
+    if let Some(skip) = grammar.skip.clone() {
+        for rule in &mut grammar.rules {
+            rule.expr = strip(&skip.rule, &rule.expr);
+        }
+    }
+    grammar
+}
+
+// The sets of rules to declare as conflicts, from the conflicts of an LR(1) table for the grammar.
+// Conflicts within rules that are not declared are left out, e.g. those within tokens, which the
+// lexer of tree-sitter reads.
+fn conflicts(grammar: &Grammar, declared: &dyn Fn(usize) -> bool) -> Vec<Vec<usize>> {
+    // A grammar that does not compile has been reported by ambiguity already.
+    let Ok(program) = Program::compile(grammar) else { return Vec::new() };
+    let Err(conflicts) = LrTable::build(&program, LrMode::Canonical) else { return Vec::new() };
+
+    let mut result: Vec<Vec<usize>> = Vec::new();
+    for conflict in conflicts {
+        let mut rules: Vec<usize> = conflict.rules(&program)
+            .into_iter()
+            .map(|rule| grammar.rules.iter().position(|other| other.name == program.rules()[rule].name).unwrap())
+            .collect();
+        rules.sort();
+        if !rules.is_empty() && rules.iter().all(|&i| declared(i)) && !result.contains(&rules) {
+            result.push(rules);
+        }
+    }
+    result
+}
+
+impl Writer<'_> {
+    // token holds the rules being written into a token, innermost last; it is empty outside tokens.
+    fn expr(&mut self, expr: &Expr, token: &mut Vec<usize>) -> String {
+        let text = match expr.expanded() {
+            Expanded::Symbol(name) => format!("$.{}", name),
+            Expanded::Byte(byte) => format!("'{}'", escape(byte, "'\\")),
+            Expanded::Rule(name) => {
+                let i = self.grammar.rules.iter().position(|rule| rule.name == *name).unwrap();
+                match token.last() {
+                    None => format!("$.{}", self.names[i]),
+                    Some(_) if token.contains(&i) => {
+                        let message = format!("@token rule '{}' uses rule '{}' recursively, which a tree-sitter token cannot", self.grammar.rules[token[0]].name, name);
+                        self.issues.push(GrammarError::new(message, expr.span));
+                        format!("$.{}", self.names[i])
+                    },
+                    Some(_) => {
+                        token.push(i);
+                        let grammar = self.grammar;
+                        let text = self.expr(&grammar.rules[i].expr, token);
+                        token.pop();
+                        text
+                    }
+                }
+            },
+            Expanded::Range(first, last) => match (first.expanded(), last.expanded()) {
+                (Expanded::Byte(first), Expanded::Byte(last)) => format!("/[{}-{}]/", escape(first, "\\]^-/"), escape(last, "\\]^-/")),
+                (Expanded::Symbol(first), Expanded::Symbol(last)) => {
+                    let (first, last) = (self.grammar.symbol_index(first).unwrap(), self.grammar.symbol_index(last).unwrap());
+                    let Symbols::Named(decls) = &self.grammar.symbols else { unreachable!() };
+                    let symbols: Vec<String> = decls[first..=last].iter().map(|decl| format!("$.{}", decl.name)).collect();
+                    format!("choice({})", symbols.join(", "))
+                },
+                _ => unreachable!("ranges are checked when a grammar is read")
+            },
+            Expanded::Seq(items) => match items {
+                [] => "blank()".to_string(),
+                [item] => self.expr(item, token),
+                _ => format!("seq({})", self.exprs(items, token))
+            },
+            Expanded::Union(items) => format!("choice({})", self.exprs(items, token)),
+            Expanded::Opt(inner) => format!("optional({})", self.expr(inner, token)),
+            Expanded::Star(inner) => format!("repeat({})", self.expr(inner, token)),
+            Expanded::Plus(inner) => format!("repeat1({})", self.expr(inner, token))
+        };
+
+        // A token has no fields, as it has no nodes inside it.
+        match &expr.label {
+            Some(label) if token.is_empty() => format!("field('{}', {})", label.name, text),
+            _ => text
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr], token: &mut Vec<usize>) -> String {
+        exprs.iter().map(|expr| self.expr(expr, token)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+// A byte as it is written in a JavaScript string or regular expression: letters, digits and
+// punctuation other than the special characters as they are, anything else as \xNN.
+fn escape(byte: u8, special: &str) -> String {
+    match byte {
+        0x21..=0x7E if !special.contains(byte as char) => (byte as char).to_string(),
+        _ => format!("\\x{:02X}", byte)
+    }
+}
@@ -0,0 +1,428 @@
+// formatter.rs
+//
+// parsergen fmt <grammar.pglsf>... [--check]
+//
+// Rewrites grammars into one canonical layout. Both sections are indented with a single tab, the
+// symbols are wrapped into lines of at most WIDTH columns, and every rule gets a line of its own. A
+// rule too long for one line is broken up: a union puts each alternative on its own line, aligned
+// on '=' and '|', and a sequence continues on the lines below. Actions are left as they are written.
+// Imports go first, one per line.
+// Comments are kept, and so are single blank lines between items; a comment from the middle of a
+// rule is moved above the rule.
+//
+// With --check, the files are left alone, and every file that is not formatted is listed with the
+// lines that would change.
+//
+
+use std::fs;
+use std::process::ExitCode;
+
+use crate::grammar::lexer::{tokenize, Token, TokenKind};
+use crate::grammar::{self, Expr, ExprKind, Grammar, GrammarError, RuleDef, Skip, Span, Symbols, Visibility};
+use crate::tester::diff;
+
+// The width lines are wrapped at, counting a tab as TAB_WIDTH columns.
+pub const WIDTH: usize = 100;
+const TAB_WIDTH: usize = 4;
+
+fn width(line: &str) -> usize {
+    line.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+// The expression written on one line, with parentheses only where they are needed.
+pub fn expr_text(expr: &Expr) -> String {
+    let text = match &expr.kind {
+        ExprKind::Symbol(name) | ExprKind::Rule(name) => name.clone(),
+        ExprKind::Call(name, args) => format!("{}({})", name, args.iter().map(expr_text).collect::<Vec<_>>().join(", ")),
+        ExprKind::Byte(byte) => format!("0x{:02X}", byte),
+        ExprKind::Range(first, last) => format!("{} ... {}", expr_text(first), expr_text(last)),
+        ExprKind::Seq(items) => items.iter().map(item_text).collect::<Vec<_>>().join(" "),
+        ExprKind::Union(alternatives) => alternatives.iter().map(alternative_text).collect::<Vec<_>>().join(" | "),
+        ExprKind::Opt(inner) => format!("?{}", operand_text(inner)),
+        ExprKind::Star(inner) => format!("*{}", operand_text(inner)),
+        ExprKind::Plus(inner) => format!("+{}", operand_text(inner))
+    };
+    match &expr.label {
+        Some(label) => format!("{}:{}", label.name, text),
+        None => text
+    }
+}
+
+// An item of a sequence.
+fn item_text(expr: &Expr) -> String {
+    match expr.kind {
+        ExprKind::Seq(_) | ExprKind::Union(_) => format!("({})", expr_text(expr)),
+        _ => expr_text(expr)
+    }
+}
+
+// An alternative of a union.
+fn alternative_text(expr: &Expr) -> String {
+    match expr.kind {
+        ExprKind::Union(_) => format!("({})", expr_text(expr)),
+        _ => expr_text(expr)
+    }
+}
+
+// The operand of '?', '*' or '+'.
+fn operand_text(expr: &Expr) -> String {
+    match expr.kind {
+        ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Rule(_) | ExprKind::Call(_, _) => expr_text(expr),
+        _ => format!("({})", expr_text(expr))
+    }
+}
+
+// Fills lines with the words, separated by spaces, wrapping before a word that would go past WIDTH.
+fn wrap(indent: &str, words: &[String]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && width(&format!("{}{} {}", indent, line, word)) > WIDTH {
+            lines.push(format!("{}{}", indent, line));
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(format!("{}{}", indent, line));
+    lines
+}
+
+// The name of a rule, with its parameters if it is a template.
+pub fn rule_head(rule: &RuleDef) -> String {
+    match rule.params.is_empty() {
+        true => rule.name.clone(),
+        false => format!("{}({})", rule.name, rule.params.join(", "))
+    }
+}
+
+// The lines of a rule. An action is kept as it is written, starting on the last line of the
+// expression.
+fn rule_lines(rule: &RuleDef) -> Vec<String> {
+    let annotations = match rule.visibility {
+        Visibility::Visible => "",
+        Visibility::Hidden => "@hidden ",
+        Visibility::Inline => "@inline "
+    };
+    let head = format!("{}{}{}", annotations, if rule.token { "@token " } else { "" }, rule_head(rule));
+    let action = rule.action.as_ref().map_or(String::new(), |action| format!(" => {}", action.code));
+    let first_line = format!("\t{} = {}{}", head, expr_text(&rule.expr), action.lines().next().unwrap_or(""));
+
+    let mut lines = match &rule.expr.kind {
+        _ if width(&first_line) < WIDTH => vec![format!("\t{} = {}", head, expr_text(&rule.expr))],
+        ExprKind::Union(alternatives) => {
+            let mut lines = vec![format!("\t{}", head)];
+            for (i, alternative) in alternatives.iter().enumerate() {
+                let operator = if i == 0 { '=' } else { '|' };
+                lines.push(format!("\t\t{} {}", operator, alternative_text(alternative)));
+            }
+            lines
+        },
+        ExprKind::Seq(items) => {
+            let words: Vec<String> = items.iter().map(item_text).collect();
+            let mut lines = vec![format!("\t{} =", head)];
+            lines.extend(wrap("\t\t", &words));
+            lines
+        },
+        _ => vec![format!("\t{} = {}", head, expr_text(&rule.expr))]
+    };
+
+    let last = lines.last_mut().unwrap();
+    last.push_str(&action);
+    last.push(';');
+    lines
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+
+    // The tokens other than comments, and the comments that have not been written yet.
+    tokens: Vec<Token>,
+    comments: Vec<Span>,
+    next_comment: usize,
+
+    output: String,
+
+    // Where the last thing written ends in the source, for finding blank lines between items.
+    last_end: usize,
+
+    // Whether nothing has been written since the start of the section.
+    section_start: bool
+}
+
+impl<'a> Formatter<'a> {
+    // The first token that starts at or after the position.
+    fn token_at(&self, pos: usize) -> Token {
+        self.tokens[self.tokens.partition_point(|token| token.span.start < pos)]
+    }
+
+    fn comment_text(&self, span: Span) -> &'a str {
+        self.source[span.start..span.end].trim_end()
+    }
+
+    fn next_comment(&self) -> Option<Span> {
+        self.comments.get(self.next_comment).copied()
+    }
+
+    // Whether the source has a blank line between the last thing written and the position.
+    fn blank_before(&self, start: usize) -> bool {
+        match self.source.get(self.last_end..start) {
+            Some(gap) => {
+                let lines: Vec<&str> = gap.split('\n').collect();
+                lines.len() > 2 && lines[1..lines.len() - 1].iter().any(|line| line.trim().is_empty())
+            },
+            None => false
+        }
+    }
+
+    // Writes lines for the part of the source in the span, keeping a blank line before them.
+    fn write(&mut self, lines: &[String], span: Span) {
+        if !self.section_start && !self.output.is_empty() && self.blank_before(span.start) {
+            self.output.push('\n');
+        }
+        for line in lines {
+            self.output.push_str(line);
+            self.output.push('\n');
+        }
+        self.last_end = span.end;
+        self.section_start = false;
+    }
+
+    // Writes the comments that start before the position, each on its own line.
+    fn comments_before(&mut self, pos: usize, indent: &str) {
+        while let Some(span) = self.next_comment().filter(|span| span.start < pos) {
+            self.write(&[format!("{}{}", indent, self.comment_text(span))], span);
+            self.next_comment += 1;
+        }
+    }
+
+    // The next comment, if it follows the position on the same line.
+    fn comment_after(&self, pos: usize) -> Option<Span> {
+        self.next_comment().filter(|span| {
+            self.source.get(pos..span.start).is_some_and(|gap| gap.chars().all(|c| c == ' ' || c == '\t'))
+        })
+    }
+
+    // Moves a comment that follows the position on the same line onto the end of the last line.
+    fn trailing_comment(&mut self, pos: usize) {
+        if let Some(span) = self.comment_after(pos) {
+            self.output.pop();
+            self.output.push(' ');
+            self.output.push_str(self.comment_text(span));
+            self.output.push('\n');
+            self.last_end = span.end;
+            self.next_comment += 1;
+        }
+    }
+
+    // Writes a section keyword.
+    fn keyword(&mut self, token: Token) {
+        self.comments_before(token.span.start, "");
+        self.write(&[token.text(self.source).to_string()], token.span);
+        self.trailing_comment(token.span.end);
+        self.section_start = true;
+    }
+
+    fn symbols(&mut self, symbols: &Symbols) {
+        let decls = match symbols {
+            Symbols::Binary => {
+                let binary = self.token_at(self.last_end);
+                let semicolon = self.token_at(binary.span.end);
+                self.comments_before(semicolon.span.end, "\t");
+                self.write(&["\tbinary;".to_string()], binary.span.to(semicolon.span));
+                self.trailing_comment(semicolon.span.end);
+                return
+            },
+            Symbols::Named(decls) if decls.is_empty() => {
+                // The symbols all come from imports: the ';' goes on the line of the keyword.
+                let semicolon = self.token_at(self.last_end);
+                if self.next_comment().is_some_and(|span| span.start < semicolon.span.start) {
+                    self.comments_before(semicolon.span.start, "\t");
+                    self.write(&["\t;".to_string()], semicolon.span);
+                } else {
+                    self.output.pop();
+                    self.output.push_str(";\n");
+                    self.last_end = semicolon.span.end;
+                }
+                self.trailing_comment(semicolon.span.end);
+                return
+            },
+            Symbols::Named(decls) => decls
+        };
+
+        // Symbols are written in groups, which are split where the source has a blank line or a
+        // comment between two symbols.
+        let mut group: Vec<String> = Vec::new();
+        let mut group_span = Span::default();
+        for (i, decl) in decls.iter().enumerate() {
+            let separator = self.token_at(decl.span.end);
+            let split = self.next_comment().is_some_and(|span| span.start < decl.span.start)
+                || (!group.is_empty() && self.source[group_span.end..decl.span.start].matches('\n').count() > 1);
+            if split && !group.is_empty() {
+                self.write(&wrap("\t", &group), group_span);
+                group.clear();
+            }
+
+            self.comments_before(decl.span.start, "\t");
+            if group.is_empty() {
+                group_span = decl.span;
+            }
+            group_span.end = separator.span.end;
+            group.push(format!("{}{}", decl.name, if i + 1 == decls.len() { ';' } else { ',' }));
+
+            if self.comment_after(separator.span.end).is_some() {
+                self.write(&wrap("\t", &group), group_span);
+                group.clear();
+                self.trailing_comment(separator.span.end);
+            }
+        }
+
+        if !group.is_empty() {
+            self.write(&wrap("\t", &group), group_span);
+        }
+    }
+
+    fn rules(&mut self, skip: Option<&Skip>, rules: &[RuleDef]) {
+        if let Some(skip) = skip {
+            self.comments_before(skip.span.start, "\t");
+            self.write(&[format!("\t@skip {};", skip.rule)], skip.span);
+            self.trailing_comment(skip.span.end);
+        }
+        for rule in rules {
+            let end = rule.action.as_ref().map_or(rule.expr.span.end, |action| action.span.end);
+            let semicolon = self.token_at(end);
+            self.comments_before(semicolon.span.end, "\t");
+            self.write(&rule_lines(rule), rule.name_span.to(semicolon.span));
+            self.trailing_comment(semicolon.span.end);
+        }
+        self.comments_before(self.source.len() + 1, "\t");
+    }
+}
+
+// The text of a grammar that has no source, such as one made by grammar::transform, in the same
+// layout. Its imports must be resolved already.
+pub fn grammar_text(grammar: &Grammar) -> String {
+    let mut text = String::from("symbols\n");
+    match &grammar.symbols {
+        Symbols::Binary => text.push_str("\tbinary;\n"),
+        Symbols::Named(decls) => {
+            let words: Vec<String> = decls.iter()
+                .enumerate()
+                .map(|(i, decl)| format!("{}{}", decl.name, if i + 1 == decls.len() { ';' } else { ',' }))
+                .collect();
+            for line in wrap("\t", &words) {
+                text.push_str(&line);
+                text.push('\n');
+            }
+        }
+    }
+
+    text.push_str("\ngrammar\n");
+    if let Some(skip) = &grammar.skip {
+        text.push_str(&format!("\t@skip {};\n", skip.rule));
+    }
+    for rule in &grammar.rules {
+        for line in rule_lines(rule) {
+            text.push_str(&line);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+// Formats the source of a grammar. Only the layout is looked at, so the names need not be valid.
+pub fn format(source: &str) -> Result<String, GrammarError> {
+    let grammar = grammar::parse(source)?;
+    let (comments, tokens): (Vec<Token>, Vec<Token>) = tokenize(source)?
+        .into_iter()
+        .partition(|token| token.kind == TokenKind::Comment);
+
+    let mut formatter = Formatter {
+        source,
+        comments: comments.iter().map(|token| token.span).collect(),
+        tokens,
+        next_comment: 0,
+        output: String::new(),
+        last_end: 0,
+        section_start: false
+    };
+
+    for import in &grammar.imports {
+        formatter.comments_before(import.span.start, "");
+        formatter.write(&[format!("import \"{}\" as {};", import.path, import.alias)], import.span);
+        formatter.trailing_comment(import.span.end);
+    }
+    if !grammar.imports.is_empty() {
+        formatter.output.push('\n');
+        formatter.section_start = true;
+    }
+
+    let symbols = formatter.token_at(formatter.last_end);
+    formatter.keyword(symbols);
+    formatter.symbols(&grammar.symbols);
+
+    // The grammar keyword follows the ';' that ends the symbols.
+    let semicolon = formatter.tokens.iter()
+        .position(|token| token.kind == TokenKind::Semicolon && token.span.start > symbols.span.start)
+        .unwrap();
+    if !formatter.output.is_empty() {
+        formatter.output.push('\n');
+    }
+    formatter.section_start = true;
+    formatter.keyword(formatter.tokens[semicolon + 1]);
+    formatter.rules(grammar.skip.as_ref(), &grammar.rules);
+
+    Ok(formatter.output)
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+    if paths.is_empty() {
+        eprintln!("usage: parsergen fmt <grammar.pglsf>... [--check]");
+        return ExitCode::FAILURE
+    }
+
+    let mut ok = true;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                ok = false;
+                continue
+            }
+        };
+
+        let formatted = match format(&source) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("{}", error.describe(path, &source));
+                ok = false;
+                continue
+            }
+        };
+
+        if formatted == source {
+            continue
+        }
+
+        if check {
+            println!("{}: not formatted", path);
+            for line in diff(&source, &formatted).lines().filter(|line| !line.starts_with(' ')) {
+                println!("{}", line);
+            }
+            ok = false;
+        } else if let Err(error) = fs::write(path, &formatted) {
+            eprintln!("{}: {}", path, error);
+            ok = false;
+        }
+    }
+
+    if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,123 @@
+use crate::formatter::*;
+use crate::grammar::read;
+
+const PGLSF: &str = include_str!("../../../languages/pglsf.pglsf");
+const ASCII_TO_PGLSF: &str = include_str!("../../../languages/ascii_to_pglsf.pglsf");
+
+#[test]
+fn test_languages_are_formatted() {
+    assert_eq!(format(PGLSF).unwrap(), PGLSF);
+    assert_eq!(format(ASCII_TO_PGLSF).unwrap(), ASCII_TO_PGLSF);
+}
+
+#[test]
+fn test_format_layout() {
+    let source = "# header\nsymbols A,B ,\n  C;\ngrammar\n  root=A  B|C ;  b = 0x0a...0x0D;\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "# header\nsymbols\n\tA, B, C;\n\ngrammar\n\troot = A B | C;\n\tb = 0x0A ... 0x0D;\n"
+    );
+}
+
+#[test]
+fn test_format_keeps_comments_and_blank_lines() {
+    let source = "symbols\n\tA, B, # first\n\n\n\t# second\n\tC;\ngrammar\n\n\t### BANNER ###\n\n\troot = A # trailing\n\t\t# inside\n\t\tB;\n\tc = C; # after\n# end\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "symbols\n\tA, B, # first\n\n\t# second\n\tC;\n\ngrammar\n\t### BANNER ###\n\n\t# trailing\n\t# inside\n\troot = A B;\n\tc = C; # after\n\t# end\n"
+    );
+}
+
+#[test]
+fn test_format_parentheses() {
+    let source = "symbols A, B;\ngrammar\n\troot = ((A)) (A B) (A | B) | (A | B) | ?(A ... B) +(A B) *c;\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "symbols\n\tA, B;\n\ngrammar\n\troot = A (A B) (A | B) | (A | B) | ?(A ... B) +(A B) *c;\n"
+    );
+}
+
+#[test]
+fn test_format_breaks_long_rules() {
+    let names: Vec<String> = (0..30).map(|i| format!("SYMBOL_{}", i)).collect();
+    let source = format!(
+        "symbols {};\ngrammar\n\tunion = {};\n\tseq = {};\n",
+        names.join(", "), names[..12].join(" | "), names[..16].join(" ")
+    );
+    let formatted = format(&source).unwrap();
+    let lines: Vec<&str> = formatted.lines().collect();
+
+    assert!(lines.iter().all(|line| line.replace('\t', "    ").len() <= WIDTH), "{}", formatted);
+    assert_eq!(lines[1], "\tSYMBOL_0, SYMBOL_1, SYMBOL_2, SYMBOL_3, SYMBOL_4, SYMBOL_5, SYMBOL_6, SYMBOL_7, SYMBOL_8,");
+    assert_eq!(lines[7], "\tunion");
+    assert_eq!(lines[8], "\t\t= SYMBOL_0");
+    assert_eq!(lines[9], "\t\t| SYMBOL_1");
+    assert_eq!(lines[19], "\t\t| SYMBOL_11;");
+    assert_eq!(lines[20], "\tseq =");
+    assert!(lines[21].starts_with("\t\tSYMBOL_0 SYMBOL_1"));
+    assert!(lines.last().unwrap().ends_with("SYMBOL_15;"));
+}
+
+#[test]
+fn test_format_is_idempotent_and_keeps_meaning() {
+    for source in [PGLSF, ASCII_TO_PGLSF, "symbols binary; # bytes\ngrammar root = 0x41 | (0x42 0x43);"] {
+        let formatted = format(source).unwrap();
+        assert_eq!(format(&formatted).unwrap(), formatted);
+
+        let before = read(source).unwrap();
+        let after = read(&formatted).unwrap();
+        assert_eq!(before.rules.len(), after.rules.len());
+        for (a, b) in before.rules.iter().zip(&after.rules) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(expr_text(&a.expr), expr_text(&b.expr));
+        }
+    }
+}
+
+#[test]
+fn test_format_needs_only_syntax() {
+    assert!(format("symbols A;\ngrammar\n\troot = UNDECLARED;\n").is_ok());
+    assert!(format("symbols A;\ngrammar\n\troot = ;\n").is_err());
+}
+
+#[test]
+fn test_format_imports() {
+    let source = "import  \"lib/common.pglsf\"  as common; # shared\nimport \"other.pglsf\" as other;\nsymbols ;\ngrammar\n\troot = common.word other.word;\n\tcommon.space = common.tab;\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "import \"lib/common.pglsf\" as common; # shared\nimport \"other.pglsf\" as other;\n\nsymbols;\n\ngrammar\n\troot = common.word other.word;\n\tcommon.space = common.tab;\n"
+    );
+}
+
+#[test]
+fn test_format_templates() {
+    let source = "symbols A, COMMA;\ngrammar\n\troot = list( A,COMMA ) ?list(pair( A ), COMMA);\n\tlist(item,sep) = item *(sep item);\n\tpair( x ) = x x;\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "symbols\n\tA, COMMA;\n\ngrammar\n\troot = list(A, COMMA) ?list(pair(A), COMMA);\n\tlist(item, sep) = item *(sep item);\n\tpair(x) = x x;\n"
+    );
+}
+
+#[test]
+fn test_format_actions() {
+    let source = "symbols A;\ngrammar\n\troot = +A=>{ values.len() }  ;\n\tcount = A   => {\n        let n = 1;\n        n\n    };\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "symbols\n\tA;\n\ngrammar\n\troot = +A => { values.len() };\n\tcount = A => {\n        let n = 1;\n        n\n    };\n"
+    );
+}
+
+#[test]
+fn test_format_labels() {
+    let source = "symbols A, B;\ngrammar\n\troot = name : A  ?(x:A...B) *(value:root);\n";
+    assert_eq!(format(source).unwrap(), "symbols\n\tA, B;\n\ngrammar\n\troot = name:A ?(x:A ... B) *value:root;\n");
+}
+
+#[test]
+fn test_format_annotations() {
+    let source = "symbols A;\ngrammar\n@skip   space ; # between words\n\t@token  @hidden word = +A;\n\t@inline space=A;\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "symbols\n\tA;\n\ngrammar\n\t@skip space; # between words\n\t@hidden @token word = +A;\n\t@inline space = A;\n"
+    );
+}
@@ -0,0 +1,412 @@
+// fuzzer.rs
+//
+// parsergen fuzz <grammar.pglsf> [--count N] [--depth D] [--seed S] [--weight rule.N=W]... [--invalid]
+//
+// Generates random sentences of a grammar by walking its rules from the root. Each sentence is
+// printed on its own line: symbol names separated by spaces, or for binary grammars the bytes, with
+// anything other than printable ASCII written as \xNN.
+//
+// Once a sentence is --depth rule calls deep, the generator takes the shortest way out of every
+// rule, so that generation always ends. --weight gives alternative N of the top-level union of a
+// rule the weight W; alternatives are weighted 1 by default, and a rule that is not a union has none
+// to weight. With --invalid, valid sentences are mutated into near misses, which are kept only if
+// the grammar rejects them.
+//
+
+use std::process::ExitCode;
+
+use crate::grammar::program::Program;
+use crate::grammar::{self, Expanded, Expr, ExprKind, Grammar, Symbols};
+use crate::rng::Rng;
+use crate::tester::{run, Outcome};
+
+const UNREACHABLE: usize = usize::MAX;
+
+// Optionals are taken, and repetitions repeated, with this probability out of 2.
+const REPEAT_CHANCE: usize = 1;
+
+// Repetitions stop after this many items, however lucky the generator gets.
+const MAX_REPEAT: usize = 8;
+
+pub struct SentenceGenerator<'a> {
+    grammar: &'a Grammar,
+    rng: Rng,
+    max_depth: usize,
+
+    // The weights of the alternatives of each rule's top-level union, or none if the rule is not
+    // a union.
+    weights: Vec<Vec<u32>>,
+
+    // For each rule, the fewest nested rule calls needed to finish it, or UNREACHABLE if it cannot finish.
+    min_depths: Vec<usize>,
+
+    // Every terminal that appears in the grammar, for mutations to draw from.
+    terminals: Vec<u32>
+}
+
+impl<'a> SentenceGenerator<'a> {
+    pub fn new(grammar: &'a Grammar, max_depth: usize, seed: u64) -> Self {
+        let weights = grammar.rules.iter()
+            .map(|rule| match &rule.expr.kind {
+                ExprKind::Union(alternatives) => vec![1; alternatives.len()],
+                _ => Vec::new()
+            })
+            .collect();
+
+        SentenceGenerator {
+            grammar,
+            rng: Rng::new(seed),
+            max_depth,
+            weights,
+            min_depths: min_depths(grammar),
+            terminals: terminals(grammar)
+        }
+    }
+
+    // Sets the weight of an alternative of a rule's top-level union.
+    pub fn set_weight(&mut self, rule: &str, alternative: usize, weight: u32) -> Result<(), String> {
+        let i = self.rule_index(rule).ok_or_else(|| format!("rule '{}' is not defined", rule))?;
+        if self.weights[i].is_empty() {
+            return Err(format!("rule '{}' is not a union, so its alternatives cannot be weighted", rule))
+        }
+
+        match self.weights[i].get_mut(alternative) {
+            Some(slot) => {
+                *slot = weight;
+                Ok(())
+            },
+            None => Err(format!("rule '{}' has no alternative {}", rule, alternative))
+        }
+    }
+
+    fn rule_index(&self, name: &str) -> Option<usize> {
+        self.grammar.rules.iter().position(|rule| rule.name == name)
+    }
+
+    // A random sentence of the root rule, as symbol indices or bytes. Returns None if the root
+    // rule cannot produce any finite sentence.
+    pub fn sentence(&mut self) -> Option<Vec<u32>> {
+        let root = self.grammar.root()?;
+        let root = self.rule_index(&root.name).unwrap();
+        if self.min_depths[root] == UNREACHABLE {
+            return None
+        }
+
+        let mut result = Vec::new();
+        self.rule(root, 0, &mut result);
+        Some(result)
+    }
+
+    fn rule(&mut self, i: usize, depth: usize, result: &mut Vec<u32>) {
+        let grammar = self.grammar;
+        let expr = &grammar.rules[i].expr;
+        match &expr.kind {
+            ExprKind::Union(alternatives) => {
+                let weights = self.weights[i].clone();
+                let choice = self.choose(alternatives, Some(&weights), depth);
+                self.expr(&alternatives[choice], depth, result);
+            },
+            _ => self.expr(expr, depth, result)
+        }
+    }
+
+    // Picks an alternative that can finish. Past the depth bound, only the alternatives that finish
+    // soonest are considered.
+    fn choose(&mut self, alternatives: &[Expr], weights: Option<&[u32]>, depth: usize) -> usize {
+        let depths: Vec<usize> = alternatives.iter().map(|alternative| self.expr_depth(alternative)).collect();
+        let closing = depth >= self.max_depth;
+        let shortest = *depths.iter().min().unwrap();
+
+        let mut effective: Vec<u32> = depths.iter().enumerate()
+            .map(|(k, &d)| {
+                let usable = if closing { d == shortest } else { d != UNREACHABLE };
+                if usable { weights.map_or(1, |weights| weights[k]) } else { 0 }
+            })
+            .collect();
+
+        // If every usable alternative was weighted 0, fall back to treating them equally.
+        if effective.iter().all(|&weight| weight == 0) {
+            effective = depths.iter().map(|&d| if d == shortest { 1 } else { 0 }).collect();
+        }
+
+        self.rng.weighted(&effective)
+    }
+
+    fn expr(&mut self, expr: &Expr, depth: usize, result: &mut Vec<u32>) {
+        let closing = depth >= self.max_depth;
+        match expr.expanded() {
+            Expanded::Symbol(_) | Expanded::Byte(_) => result.push(terminal(self.grammar, expr)),
+            Expanded::Range(first, last) => {
+                let (first, last) = (terminal(self.grammar, first), terminal(self.grammar, last));
+                let (low, high) = (first.min(last), first.max(last));
+                result.push(low + self.rng.below((high - low + 1) as usize) as u32);
+            },
+            Expanded::Rule(name) => {
+                let i = self.rule_index(name).unwrap();
+                self.rule(i, depth + 1, result);
+            },
+            Expanded::Seq(items) => for item in items {
+                self.expr(item, depth, result);
+            },
+            Expanded::Union(alternatives) => {
+                let choice = self.choose(alternatives, None, depth);
+                self.expr(&alternatives[choice], depth, result);
+            },
+            Expanded::Opt(inner) => if !closing && self.expr_depth(inner) != UNREACHABLE && self.rng.chance(REPEAT_CHANCE, 2) {
+                self.expr(inner, depth, result);
+            },
+            Expanded::Star(inner) | Expanded::Plus(inner) => {
+                if matches!(expr.kind, ExprKind::Plus(_)) {
+                    self.expr(inner, depth, result);
+                }
+                let mut count = 0;
+                while !closing && count < MAX_REPEAT && self.expr_depth(inner) != UNREACHABLE && self.rng.chance(REPEAT_CHANCE, 2) {
+                    self.expr(inner, depth, result);
+                    count += 1;
+                }
+            }
+        }
+    }
+
+    fn expr_depth(&self, expr: &Expr) -> usize {
+        expr_depth(self.grammar, &self.min_depths, expr)
+    }
+
+    // A copy of a sentence with one small random change: a symbol deleted, inserted, replaced,
+    // duplicated or swapped with its neighbour, or the end cut off. The result may still be valid.
+    pub fn near_miss(&mut self, sentence: &[u32]) -> Vec<u32> {
+        let terminals = &self.terminals;
+        let mut result = sentence.to_vec();
+        let len = result.len();
+
+        match (self.rng.below(6), len) {
+            (_, 0) => result.push(terminals[self.rng.below(terminals.len())]),
+            (0, _) => { result.remove(self.rng.below(len)); },
+            (1, _) => result.insert(self.rng.below(len + 1), terminals[self.rng.below(terminals.len())]),
+            (2, _) => {
+                let i = self.rng.below(len);
+                let others: Vec<u32> = terminals.iter().copied().filter(|&t| t != result[i]).collect();
+                if !others.is_empty() {
+                    result[i] = others[self.rng.below(others.len())];
+                }
+            },
+            (3, _) => {
+                let i = self.rng.below(len);
+                result.insert(i, result[i]);
+            },
+            (4, 1) => result.clear(),
+            (4, _) => {
+                let i = self.rng.below(len - 1);
+                result.swap(i, i + 1);
+            },
+            _ => result.truncate(self.rng.below(len))
+        }
+
+        result
+    }
+}
+
+fn terminal(grammar: &Grammar, expr: &Expr) -> u32 {
+    match &expr.kind {
+        ExprKind::Symbol(name) => grammar.symbol_index(name).unwrap() as u32,
+        ExprKind::Byte(byte) => *byte as u32,
+        _ => panic!()
+    }
+}
+
+// Every terminal that appears in the grammar, for mutations to draw from.
+fn terminals(grammar: &Grammar) -> Vec<u32> {
+    let mut result = Vec::new();
+    match &grammar.symbols {
+        Symbols::Named(decls) => result.extend(0..decls.len() as u32),
+        Symbols::Binary => {
+            fn collect(grammar: &Grammar, expr: &Expr, result: &mut Vec<u32>) {
+                match expr.expanded() {
+                    Expanded::Byte(_) => result.push(terminal(grammar, expr)),
+                    Expanded::Range(first, last) => {
+                        let (first, last) = (terminal(grammar, first), terminal(grammar, last));
+                        result.extend(first.min(last)..=first.max(last));
+                    },
+                    Expanded::Seq(items) | Expanded::Union(items) => for item in items {
+                        collect(grammar, item, result);
+                    },
+                    Expanded::Opt(inner) | Expanded::Star(inner) | Expanded::Plus(inner) => collect(grammar, inner, result),
+                    Expanded::Symbol(_) | Expanded::Rule(_) => ()
+                }
+            }
+            for rule in &grammar.rules {
+                collect(grammar, &rule.expr, &mut result);
+            }
+            result.sort_unstable();
+            result.dedup();
+        }
+    }
+    result
+}
+
+// The fewest nested rule calls needed to finish the expression.
+fn expr_depth(grammar: &Grammar, min_depths: &[usize], expr: &Expr) -> usize {
+    match expr.expanded() {
+        Expanded::Symbol(_) | Expanded::Byte(_) | Expanded::Range(_, _) => 0,
+        Expanded::Rule(name) => {
+            let i = grammar.rules.iter().position(|rule| &rule.name == name).unwrap();
+            min_depths[i]
+        },
+        Expanded::Seq(items) => items.iter().map(|item| expr_depth(grammar, min_depths, item)).max().unwrap_or(0),
+        Expanded::Union(items) => items.iter().map(|item| expr_depth(grammar, min_depths, item)).min().unwrap_or(0),
+        Expanded::Opt(_) | Expanded::Star(_) => 0,
+        Expanded::Plus(inner) => expr_depth(grammar, min_depths, inner)
+    }
+}
+
+// The fewest nested rule calls needed to finish each rule, found by iterating to a fixed point.
+fn min_depths(grammar: &Grammar) -> Vec<usize> {
+    let mut depths = vec![UNREACHABLE; grammar.rules.len()];
+    loop {
+        let mut changed = false;
+        for (i, rule) in grammar.rules.iter().enumerate() {
+            let depth = expr_depth(grammar, &depths, &rule.expr).saturating_add(1);
+            if depth < depths[i] {
+                depths[i] = depth;
+                changed = true;
+            }
+        }
+
+        if !changed { return depths }
+    }
+}
+
+// Writes a sentence the way the tester reads inputs.
+pub fn format_sentence(grammar: &Grammar, sentence: &[u32]) -> String {
+    match &grammar.symbols {
+        Symbols::Named(decls) => sentence.iter()
+            .map(|&symbol| decls[symbol as usize].name.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+        Symbols::Binary => sentence.iter()
+            .map(|&byte| match byte as u8 {
+                b'\\' => "\\\\".to_string(),
+                byte @ 0x20..=0x7E => (byte as char).to_string(),
+                byte => format!("\\x{:02X}", byte)
+            })
+            .collect()
+    }
+}
+
+struct Options {
+    grammar_path: String,
+    count: usize,
+    depth: usize,
+    seed: u64,
+    weights: Vec<(String, usize, u32)>,
+    invalid: bool
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options { grammar_path: String::new(), count: 10, depth: 16, seed: Rng::clock_seed(), weights: Vec::new(), invalid: false };
+    let mut grammar_path = None;
+    let mut args = args.iter();
+
+    fn number<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+        value.and_then(|value| value.parse().ok()).ok_or_else(|| format!("{} needs a number", flag))
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" => options.count = number("--count", args.next())?,
+            "--depth" => options.depth = number("--depth", args.next())?,
+            "--seed" => options.seed = number("--seed", args.next())?,
+            "--invalid" => options.invalid = true,
+            "--weight" => {
+                let value = args.next().ok_or("--weight needs rule.N=W")?;
+                let parsed = value.split_once('=').and_then(|(target, weight)| {
+                    let (rule, alternative) = target.rsplit_once('.')?;
+                    Some((rule.to_string(), alternative.parse().ok()?, weight.parse().ok()?))
+                });
+                options.weights.push(parsed.ok_or_else(|| format!("'{}' is not of the form rule.N=W", value))?);
+            },
+            _ if grammar_path.is_none() && !arg.starts_with("--") => grammar_path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument '{}'", arg))
+        }
+    }
+
+    options.grammar_path = grammar_path.ok_or("missing grammar")?;
+    Ok(options)
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("usage: parsergen fuzz <grammar.pglsf> [--count N] [--depth D] [--seed S] [--weight rule.N=W]... [--invalid]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let (grammar, source) = match grammar::load(&options.grammar_path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let mut generator = SentenceGenerator::new(&grammar, options.depth, options.seed);
+    for (rule, alternative, weight) in &options.weights {
+        if let Err(message) = generator.set_weight(rule, *alternative, *weight) {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    }
+
+    // Near misses are checked against the grammar, which needs a program to run.
+    let program = if options.invalid {
+        match Program::compile(&grammar) {
+            Ok(program) => Some(program),
+            Err(error) => {
+                eprintln!("{}", error.describe(&options.grammar_path, &source));
+                return ExitCode::FAILURE
+            }
+        }
+    } else {
+        None
+    };
+
+    eprintln!("seed {}", options.seed);
+    let mut printed = 0;
+    let mut attempts = 0;
+    while printed < options.count {
+        let sentence = match generator.sentence() {
+            Some(sentence) => sentence,
+            None => {
+                eprintln!("the root rule cannot produce a finite sentence");
+                return ExitCode::FAILURE
+            }
+        };
+
+        let sentence = match &program {
+            Some(program) => {
+                attempts += 1;
+                let mutated = generator.near_miss(&sentence);
+                if matches!(run(program, &mutated), Outcome::Accept(_)) {
+                    if attempts > options.count * 100 {
+                        eprintln!("could not find enough near misses that the grammar rejects");
+                        return ExitCode::FAILURE
+                    }
+                    continue
+                }
+                mutated
+            },
+            None => sentence
+        };
+
+        println!("{}", format_sentence(&grammar, &sentence));
+        printed += 1;
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,66 @@
+use crate::fuzzer::*;
+use crate::grammar::read;
+use crate::grammar::program::Program;
+use crate::tester::{run, Outcome};
+
+const NESTED: &str = "\
+symbols LEFT_PAREN, RIGHT_PAREN, A, COMMA;
+grammar
+	root = list;
+	list = LEFT_PAREN ?(item *(COMMA item)) RIGHT_PAREN;
+	item = A | list;
+";
+
+#[test]
+fn test_sentences_are_accepted() {
+    let grammar = read(NESTED).unwrap();
+    let program = Program::compile(&grammar).unwrap();
+    let mut generator = SentenceGenerator::new(&grammar, 6, 1);
+
+    for _ in 0..200 {
+        let sentence = generator.sentence().unwrap();
+        assert!(matches!(run(&program, &sentence), Outcome::Accept(_)), "{}", format_sentence(&grammar, &sentence));
+    }
+}
+
+#[test]
+fn test_binary_sentences_are_accepted() {
+    let source = "symbols binary;\ngrammar\n\tword = +letter *(0x2D +letter);\n\tletter = 0x61 ... 0x7A;\n";
+    let grammar = read(source).unwrap();
+    let program = Program::compile(&grammar).unwrap();
+    let mut generator = SentenceGenerator::new(&grammar, 4, 7);
+
+    for _ in 0..100 {
+        let sentence = generator.sentence().unwrap();
+        assert!(matches!(run(&program, &sentence), Outcome::Accept(_)), "{}", format_sentence(&grammar, &sentence));
+    }
+}
+
+#[test]
+fn test_weights() {
+    let grammar = read("symbols A, B;\ngrammar\n\troot = A | B;\n\tpair = A (A | B);\n").unwrap();
+    let mut generator = SentenceGenerator::new(&grammar, 4, 3);
+    generator.set_weight("root", 0, 0).unwrap();
+    assert!(generator.set_weight("root", 2, 1).is_err());
+    assert!(generator.set_weight("pair", 0, 1).is_err());
+
+    for _ in 0..50 {
+        assert_eq!(generator.sentence(), Some(vec![1]));
+    }
+}
+
+#[test]
+fn test_near_misses_are_mostly_rejected() {
+    let grammar = read(NESTED).unwrap();
+    let program = Program::compile(&grammar).unwrap();
+    let mut generator = SentenceGenerator::new(&grammar, 6, 5);
+
+    let mut rejected = 0;
+    for _ in 0..100 {
+        let sentence = generator.sentence().unwrap();
+        if !matches!(run(&program, &generator.near_miss(&sentence)), Outcome::Accept(_)) {
+            rejected += 1;
+        }
+    }
+    assert!(rejected >= 80, "only {} near misses were rejected", rejected);
+}
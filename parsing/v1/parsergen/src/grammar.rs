@@ -0,0 +1,275 @@
+// grammar.rs
+//
+// use grammar::*
+//
+// The in-memory form of a .pglsf file. A grammar is read from text by the reader and can be
+// compiled into a Program that drives a ParseMachine directly, without generating any code.
+//
+
+pub mod lexer;
+mod reader;
+pub mod import;
+pub mod template;
+pub mod skip;
+pub mod analysis;
+pub mod lookahead;
+pub mod program;
+pub mod transform;
+
+use std::path::Path;
+
+pub use reader::{parse, read};
+
+// A range of bytes in the source text of a grammar.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    // The smallest span covering both spans.
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+
+    // The 1-based line and column of the start of this span.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let col = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        (line, col)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GrammarError {
+    pub message: String,
+    pub span: Span
+}
+
+impl GrammarError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        GrammarError { message: message.into(), span }
+    }
+
+    // Formats the error as "path:line:col: message".
+    pub fn describe(&self, path: &str, source: &str) -> String {
+        let (line, col) = self.span.line_col(source);
+        format!("{}:{}:{}: {}", path, line, col, self.message)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SymbolDecl {
+    pub name: String,
+    pub span: Span
+}
+
+#[derive(Clone, Debug)]
+pub enum Symbols {
+    // The symbols are the declared UPPER_NAMEs.
+    Named(Vec<SymbolDecl>),
+
+    // The symbols are bytes, written as hex literals such as 0x41.
+    Binary
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleDef {
+    pub name: String,
+    pub name_span: Span,
+
+    // The parameters of a rule template, e.g. item and sep in sep_list(item, sep). Empty for an
+    // ordinary rule.
+    pub params: Vec<String>,
+
+    pub expr: Expr,
+
+    // The Rust block after '=>', if any, that computes the value of the rule when it is reduced.
+    pub action: Option<Action>,
+
+    // Set by @hidden and @inline.
+    pub visibility: Visibility,
+
+    // Set by @token: the rule is read as one word, without skipping anything inside it.
+    pub token: bool
+}
+
+// How a rule shows up in the symbols a ParseMachine returns, and in trees.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Visibility {
+    #[default]
+    Visible,
+
+    // The rule is left out of trees and reductions, along with everything it matched.
+    Hidden,
+
+    // The rule has no node of its own: what it matched goes to the rule that used it.
+    Inline
+}
+
+// The rule given by @skip, which may be matched after every symbol or token, e.g. @skip ws;
+#[derive(Clone, Debug)]
+pub struct Skip {
+    pub rule: String,
+    pub name_span: Span,
+
+    // From the @skip to the ';'.
+    pub span: Span
+}
+
+#[derive(Clone, Debug)]
+pub struct Action {
+    // The text of the block, braces included.
+    pub code: String,
+    pub span: Span
+}
+
+#[derive(Clone, Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+
+    // The label of a symbol or rule that is captured as a field of the tree, e.g. body in
+    // body:rule_expr.
+    pub label: Option<Label>
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span, label: None }
+    }
+
+    // The kind of an expression of a grammar that has been read, which has no template calls left.
+    pub fn expanded(&self) -> Expanded<'_> {
+        match &self.kind {
+            ExprKind::Symbol(name) => Expanded::Symbol(name),
+            ExprKind::Byte(byte) => Expanded::Byte(*byte),
+            ExprKind::Rule(name) => Expanded::Rule(name),
+            ExprKind::Call(_, _) => unreachable!("templates are expanded when a grammar is read"),
+            ExprKind::Range(first, last) => Expanded::Range(first, last),
+            ExprKind::Seq(items) => Expanded::Seq(items),
+            ExprKind::Union(items) => Expanded::Union(items),
+            ExprKind::Opt(inner) => Expanded::Opt(inner),
+            ExprKind::Star(inner) => Expanded::Star(inner),
+            ExprKind::Plus(inner) => Expanded::Plus(inner)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Label {
+    pub name: String,
+    pub span: Span
+}
+
+#[derive(Clone, Debug)]
+pub enum ExprKind {
+    // A declared symbol, e.g. LETTER_A.
+    Symbol(String),
+
+    // A byte in a binary grammar, e.g. 0x41.
+    Byte(u8),
+
+    // A reference to another rule, e.g. lower_name.
+    Rule(String),
+
+    // A use of a rule template, e.g. sep_list(symbol_name, comma_sep). Templates are expanded when
+    // a grammar is read and checked, so only grammars from parse have these.
+    Call(String, Vec<Expr>),
+
+    // Every symbol from the first to the last, inclusive, e.g. LETTER_A ... LETTER_Z.
+    Range(Box<Expr>, Box<Expr>),
+
+    Seq(Vec<Expr>),
+    Union(Vec<Expr>),
+    Opt(Box<Expr>),
+    Star(Box<Expr>),
+    Plus(Box<Expr>)
+}
+
+// An ExprKind without Call, for the passes that run on grammars whose templates are expanded.
+#[derive(Copy, Clone, Debug)]
+pub enum Expanded<'a> {
+    Symbol(&'a String),
+    Byte(u8),
+    Rule(&'a String),
+    Range(&'a Expr, &'a Expr),
+    Seq(&'a [Expr]),
+    Union(&'a [Expr]),
+    Opt(&'a Expr),
+    Star(&'a Expr),
+    Plus(&'a Expr)
+}
+
+// An import of another grammar file, e.g. import "common.pglsf" as common;
+#[derive(Clone, Debug)]
+pub struct Import {
+    // The path of the file, relative to the file that imports it.
+    pub path: String,
+
+    // The name its rules are qualified with, e.g. common.lower_name.
+    pub alias: String,
+    pub span: Span
+}
+
+#[derive(Clone, Debug)]
+pub struct Grammar {
+    // The imports, until they are resolved. The rules and symbols of a resolved grammar include
+    // those of the files it imports.
+    pub imports: Vec<Import>,
+
+    pub symbols: Symbols,
+
+    // The rule given by @skip, if any. Only the @skip of the file being read counts, not those of
+    // the files it imports.
+    pub skip: Option<Skip>,
+
+    pub rules: Vec<RuleDef>
+}
+
+impl Grammar {
+    pub fn is_binary(&self) -> bool {
+        matches!(self.symbols, Symbols::Binary)
+    }
+
+    pub fn rule(&self, name: &str) -> Option<&RuleDef> {
+        self.rules.iter().find(|rule| rule.name == name)
+    }
+
+    // The rule parsing starts from: the rule named root if there is one, otherwise the first rule.
+    pub fn root(&self) -> Option<&RuleDef> {
+        self.rule("root").or_else(|| self.rules.first())
+    }
+
+    pub fn symbol_index(&self, name: &str) -> Option<usize> {
+        match &self.symbols {
+            Symbols::Named(decls) => decls.iter().position(|decl| decl.name == name),
+            Symbols::Binary => None
+        }
+    }
+}
+
+// Reads and checks the source of a grammar that was loaded from the file at the path. The files it
+// imports are loaded relative to it.
+pub fn read_from(path: &Path, source: &str) -> Result<Grammar, GrammarError> {
+    import::resolve(parse(source)?, path, &mut |path| std::fs::read_to_string(path).map_err(|error| error.to_string()))
+}
+
+// Reads the grammar in the file at the path. Also returns the source, for describing errors that
+// are found later.
+pub fn load(path: &str) -> Result<(Grammar, String), String> {
+    let source = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    match read_from(Path::new(path), &source) {
+        Ok(grammar) => Ok((grammar, source)),
+        Err(error) => Err(error.describe(path, &source))
+    }
+}
+
+#[cfg(test)]
+mod tests;
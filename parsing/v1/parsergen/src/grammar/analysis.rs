@@ -0,0 +1,211 @@
+// analysis.rs
+//
+// Properties of grammars that other passes rely on.
+//
+
+use super::*;
+
+// For every rule in the grammar, whether it can match the empty input.
+pub fn nullable_rules(grammar: &Grammar) -> Vec<bool> {
+    let mut nullable = vec![false; grammar.rules.len()];
+
+    // Iterate to a fixed point, since rules may refer to each other in any order.
+    loop {
+        let mut changed = false;
+        for (i, rule) in grammar.rules.iter().enumerate() {
+            if !nullable[i] && is_nullable(grammar, &nullable, &rule.expr) {
+                nullable[i] = true;
+                changed = true;
+            }
+        }
+
+        if !changed { return nullable }
+    }
+}
+
+// Whether the expression can match the empty input, given which rules can.
+pub fn is_nullable(grammar: &Grammar, nullable: &[bool], expr: &Expr) -> bool {
+    match expr.expanded() {
+        Expanded::Symbol(_) | Expanded::Byte(_) | Expanded::Range(_, _) => false,
+        Expanded::Rule(name) => grammar.rules.iter().position(|rule| &rule.name == name).is_some_and(|i| nullable[i]),
+        Expanded::Seq(items) => items.iter().all(|item| is_nullable(grammar, nullable, item)),
+        Expanded::Union(items) => items.iter().any(|item| is_nullable(grammar, nullable, item)),
+        Expanded::Opt(_) | Expanded::Star(_) => true,
+        Expanded::Plus(inner) => is_nullable(grammar, nullable, inner)
+    }
+}
+
+// The rules that the expression may call before it has consumed any input.
+fn left_calls<'a>(grammar: &Grammar, nullable: &[bool], expr: &'a Expr, result: &mut Vec<&'a str>) {
+    match expr.expanded() {
+        Expanded::Symbol(_) | Expanded::Byte(_) | Expanded::Range(_, _) => (),
+        Expanded::Rule(name) => result.push(name),
+        Expanded::Seq(items) => for item in items {
+            left_calls(grammar, nullable, item, result);
+            if !is_nullable(grammar, nullable, item) { break }
+        },
+        Expanded::Union(items) => for item in items {
+            left_calls(grammar, nullable, item, result);
+        },
+        Expanded::Opt(inner) | Expanded::Star(inner) | Expanded::Plus(inner) => left_calls(grammar, nullable, inner, result)
+    }
+}
+
+// Finds a rule that can call itself without consuming input. A ParseMachine would expand such a
+// rule forever. Returns the cycle of rule names, starting and ending with the same rule.
+pub fn left_recursion(grammar: &Grammar) -> Option<Vec<String>> {
+    let nullable = nullable_rules(grammar);
+    let edges: Vec<Vec<usize>> = grammar.rules.iter()
+        .map(|rule| {
+            let mut calls = Vec::new();
+            left_calls(grammar, &nullable, &rule.expr, &mut calls);
+            calls.iter()
+                .filter_map(|name| grammar.rules.iter().position(|rule| rule.name == *name))
+                .collect()
+        })
+        .collect();
+
+    // Depth first search for a back edge. 0 = unvisited, 1 = on the path, 2 = done.
+    fn visit(edges: &[Vec<usize>], marks: &mut [u8], path: &mut Vec<usize>, i: usize) -> Option<Vec<usize>> {
+        marks[i] = 1;
+        path.push(i);
+        for &j in &edges[i] {
+            match marks[j] {
+                0 => if let Some(cycle) = visit(edges, marks, path, j) { return Some(cycle) },
+                1 => {
+                    let start = path.iter().position(|&k| k == j).unwrap();
+                    let mut cycle = path[start..].to_vec();
+                    cycle.push(j);
+                    return Some(cycle)
+                },
+                _ => ()
+            }
+        }
+        path.pop();
+        marks[i] = 2;
+        None
+    }
+
+    let mut marks = vec![0u8; grammar.rules.len()];
+    for i in 0..grammar.rules.len() {
+        if marks[i] == 0 {
+            if let Some(cycle) = visit(&edges, &mut marks, &mut Vec::new(), i) {
+                return Some(cycle.into_iter().map(|k| grammar.rules[k].name.clone()).collect())
+            }
+        }
+    }
+
+    None
+}
+
+// Finds a repetition whose body can match the empty input. A ParseMachine would repeat such a body
+// forever without consuming anything.
+pub fn nullable_repetition(grammar: &Grammar) -> Option<Span> {
+    let nullable = nullable_rules(grammar);
+
+    fn find(grammar: &Grammar, nullable: &[bool], expr: &Expr) -> Option<Span> {
+        match &expr.kind {
+            ExprKind::Star(inner) | ExprKind::Plus(inner) => if is_nullable(grammar, nullable, inner) {
+                Some(expr.span)
+            } else {
+                find(grammar, nullable, inner)
+            },
+            ExprKind::Opt(inner) => find(grammar, nullable, inner),
+            ExprKind::Seq(items) | ExprKind::Union(items) => items.iter().find_map(|item| find(grammar, nullable, item)),
+            _ => None
+        }
+    }
+
+    grammar.rules.iter().find_map(|rule| find(grammar, &nullable, &rule.expr))
+}
+
+// For every rule, the indices of the rules it refers to, each listed once.
+pub fn rule_references(grammar: &Grammar) -> Vec<Vec<usize>> {
+    fn collect(grammar: &Grammar, expr: &Expr, result: &mut Vec<usize>) {
+        match expr.expanded() {
+            Expanded::Rule(name) => if let Some(i) = grammar.rules.iter().position(|rule| &rule.name == name) {
+                if !result.contains(&i) {
+                    result.push(i);
+                }
+            },
+            Expanded::Seq(items) | Expanded::Union(items) => for item in items {
+                collect(grammar, item, result);
+            },
+            Expanded::Opt(inner) | Expanded::Star(inner) | Expanded::Plus(inner) => collect(grammar, inner, result),
+            Expanded::Symbol(_) | Expanded::Byte(_) | Expanded::Range(_, _) => ()
+        }
+    }
+
+    grammar.rules.iter()
+        .map(|rule| {
+            let mut result = Vec::new();
+            collect(grammar, &rule.expr, &mut result);
+            result
+        })
+        .collect()
+}
+
+// Groups the rules into sets that refer to each other, directly or not (Tarjan's algorithm). A
+// rule is recursive if its set has more than one rule, or if it refers to itself.
+pub fn rule_components(grammar: &Grammar) -> Vec<Vec<usize>> {
+    struct State<'a> {
+        edges: &'a [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next: usize,
+        components: Vec<Vec<usize>>
+    }
+
+    fn connect(state: &mut State, i: usize) {
+        state.index[i] = Some(state.next);
+        state.low[i] = state.next;
+        state.next += 1;
+        state.stack.push(i);
+        state.on_stack[i] = true;
+
+        for &j in &state.edges[i] {
+            match state.index[j] {
+                None => {
+                    connect(state, j);
+                    state.low[i] = state.low[i].min(state.low[j]);
+                },
+                Some(index) => if state.on_stack[j] {
+                    state.low[i] = state.low[i].min(index);
+                }
+            }
+        }
+
+        if Some(state.low[i]) == state.index[i] {
+            let mut component = Vec::new();
+            while let Some(j) = state.stack.pop() {
+                state.on_stack[j] = false;
+                component.push(j);
+                if j == i { break }
+            }
+            component.sort_unstable();
+            state.components.push(component);
+        }
+    }
+
+    let edges = rule_references(grammar);
+    let count = grammar.rules.len();
+    let mut state = State {
+        edges: &edges,
+        index: vec![None; count],
+        low: vec![0; count],
+        on_stack: vec![false; count],
+        stack: Vec::new(),
+        next: 0,
+        components: Vec::new()
+    };
+
+    for i in 0..count {
+        if state.index[i].is_none() {
+            connect(&mut state, i);
+        }
+    }
+
+    state.components
+}
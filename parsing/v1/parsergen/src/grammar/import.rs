@@ -0,0 +1,187 @@
+// import.rs
+//
+// Resolves the imports of a grammar into one grammar.
+//
+//     import "common.pglsf" as common;
+//
+// brings in the rules of common.pglsf under qualified names such as common.lower_name, and merges
+// its symbols with the symbols of the importing grammar. A rule of the importing grammar with a
+// qualified name, e.g. common.whitespace = SPACE;, overrides the imported rule, also where the
+// imported rules refer to it. Templates can be imported and used the same way, e.g.
+// common.sep_list(item, COMMA). Imported grammars may import others in turn; a file that imports
+// itself, directly or not, is an error.
+//
+// A range in an imported rule, such as A ... C, becomes a union of the symbols between its ends in
+// the order the imported grammar declares them, as the merged symbols may be in another order.
+//
+// The spans of imported rules and symbols are those of the import, so that errors and reports
+// about them point at the file that is being read.
+//
+
+use std::path::{Component, Path, PathBuf};
+
+use super::reader::check;
+use super::*;
+
+// Resolves the imports of a grammar read from the file at the path, and checks the result.
+// read_file gives the text of an imported file, or a message saying why it cannot.
+pub fn resolve(grammar: Grammar, path: &Path, read_file: &mut dyn FnMut(&Path) -> Result<String, String>) -> Result<Grammar, GrammarError> {
+    let path = normalize(path);
+    let grammar = resolve_in(grammar, &path, read_file, &mut vec![path.clone()])?;
+
+    // Templates may come from imports, so they are only expanded once everything is in one place.
+    // Skipping applies to the expanded rules.
+    skip::insert(template::expand(grammar)?)
+}
+
+// Removes the . and .. components of a path where it can, so that the same file is recognized when
+// it is reached by different paths.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir if matches!(result.components().next_back(), Some(Component::Normal(_))) => {
+                result.pop();
+            },
+            component => result.push(component)
+        }
+    }
+    result
+}
+
+// stack holds the files being imported, starting with the file that is being read.
+fn resolve_in(
+    mut grammar: Grammar,
+    path: &Path,
+    read_file: &mut dyn FnMut(&Path) -> Result<String, String>,
+    stack: &mut Vec<PathBuf>
+) -> Result<Grammar, GrammarError> {
+    let imports = std::mem::take(&mut grammar.imports);
+    let mut imported = Vec::new();
+
+    for (i, import) in imports.iter().enumerate() {
+        if imports[..i].iter().any(|other| other.alias == import.alias) {
+            return Err(GrammarError::new(format!("'{}' is imported more than once", import.alias), import.span))
+        }
+
+        let import_path = normalize(&path.parent().unwrap_or(Path::new("")).join(&import.path));
+        if let Some(start) = stack.iter().position(|other| *other == import_path) {
+            let cycle: Vec<String> = stack[start..].iter().chain([&import_path]).map(|path| path.display().to_string()).collect();
+            return Err(GrammarError::new(format!("import cycle: {}", cycle.join(" -> ")), import.span))
+        }
+
+        let source = read_file(&import_path)
+            .map_err(|message| GrammarError::new(format!("cannot import \"{}\": {}", import.path, message), import.span))?;
+
+        // Errors in the imported file are reported at the import.
+        let in_import = |error: GrammarError| {
+            GrammarError::new(format!("in {}", error.describe(&import_path.display().to_string(), &source)), import.span)
+        };
+
+        stack.push(import_path.clone());
+        let module = parse(&source).and_then(|module| resolve_in(module, &import_path, read_file, stack));
+        stack.pop();
+        let module = module.map_err(in_import)?;
+
+        for rule in module.rules {
+            imported.push(RuleDef {
+                name: format!("{}.{}", import.alias, rule.name),
+                name_span: import.span,
+                expr: qualify(rule.expr, &import.alias, &rule.params, &module.symbols, import.span),
+                params: rule.params,
+                action: rule.action.map(|action| Action { span: import.span, ..action }),
+                visibility: rule.visibility,
+                token: rule.token
+            });
+        }
+        merge_symbols(&mut grammar.symbols, module.symbols, import)?;
+    }
+
+    let mut rules = Vec::new();
+    let mut overridden: Vec<String> = Vec::new();
+    for rule in grammar.rules {
+        if !rule.name.contains('.') {
+            rules.push(rule);
+            continue
+        }
+
+        if overridden.contains(&rule.name) {
+            return Err(GrammarError::new(format!("rule '{}' is overridden more than once", rule.name), rule.name_span))
+        }
+        match imported.iter_mut().find(|other| other.name == rule.name) {
+            Some(other) => {
+                overridden.push(rule.name.clone());
+                *other = rule;
+            },
+            None => return Err(GrammarError::new(format!("rule '{}' overrides nothing, as no imported rule has that name", rule.name), rule.name_span))
+        }
+    }
+
+    // The grammar's own rules come first, so that its first rule is still the root by default.
+    rules.extend(imported);
+    grammar.rules = rules;
+    check(&grammar)?;
+    Ok(grammar)
+}
+
+// Adds the symbols of an imported grammar to the symbols of the grammar importing it.
+fn merge_symbols(symbols: &mut Symbols, imported: Symbols, import: &Import) -> Result<(), GrammarError> {
+    let mismatch = || GrammarError::new("a binary grammar cannot be combined with a grammar that declares symbols", import.span);
+
+    match imported {
+        Symbols::Named(imported) => match symbols {
+            Symbols::Named(decls) => {
+                for decl in imported {
+                    if !decls.iter().any(|other| other.name == decl.name) {
+                        decls.push(SymbolDecl { name: decl.name, span: import.span });
+                    }
+                }
+                Ok(())
+            },
+            Symbols::Binary if imported.is_empty() => Ok(()),
+            Symbols::Binary => Err(mismatch())
+        },
+        Symbols::Binary => match symbols {
+            Symbols::Binary => Ok(()),
+            Symbols::Named(decls) if decls.is_empty() => {
+                *symbols = Symbols::Binary;
+                Ok(())
+            },
+            Symbols::Named(_) => Err(mismatch())
+        }
+    }
+}
+
+// Qualifies the rules an imported expression refers to with the name of the import, and moves the
+// expression to the span of the import. params are the parameters of the rule it belongs to, which
+// are left as they are, and symbols are those of the imported grammar.
+fn qualify(expr: Expr, alias: &str, params: &[String], symbols: &Symbols, span: Span) -> Expr {
+    let boxed = |inner: Box<Expr>| Box::new(qualify(*inner, alias, params, symbols, span));
+    let all = |items: Vec<Expr>| items.into_iter().map(|item| qualify(item, alias, params, symbols, span)).collect();
+    let position = |name: &str| match symbols {
+        Symbols::Named(decls) => decls.iter().position(|decl| decl.name == name),
+        Symbols::Binary => None
+    };
+    let kind = match expr.kind {
+        ExprKind::Rule(name) if params.contains(&name) => ExprKind::Rule(name),
+        ExprKind::Rule(name) => ExprKind::Rule(format!("{}.{}", alias, name)),
+        ExprKind::Call(name, args) => ExprKind::Call(format!("{}.{}", alias, name), all(args)),
+        ExprKind::Symbol(_) | ExprKind::Byte(_) => expr.kind,
+        ExprKind::Range(first, last) => match (&first.kind, &last.kind, symbols) {
+            (ExprKind::Symbol(first_name), ExprKind::Symbol(last_name), Symbols::Named(decls)) => match (position(first_name), position(last_name)) {
+                (Some(start), Some(end)) if start <= end => ExprKind::Union(decls[start..=end].iter()
+                    .map(|decl| Expr::new(ExprKind::Symbol(decl.name.clone()), span))
+                    .collect()),
+                _ => ExprKind::Range(boxed(first), boxed(last))
+            },
+            _ => ExprKind::Range(boxed(first), boxed(last))
+        },
+        ExprKind::Seq(items) => ExprKind::Seq(all(items)),
+        ExprKind::Union(items) => ExprKind::Union(all(items)),
+        ExprKind::Opt(inner) => ExprKind::Opt(boxed(inner)),
+        ExprKind::Star(inner) => ExprKind::Star(boxed(inner)),
+        ExprKind::Plus(inner) => ExprKind::Plus(boxed(inner))
+    };
+    Expr { kind, span, label: expr.label }
+}
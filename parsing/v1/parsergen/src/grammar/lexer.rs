@@ -0,0 +1,206 @@
+// lexer.rs
+//
+// Splits the text of a .pglsf file into tokens. Comments are kept as tokens so that tools which
+// care about them can see them; the reader skips them.
+//
+
+use super::{GrammarError, Span};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    LowerName,
+    UpperName,
+    Hex,
+    String,
+
+    // A block of Rust code, from its '{' to the matching '}'.
+    Action,
+
+    // An '@' followed by a name, e.g. @hidden.
+    Annotation,
+    Arrow,
+    Colon,
+    EqualSign,
+    Semicolon,
+    Comma,
+    Pipe,
+    LeftParen,
+    RightParen,
+    QuestionMark,
+    Asterisk,
+    PlusSign,
+    Ellipsis,
+    Comment,
+    End
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span
+}
+
+impl Token {
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.start..self.span.end]
+    }
+}
+
+impl TokenKind {
+    pub fn describe(self) -> &'static str {
+        match self {
+            TokenKind::LowerName => "a lower_name",
+            TokenKind::UpperName => "an UPPER_NAME",
+            TokenKind::Hex => "a hex byte",
+            TokenKind::String => "a string",
+            TokenKind::Action => "an action",
+            TokenKind::Annotation => "an annotation",
+            TokenKind::Arrow => "'=>'",
+            TokenKind::Colon => "':'",
+            TokenKind::EqualSign => "'='",
+            TokenKind::Semicolon => "';'",
+            TokenKind::Comma => "','",
+            TokenKind::Pipe => "'|'",
+            TokenKind::LeftParen => "'('",
+            TokenKind::RightParen => "')'",
+            TokenKind::QuestionMark => "'?'",
+            TokenKind::Asterisk => "'*'",
+            TokenKind::PlusSign => "'+'",
+            TokenKind::Ellipsis => "'...'",
+            TokenKind::Comment => "a comment",
+            TokenKind::End => "the end of the file"
+        }
+    }
+}
+
+fn is_name_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+// The end of the Rust block starting at the '{' at start, past its matching '}'. Braces inside
+// strings, characters and comments do not count.
+fn action_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1)
+                }
+            },
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' { i += 1 }
+                    i += 1;
+                }
+            },
+            b'\'' if bytes.get(i + 2) == Some(&b'\'') => i += 2,
+            b'\'' if bytes.get(i + 1) == Some(&b'\\') => {
+                i += 3;
+                while i < bytes.len() && bytes[i] != b'\'' { i += 1 }
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' { i += 1 }
+            },
+            _ => ()
+        }
+        i += 1;
+    }
+    None
+}
+
+// Tokenizes the whole source. The last token is always End.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, GrammarError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let c = bytes[i];
+
+        let kind = match c {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                i += 1;
+                continue
+            },
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' { i += 1 }
+                TokenKind::Comment
+            },
+            b'@' => {
+                i += 1;
+                while i < bytes.len() && is_name_char(bytes[i]) { i += 1 }
+                TokenKind::Annotation
+            },
+            b'=' if bytes.get(i + 1) == Some(&b'>') => { i += 2; TokenKind::Arrow },
+            b'=' => { i += 1; TokenKind::EqualSign },
+            b'{' => {
+                i = action_end(bytes, i).ok_or_else(|| GrammarError::new("unterminated action", Span::new(start, start + 1)))?;
+                TokenKind::Action
+            },
+            b';' => { i += 1; TokenKind::Semicolon },
+            b':' => { i += 1; TokenKind::Colon },
+            b',' => { i += 1; TokenKind::Comma },
+            b'|' => { i += 1; TokenKind::Pipe },
+            b'(' => { i += 1; TokenKind::LeftParen },
+            b')' => { i += 1; TokenKind::RightParen },
+            b'?' => { i += 1; TokenKind::QuestionMark },
+            b'*' => { i += 1; TokenKind::Asterisk },
+            b'+' => { i += 1; TokenKind::PlusSign },
+            b'.' => if source[i..].starts_with("...") {
+                i += 3;
+                TokenKind::Ellipsis
+            } else {
+                return Err(GrammarError::new("expected '...'", Span::new(i, i + 1)))
+            },
+            b'0' if bytes.get(i + 1) == Some(&b'x') => {
+                i += 2;
+                while i < bytes.len() && bytes[i].is_ascii_hexdigit() { i += 1 }
+                if i - start != 4 {
+                    return Err(GrammarError::new("hex bytes are written as 0x followed by two digits", Span::new(start, i)))
+                }
+                TokenKind::Hex
+            },
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' && bytes[i] != b'\n' { i += 1 }
+                if bytes.get(i) != Some(&b'"') {
+                    return Err(GrammarError::new("unterminated string", Span::new(start, i)))
+                }
+                i += 1;
+                TokenKind::String
+            },
+            c if c.is_ascii_lowercase() => {
+                // The name of an imported rule is qualified by the name it was imported as, e.g.
+                // common.lower_name.
+                loop {
+                    while i < bytes.len() && is_name_char(bytes[i]) { i += 1 }
+                    if bytes.get(i) == Some(&b'.') && bytes.get(i + 1).is_some_and(u8::is_ascii_lowercase) {
+                        i += 1;
+                    } else {
+                        break
+                    }
+                }
+                TokenKind::LowerName
+            },
+            c if c.is_ascii_uppercase() => {
+                while i < bytes.len() && is_name_char(bytes[i]) { i += 1 }
+                TokenKind::UpperName
+            },
+            _ => {
+                let len = source[i..].chars().next().map_or(1, char::len_utf8);
+                return Err(GrammarError::new("unexpected character", Span::new(i, i + len)))
+            }
+        };
+
+        tokens.push(Token { kind, span: Span::new(start, i) });
+    }
+
+    tokens.push(Token { kind: TokenKind::End, span: Span::new(bytes.len(), bytes.len()) });
+    Ok(tokens)
+}
@@ -0,0 +1,248 @@
+// lookahead.rs
+//
+// FIRST and FOLLOW sets of the nodes of a Program, and the prediction table built from them. For
+// every node that chooses between ways of continuing (a union, range, optional or repetition),
+// the table holds the symbols each way can start with. A ParseMachine that knows the next symbol
+// then only takes the ways that can read it. Where every symbol leaves at most one way, the choice
+// is LL(1) and the machine does not fork at all; elsewhere it still forks, but only between the
+// ways the symbol allows.
+//
+// The sets are over-approximations: FOLLOW is computed for each node wherever it is used, not per
+// use. That can make choices look ambiguous that are not, but never leaves out a way that could
+// have read the symbol.
+//
+
+use super::program::{Node, NodeId};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SymbolSet {
+    words: Vec<u64>
+}
+
+impl SymbolSet {
+    pub fn contains(&self, symbol: u32) -> bool {
+        let (word, bit) = (symbol as usize / 64, symbol % 64);
+        self.words.get(word).is_some_and(|word| word & (1 << bit) != 0)
+    }
+
+    pub fn insert(&mut self, symbol: u32) {
+        let (word, bit) = (symbol as usize / 64, symbol % 64);
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << bit;
+    }
+
+    // Adds the symbols of the other set. Returns whether any were new.
+    pub fn extend(&mut self, other: &SymbolSet) -> bool {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        let mut changed = false;
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            changed |= *other & !*word != 0;
+            *word |= other;
+        }
+        changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words.iter()
+            .enumerate()
+            .flat_map(|(i, &word)| (0..64).filter(move |bit| word & (1 << bit) != 0).map(move |bit| (i * 64 + bit) as u32))
+    }
+}
+
+// A choice between the ways a node can continue, in the order the node expands to them.
+#[derive(Clone, Debug)]
+pub struct Decision {
+    pub node: NodeId,
+
+    // The rule the node belongs to.
+    pub rule: usize,
+
+    // The symbols each way can start with, including what may follow the node if it can be empty.
+    pub alternatives: Vec<SymbolSet>
+}
+
+impl Decision {
+    // The symbols that leave more than one way to go.
+    pub fn conflicts(&self) -> SymbolSet {
+        let mut seen = SymbolSet::default();
+        let mut conflicts = SymbolSet::default();
+        for alternative in &self.alternatives {
+            for symbol in alternative.iter() {
+                match seen.contains(symbol) {
+                    true => conflicts.insert(symbol),
+                    false => seen.insert(symbol)
+                }
+            }
+        }
+        conflicts
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.conflicts().is_empty()
+    }
+}
+
+// The decisions of the nodes, rule by rule. bodies are the body nodes of the rules.
+pub fn decisions(nodes: &[Node], bodies: &[NodeId]) -> Vec<Decision> {
+    let (first, nullable) = first_sets(nodes, bodies);
+    let follow = follow_sets(nodes, bodies, &first, &nullable);
+
+    // The symbols a way can start with, given what follows the node choosing it.
+    let start = |way: NodeId, after: &SymbolSet| {
+        let mut set = first[way as usize].clone();
+        if nullable[way as usize] {
+            set.extend(after);
+        }
+        set
+    };
+
+    let mut result = Vec::new();
+    for (rule, &body) in bodies.iter().enumerate() {
+        let mut pending = vec![body];
+        while let Some(node) = pending.pop() {
+            let after = &follow[node as usize];
+            let alternatives = match &nodes[node as usize] {
+                Node::Union(ways) | Node::Range(ways) => ways.iter().map(|&way| start(way, after)).collect(),
+                Node::Opt(body, _) | Node::Star(body, _) | Node::Repeat(body) => vec![start(*body, after), after.clone()],
+                _ => Vec::new()
+            };
+            if !alternatives.is_empty() {
+                result.push(Decision { node, rule, alternatives });
+            }
+
+            // A repetition's body is reached through its Plus node already.
+            if !matches!(nodes[node as usize], Node::Repeat(_)) {
+                pending.extend(children(&nodes[node as usize]).into_iter().rev());
+            }
+        }
+    }
+    result
+}
+
+// The nodes a node expands to, apart from rule bodies, which belong to their own rules.
+pub fn children(node: &Node) -> Vec<NodeId> {
+    match node {
+        Node::Seq(items) | Node::Union(items) | Node::Range(items) => items.clone(),
+        Node::Opt(a, b) | Node::Star(a, b) | Node::Plus(a, b) => vec![*a, *b],
+        Node::Repeat(body) => vec![*body],
+        Node::Call(_) | Node::Exit(_) | Node::Label(_) | Node::Symbol(_) | Node::Skip => Vec::new()
+    }
+}
+
+// For every node, the symbols it can start with and whether it can match nothing.
+pub fn first_sets(nodes: &[Node], bodies: &[NodeId]) -> (Vec<SymbolSet>, Vec<bool>) {
+    let mut first = vec![SymbolSet::default(); nodes.len()];
+    let mut nullable = vec![false; nodes.len()];
+
+    // Iterate to a fixed point, since rules may call each other in any order.
+    loop {
+        let mut changed = false;
+        for (i, node) in nodes.iter().enumerate() {
+            let mut set = SymbolSet::default();
+            let empty = match node {
+                Node::Call(rule) => {
+                    let body = bodies[*rule] as usize;
+                    set.extend(&first[body]);
+                    nullable[body]
+                },
+                Node::Exit(_) | Node::Label(_) | Node::Skip => true,
+                Node::Symbol(symbol) => {
+                    set.insert(*symbol);
+                    false
+                },
+                Node::Seq(items) => {
+                    let mut empty = true;
+                    for &item in items {
+                        set.extend(&first[item as usize]);
+                        if !nullable[item as usize] {
+                            empty = false;
+                            break
+                        }
+                    }
+                    empty
+                },
+                Node::Union(ways) | Node::Range(ways) => {
+                    for &way in ways {
+                        set.extend(&first[way as usize]);
+                    }
+                    ways.iter().any(|&way| nullable[way as usize])
+                },
+                Node::Opt(body, _) | Node::Star(body, _) | Node::Repeat(body) => {
+                    set.extend(&first[*body as usize]);
+                    true
+                },
+                Node::Plus(body, _) => {
+                    set.extend(&first[*body as usize]);
+                    nullable[*body as usize]
+                }
+            };
+
+            changed |= first[i].extend(&set);
+            if empty && !nullable[i] {
+                nullable[i] = true;
+                changed = true;
+            }
+        }
+
+        if !changed { return (first, nullable) }
+    }
+}
+
+// For every node, the symbols that can come right after it. Nothing comes after the root rule.
+fn follow_sets(nodes: &[Node], bodies: &[NodeId], first: &[SymbolSet], nullable: &[bool]) -> Vec<SymbolSet> {
+    let mut follow = vec![SymbolSet::default(); nodes.len()];
+
+    loop {
+        let mut changed = false;
+        for (i, node) in nodes.iter().enumerate() {
+            let after = follow[i].clone();
+            let mut add = |node: NodeId, set: &SymbolSet| changed |= follow[node as usize].extend(set);
+
+            match node {
+                // The Exit after the body matches nothing.
+                Node::Call(rule) => add(bodies[*rule], &after),
+                Node::Seq(items) => {
+                    // Walk backwards, keeping what can follow each item.
+                    let mut rest = after;
+                    for &item in items.iter().rev() {
+                        add(item, &rest);
+                        if !nullable[item as usize] {
+                            rest = SymbolSet::default();
+                        }
+                        rest.extend(&first[item as usize]);
+                    }
+                },
+                Node::Union(ways) | Node::Range(ways) => for &way in ways {
+                    add(way, &after);
+                },
+                Node::Opt(body, skip) | Node::Star(body, skip) => {
+                    add(*body, &after);
+                    add(*skip, &after);
+                },
+                // The body is followed by the Repeat node, which may run it again or stop.
+                Node::Plus(body, repeat) => {
+                    let mut set = first[*body as usize].clone();
+                    set.extend(&after);
+                    add(*body, &set);
+                    add(*repeat, &after);
+                },
+                Node::Repeat(body) => {
+                    let mut set = first[*body as usize].clone();
+                    set.extend(&after);
+                    add(*body, &set);
+                },
+                Node::Exit(_) | Node::Label(_) | Node::Symbol(_) | Node::Skip => ()
+            }
+        }
+
+        if !changed { return follow }
+    }
+}
@@ -0,0 +1,401 @@
+// program.rs
+//
+// Compiles a Grammar into a table of nodes that a ParseMachine can run directly. Every position in
+// every rule expression becomes a node, and ProgramRule, a reference to one node, is the RuleType
+// of the machine. Executing a node replaces it on the stack with what it expands to, forking once
+// per alternative of a union, optional or repetition.
+//
+// Calls to rules are bracketed by a Call node and an Exit node, so the symbols a ParseMachine
+// returns can be rebuilt into a tree. The Call and Exit nodes of @inline rules are not returned,
+// and trees leave out @hidden rules. Likewise every choice leaves a trace: leaving out an optional
+// or repetition executes a Skip node, so it is possible to tell from the returned symbols which
+// way each choice went.
+//
+// @hidden only changes trees. The Call and Exit nodes of a hidden rule are still returned, as the
+// tree builder and the action reducer need them to drop the rule's symbols and values. Anything
+// that reads the returned symbols directly sees hidden rules, so a rule that should leave no trace
+// in them must be @inline.
+//
+// Each choice also has a row in a prediction table (see lookahead.rs), so that when the next symbol
+// is known only the ways that can read it are taken.
+//
+
+use super::analysis;
+use super::lookahead::{self, Decision};
+use super::*;
+use crate::arena::Arena;
+use crate::parse_machine::{ParseMachine, ParseRule, Stacks, SymbolOrRule};
+use crate::tree::Tree;
+
+pub type NodeId = u32;
+
+#[derive(Clone, Debug)]
+pub enum Node {
+    // Enters the rule with the given index.
+    Call(usize),
+
+    // Leaves the rule with the given index, which was entered last.
+    Exit(usize),
+
+    // Labels the symbol or rule call that follows, which then becomes a field of the tree.
+    Label(String),
+
+    Symbol(u32),
+    Seq(Vec<NodeId>),
+    Union(Vec<NodeId>),
+
+    // A union of the symbols of a range, one alternative per symbol.
+    Range(Vec<NodeId>),
+
+    // The body, and the Skip node taken instead of it.
+    Opt(NodeId, NodeId),
+
+    // The Plus node for one or more repetitions, and the Skip node taken instead of it.
+    Star(NodeId, NodeId),
+
+    // The body, and the Repeat node that follows it.
+    Plus(NodeId, NodeId),
+
+    // Runs the body again, or stops.
+    Repeat(NodeId),
+
+    // Does nothing.
+    Skip
+}
+
+#[derive(Clone, Debug)]
+pub struct CompiledRule {
+    pub name: String,
+    pub visibility: Visibility,
+    pub call: NodeId,
+    pub body: NodeId,
+    pub exit: NodeId
+}
+
+pub struct Program {
+    // The names of the symbols, or None for a binary grammar whose symbols are bytes.
+    symbol_names: Option<Vec<String>>,
+    nodes: Vec<Node>,
+
+    // The part of the grammar each node was compiled from.
+    spans: Vec<Span>,
+    rules: Vec<CompiledRule>,
+    root: usize,
+
+    // The choices of the rules, and for every node the index of its choice, if it makes one.
+    decisions: Vec<Decision>,
+    decision_of: Vec<Option<usize>>
+}
+
+#[derive(Copy, Clone)]
+pub struct ProgramRule<'a> {
+    program: &'a Program,
+    node: NodeId
+}
+
+impl<'a> ProgramRule<'a> {
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+}
+
+// Rules are the same when they are the same node of the same program.
+impl PartialEq for ProgramRule<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.program, other.program) && self.node == other.node
+    }
+}
+
+pub type ProgramSymbolOrRule<'a> = SymbolOrRule<u32, ProgramRule<'a>>;
+
+impl Program {
+    // Compiles a grammar that has been read and checked. Grammars that would make the parse
+    // machine expand forever, through left recursion or repeating something empty, are rejected.
+    pub fn compile(grammar: &Grammar) -> Result<Program, GrammarError> {
+        let root = match grammar.root() {
+            Some(root) => grammar.rules.iter().position(|rule| rule.name == root.name).unwrap(),
+            None => return Err(GrammarError::new("the grammar has no rules", Span::default()))
+        };
+        if grammar.rules[root].visibility != Visibility::Visible {
+            return Err(GrammarError::new("the root rule cannot be @hidden or @inline", grammar.rules[root].name_span))
+        }
+
+        if let Some(cycle) = analysis::left_recursion(grammar) {
+            let span = grammar.rule(&cycle[0]).unwrap().name_span;
+            return Err(GrammarError::new(format!("rule '{}' is left-recursive ({})", cycle[0], cycle.join(" -> ")), span))
+        }
+
+        if let Some(span) = analysis::nullable_repetition(grammar) {
+            return Err(GrammarError::new("the body of this repetition can match nothing", span))
+        }
+
+        let mut program = Program {
+            symbol_names: match &grammar.symbols {
+                Symbols::Named(decls) => Some(decls.iter().map(|decl| decl.name.clone()).collect()),
+                Symbols::Binary => None
+            },
+            nodes: Vec::new(),
+            spans: Vec::new(),
+            rules: Vec::new(),
+            root,
+            decisions: Vec::new(),
+            decision_of: Vec::new()
+        };
+
+        // Each rule gets a Call node to start parsing from, and an Exit node shared by every call to it.
+        for (i, rule) in grammar.rules.iter().enumerate() {
+            let call = program.push(Node::Call(i), rule.name_span);
+            let exit = program.push(Node::Exit(i), rule.name_span);
+            program.rules.push(CompiledRule { name: rule.name.clone(), visibility: rule.visibility, call, body: 0, exit });
+        }
+
+        for (i, rule) in grammar.rules.iter().enumerate() {
+            program.rules[i].body = program.compile_expr(grammar, &rule.expr);
+        }
+
+        let bodies: Vec<NodeId> = program.rules.iter().map(|rule| rule.body).collect();
+        program.decisions = lookahead::decisions(&program.nodes, &bodies);
+        program.decision_of = vec![None; program.nodes.len()];
+        for (i, decision) in program.decisions.iter().enumerate() {
+            program.decision_of[decision.node as usize] = Some(i);
+        }
+
+        Ok(program)
+    }
+
+    fn push(&mut self, node: Node, span: Span) -> NodeId {
+        self.nodes.push(node);
+        self.spans.push(span);
+        (self.nodes.len() - 1) as NodeId
+    }
+
+    fn compile_expr(&mut self, grammar: &Grammar, expr: &Expr) -> NodeId {
+        let node = self.compile_kind(grammar, expr);
+        match &expr.label {
+            Some(label) => {
+                let label = self.push(Node::Label(label.name.clone()), label.span);
+                self.push(Node::Seq(vec![label, node]), expr.span)
+            },
+            None => node
+        }
+    }
+
+    fn compile_kind(&mut self, grammar: &Grammar, expr: &Expr) -> NodeId {
+        let node = match expr.expanded() {
+            Expanded::Symbol(_) | Expanded::Byte(_) => Node::Symbol(self.symbol_of(grammar, expr)),
+            Expanded::Rule(name) => {
+                let i = grammar.rules.iter().position(|rule| &rule.name == name).unwrap();
+                match grammar.rules[i].visibility {
+                    // The call leaves no trace, so it goes in a sequence of its own, which records
+                    // that this way was taken.
+                    Visibility::Inline => Node::Seq(vec![self.push(Node::Call(i), expr.span)]),
+                    _ => Node::Call(i)
+                }
+            },
+            Expanded::Range(first, last) => {
+                let (first, last) = (self.symbol_of(grammar, first), self.symbol_of(grammar, last));
+                let alternatives = (first.min(last)..=first.max(last))
+                    .map(|symbol| self.push(Node::Symbol(symbol), expr.span))
+                    .collect();
+                Node::Range(alternatives)
+            },
+            Expanded::Seq(items) => Node::Seq(items.iter().map(|item| self.compile_expr(grammar, item)).collect()),
+            Expanded::Union(items) => Node::Union(items.iter().map(|item| self.compile_expr(grammar, item)).collect()),
+            Expanded::Opt(inner) => {
+                let body = self.compile_expr(grammar, inner);
+                Node::Opt(body, self.push(Node::Skip, expr.span))
+            },
+            Expanded::Star(inner) => {
+                let plus = self.compile_plus(grammar, inner, expr.span);
+                Node::Star(plus, self.push(Node::Skip, expr.span))
+            },
+            Expanded::Plus(inner) => return self.compile_plus(grammar, inner, expr.span)
+        };
+
+        self.push(node, expr.span)
+    }
+
+    fn compile_plus(&mut self, grammar: &Grammar, inner: &Expr, span: Span) -> NodeId {
+        let body = self.compile_expr(grammar, inner);
+        let repeat = self.push(Node::Repeat(body), span);
+        self.push(Node::Plus(body, repeat), span)
+    }
+
+    fn symbol_of(&self, grammar: &Grammar, expr: &Expr) -> u32 {
+        match &expr.kind {
+            ExprKind::Symbol(name) => grammar.symbol_index(name).unwrap() as u32,
+            ExprKind::Byte(byte) => *byte as u32,
+            _ => panic!()
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.symbol_names.is_none()
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn span(&self, node: NodeId) -> Span {
+        self.spans[node as usize]
+    }
+
+    pub fn rules(&self) -> &[CompiledRule] {
+        &self.rules
+    }
+
+    // Every choice in the grammar, rule by rule, with the symbols that lead to each way.
+    pub fn decisions(&self) -> &[Decision] {
+        &self.decisions
+    }
+
+    // The index of the rule parsing starts from.
+    pub fn root_index(&self) -> usize {
+        self.root
+    }
+
+    pub fn root(&self) -> ProgramRule<'_> {
+        ProgramRule { program: self, node: self.rules[self.root].call }
+    }
+
+    // The node as a rule of the machine, e.g. to return it among the parsed symbols.
+    pub fn node_rule(&self, node: NodeId) -> ProgramRule<'_> {
+        ProgramRule { program: self, node }
+    }
+
+    pub fn machine(&self) -> ParseMachine<u32, ProgramRule<'_>> {
+        ParseMachine::new(self.root())
+    }
+
+    // A machine that keeps its stacks in an arena, which is cheaper where it parses many inputs one
+    // after another, resetting in between.
+    pub fn arena_machine(&self) -> ParseMachine<u32, ProgramRule<'_>, Arena<ProgramSymbolOrRule<'_>>> {
+        ParseMachine::with_stacks(self.root(), Arena::new())
+    }
+
+    // The symbol with the given name, or for binary grammars the byte written as 0xNN.
+    pub fn symbol(&self, name: &str) -> Option<u32> {
+        match &self.symbol_names {
+            Some(names) => names.iter().position(|other| other == name).map(|i| i as u32),
+            None => name.strip_prefix("0x")
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .map(|byte| byte as u32)
+        }
+    }
+
+    // How many symbols there are: the declared ones, or the 256 bytes of a binary grammar.
+    pub fn symbol_count(&self) -> u32 {
+        match &self.symbol_names {
+            Some(names) => names.len() as u32,
+            None => 256
+        }
+    }
+
+    pub fn symbol_name(&self, symbol: u32) -> String {
+        match &self.symbol_names {
+            Some(names) => names[symbol as usize].clone(),
+            None => format!("0x{:02X}", symbol)
+        }
+    }
+
+    // Rebuilds the tree of rule calls from the symbols returned by a ParseMachine. Returns None if
+    // the calls and exits do not pair up.
+    pub fn tree(&self, symbols: &[ProgramSymbolOrRule]) -> Option<Tree> {
+        // The rules entered and not yet left, each with the label it was called with.
+        let mut open: Vec<(Option<String>, Tree)> = Vec::new();
+        let mut root = None;
+
+        // The label for the next symbol or rule call.
+        let mut label = None;
+        let labelled = |label: Option<String>, tree: Tree| match label {
+            Some(label) => Tree::Field(label, Box::new(tree)),
+            None => tree
+        };
+
+        for item in symbols {
+            match item {
+                SymbolOrRule::Symbol(symbol) => {
+                    let leaf = labelled(label.take(), Tree::Leaf(self.symbol_name(*symbol)));
+                    open.last_mut()?.1.push(leaf);
+                },
+                SymbolOrRule::Rule(rule) => match &self.nodes[rule.node as usize] {
+                    Node::Call(i) => open.push((label.take(), Tree::Node(self.rules[*i].name.clone(), Vec::new()))),
+                    Node::Exit(i) => {
+                        let (label, done) = open.pop()?;
+                        match open.last_mut() {
+                            Some(_) if self.rules[*i].visibility == Visibility::Hidden => (),
+                            Some((_, parent)) => parent.push(labelled(label, done)),
+                            None => root = Some(done)
+                        }
+                    },
+                    Node::Label(name) => label = Some(name.clone()),
+                    _ => ()
+                }
+            }
+        }
+
+        if open.is_empty() { root } else { None }
+    }
+}
+
+impl<'a> ParseRule<u32, ProgramRule<'a>> for ProgramRule<'a> {
+    fn execute<S>(&self, stacks: &mut S, stack: S::Stack) -> Vec<S::Stack>
+        where S: Stacks<ProgramSymbolOrRule<'a>>
+    {
+        let program = self.program;
+        let rule = |node: NodeId| SymbolOrRule::Rule(ProgramRule { program, node });
+
+        match &program.nodes[self.node as usize] {
+            Node::Call(i) => {
+                let compiled = &program.rules[*i];
+                let exit = stacks.cons(rule(compiled.exit), stack);
+                vec![stacks.cons(rule(compiled.body), exit)]
+            },
+            Node::Exit(_) | Node::Label(_) => vec![stack],
+            Node::Symbol(symbol) => vec![stacks.cons(SymbolOrRule::Symbol(*symbol), stack)],
+            Node::Seq(items) => vec![items.iter().rev().fold(stack, |tail, &item| stacks.cons(rule(item), tail))],
+            Node::Union(alternatives) | Node::Range(alternatives) =>
+                alternatives.iter().map(|&item| stacks.cons(rule(item), stack.clone())).collect(),
+            Node::Opt(body, skip) | Node::Star(body, skip) => vec![stacks.cons(rule(*body), stack.clone()), stacks.cons(rule(*skip), stack)],
+            Node::Plus(body, repeat) => {
+                let repeat = stacks.cons(rule(*repeat), stack);
+                vec![stacks.cons(rule(*body), repeat)]
+            },
+            Node::Repeat(body) => {
+                let repeat = stacks.cons(rule(self.node), stack.clone());
+                vec![stacks.cons(rule(*body), repeat), stack]
+            },
+            Node::Skip => vec![stack]
+        }
+    }
+
+    fn predict<S>(&self, stacks: &mut S, stack: S::Stack, lookahead: u32) -> Vec<S::Stack>
+        where S: Stacks<ProgramSymbolOrRule<'a>>
+    {
+        let expanded = self.execute(stacks, stack);
+        match self.program.decision_of[self.node as usize] {
+            Some(i) => expanded.into_iter()
+                .zip(&self.program.decisions[i].alternatives)
+                .filter(|(_, symbols)| symbols.contains(lookahead))
+                .map(|(stack, _)| stack)
+                .collect(),
+            None => expanded
+        }
+    }
+
+    fn expands_once(&self) -> bool {
+        match &self.program.nodes[self.node as usize] {
+            Node::Union(alternatives) | Node::Range(alternatives) => alternatives.len() == 1,
+            Node::Opt(..) | Node::Star(..) | Node::Repeat(_) => false,
+            _ => true
+        }
+    }
+
+    fn is_recorded(&self) -> bool {
+        match self.program.nodes[self.node as usize] {
+            Node::Call(i) | Node::Exit(i) => self.program.rules[i].visibility != Visibility::Inline,
+            _ => true
+        }
+    }
+}
@@ -0,0 +1,505 @@
+// reader.rs
+//
+// Reads the text of a .pglsf file into a Grammar.
+//
+// file    = *import 'symbols' symbols 'grammar' ['@skip' lower_name ';'] *rule
+// import  = 'import' STRING 'as' lower_name ';'
+// symbols = 'binary' ';' | [UPPER_NAME *(',' UPPER_NAME)] ';'
+// rule    = *('@hidden' | '@inline' | '@token') lower_name ['(' lower_name *(',' lower_name) ')'] '='
+//           union ['=>' action] ';'
+// union   = seq *('|' seq)
+// seq     = +unary
+// unary   = ('?' | '*' | '+') [label] atom | [label] (range | atom)
+// label   = lower_name ':'
+// range   = (UPPER_NAME | 0xNN) '...' (UPPER_NAME | 0xNN)
+// atom    = UPPER_NAME | 0xNN | lower_name | call | '(' union ')'
+// call    = lower_name '(' arg *(',' arg) ')'
+// arg     = UPPER_NAME | 0xNN | lower_name | call
+//
+// A label names what it captures as a field of the tree, so only symbols, ranges and rules can be
+// labelled.
+//
+// An action is a block of Rust code in braces, kept as it is written.
+//
+// The '(' of a call follows the name without any space, which tells it apart from a rule followed
+// by a parenthesized expression.
+//
+
+use std::path::Path;
+
+use super::import::resolve;
+use super::lexer::{tokenize, Token, TokenKind};
+use super::*;
+
+struct Reader<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Token {
+        self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos];
+        if token.kind != TokenKind::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn text(&self, token: Token) -> &'a str {
+        token.text(self.source)
+    }
+
+    fn unexpected(&self, expected: &str) -> GrammarError {
+        let token = self.peek();
+        let found = match token.kind {
+            TokenKind::LowerName | TokenKind::UpperName | TokenKind::Hex => format!("'{}'", self.text(token)),
+            kind => kind.describe().to_string()
+        };
+        GrammarError::new(format!("expected {}, found {}", expected, found), token.span)
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, GrammarError> {
+        if self.peek().kind == kind {
+            Ok(self.next())
+        } else {
+            Err(self.unexpected(kind.describe()))
+        }
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<Token, GrammarError> {
+        let token = self.peek();
+        if token.kind == TokenKind::LowerName && self.text(token) == word {
+            Ok(self.next())
+        } else {
+            Err(self.unexpected(&format!("'{}'", word)))
+        }
+    }
+
+    fn file(&mut self) -> Result<Grammar, GrammarError> {
+        let mut imports = Vec::new();
+        while self.peek().kind == TokenKind::LowerName && self.text(self.peek()) == "import" {
+            imports.push(self.import()?);
+        }
+
+        self.expect_word("symbols")?;
+        let symbols = self.symbols()?;
+        self.expect_word("grammar")?;
+
+        let mut skip = None;
+        let token = self.peek();
+        if token.kind == TokenKind::Annotation && self.text(token) == "@skip" {
+            self.next();
+            let name = self.expect(TokenKind::LowerName)?;
+            let end = self.expect(TokenKind::Semicolon)?;
+            skip = Some(Skip { rule: self.text(name).to_string(), name_span: name.span, span: token.span.to(end.span) });
+        }
+
+        let mut rules = Vec::new();
+        while self.peek().kind != TokenKind::End {
+            rules.push(self.rule()?);
+        }
+
+        Ok(Grammar { imports, symbols, skip, rules })
+    }
+
+    fn import(&mut self) -> Result<Import, GrammarError> {
+        let start = self.next();
+        let path = self.expect(TokenKind::String)?;
+        self.expect_word("as")?;
+        let alias = self.expect(TokenKind::LowerName)?;
+        if self.text(alias).contains('.') {
+            return Err(GrammarError::new("the name of an import cannot contain '.'", alias.span))
+        }
+        let end = self.expect(TokenKind::Semicolon)?;
+
+        let path_text = self.text(path);
+        Ok(Import {
+            path: path_text[1..path_text.len() - 1].to_string(),
+            alias: self.text(alias).to_string(),
+            span: start.span.to(end.span)
+        })
+    }
+
+    fn symbols(&mut self) -> Result<Symbols, GrammarError> {
+        let token = self.peek();
+        if token.kind == TokenKind::LowerName && self.text(token) == "binary" {
+            self.next();
+            self.expect(TokenKind::Semicolon)?;
+            return Ok(Symbols::Binary)
+        }
+
+        // A grammar may take all of its symbols from the files it imports.
+        if token.kind == TokenKind::Semicolon {
+            self.next();
+            return Ok(Symbols::Named(Vec::new()))
+        }
+
+        let mut decls = Vec::new();
+        loop {
+            let name = self.expect(TokenKind::UpperName)?;
+            decls.push(SymbolDecl { name: self.text(name).to_string(), span: name.span });
+            match self.next().kind {
+                TokenKind::Comma => continue,
+                TokenKind::Semicolon => break,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.unexpected("',' or ';'"))
+                }
+            }
+        }
+
+        Ok(Symbols::Named(decls))
+    }
+
+    fn rule(&mut self) -> Result<RuleDef, GrammarError> {
+        let mut visibility = Visibility::Visible;
+        let mut token = false;
+        while self.peek().kind == TokenKind::Annotation {
+            let annotation = self.next();
+            let text = self.text(annotation);
+            let given = match text {
+                "@token" => None,
+                "@hidden" => Some(Visibility::Hidden),
+                "@inline" => Some(Visibility::Inline),
+                "@skip" => return Err(GrammarError::new("@skip goes before the first rule", annotation.span)),
+                _ => return Err(GrammarError::new(format!("unknown annotation '{}'", text), annotation.span))
+            };
+            match given {
+                None if !token => token = true,
+                Some(given) if visibility == Visibility::Visible => visibility = given,
+                Some(given) if visibility != given => {
+                    return Err(GrammarError::new("a rule cannot be both @hidden and @inline", annotation.span))
+                },
+                _ => return Err(GrammarError::new(format!("{} is given more than once", text), annotation.span))
+            }
+        }
+
+        let name = self.expect(TokenKind::LowerName)?;
+
+        let mut params = Vec::new();
+        if self.peek().kind == TokenKind::LeftParen {
+            self.next();
+            loop {
+                let param = self.expect(TokenKind::LowerName)?;
+                params.push(self.text(param).to_string());
+                match self.next().kind {
+                    TokenKind::Comma => continue,
+                    TokenKind::RightParen => break,
+                    _ => {
+                        self.pos -= 1;
+                        return Err(self.unexpected("',' or ')'"))
+                    }
+                }
+            }
+        }
+
+        self.expect(TokenKind::EqualSign)?;
+        let expr = self.union()?;
+
+        let mut action = None;
+        if self.peek().kind == TokenKind::Arrow {
+            self.next();
+            let block = self.expect(TokenKind::Action)?;
+            action = Some(Action { code: self.text(block).to_string(), span: block.span });
+        }
+
+        self.expect(TokenKind::Semicolon)?;
+        Ok(RuleDef { name: self.text(name).to_string(), name_span: name.span, params, expr, action, visibility, token })
+    }
+
+    fn union(&mut self) -> Result<Expr, GrammarError> {
+        let mut alternatives = vec![self.seq()?];
+        while self.peek().kind == TokenKind::Pipe {
+            self.next();
+            alternatives.push(self.seq()?);
+        }
+
+        Ok(collapse(alternatives, ExprKind::Union))
+    }
+
+    fn seq(&mut self) -> Result<Expr, GrammarError> {
+        let mut items = vec![self.unary()?];
+        while !matches!(self.peek().kind, TokenKind::Pipe | TokenKind::RightParen | TokenKind::Semicolon | TokenKind::Arrow | TokenKind::End) {
+            items.push(self.unary()?);
+        }
+
+        Ok(collapse(items, ExprKind::Seq))
+    }
+
+    fn unary(&mut self) -> Result<Expr, GrammarError> {
+        let operator = self.peek();
+        let wrap: fn(Box<Expr>) -> ExprKind = match operator.kind {
+            TokenKind::QuestionMark => ExprKind::Opt,
+            TokenKind::Asterisk => ExprKind::Star,
+            TokenKind::PlusSign => ExprKind::Plus,
+            _ => return self.labelled(Self::range)
+        };
+
+        self.next();
+        let operand = self.labelled(Self::atom)?;
+        let span = operator.span.to(operand.span);
+        Ok(Expr::new(wrap(Box::new(operand)), span))
+    }
+
+    // Reads what parse reads, along with the label in front of it, if any.
+    fn labelled(&mut self, parse: fn(&mut Self) -> Result<Expr, GrammarError>) -> Result<Expr, GrammarError> {
+        let name = self.peek();
+        if name.kind != TokenKind::LowerName || self.tokens[self.pos + 1].kind != TokenKind::Colon {
+            return parse(self)
+        }
+        self.pos += 2;
+
+        if matches!(self.peek().kind, TokenKind::QuestionMark | TokenKind::Asterisk | TokenKind::PlusSign) {
+            return Err(GrammarError::new("a label goes after '?', '*' or '+', e.g. ?name:rule", name.span))
+        }
+        let mut expr = parse(self)?;
+        match expr.kind {
+            ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Rule(_) | ExprKind::Call(_, _) | ExprKind::Range(_, _) => (),
+            _ => return Err(GrammarError::new("only symbols, ranges and rules can be labelled", name.span.to(expr.span)))
+        }
+        if expr.label.is_some() {
+            return Err(GrammarError::new("this is labelled already", name.span.to(expr.span)))
+        }
+
+        expr.label = Some(Label { name: self.text(name).to_string(), span: name.span });
+        Ok(expr)
+    }
+
+    fn range(&mut self) -> Result<Expr, GrammarError> {
+        let first = self.atom()?;
+        if self.peek().kind != TokenKind::Ellipsis {
+            return Ok(first)
+        }
+
+        self.next();
+        let last = self.atom()?;
+        match (&first.kind, &last.kind) {
+            (ExprKind::Symbol(_), ExprKind::Symbol(_)) | (ExprKind::Byte(_), ExprKind::Byte(_)) => {
+                let span = first.span.to(last.span);
+                Ok(Expr::new(ExprKind::Range(Box::new(first), Box::new(last)), span))
+            },
+            _ => Err(GrammarError::new("a range must be between two symbols or two bytes", first.span.to(last.span)))
+        }
+    }
+
+    // A call: the name has been read, and the '(' is next.
+    fn call(&mut self, name: Token) -> Result<Expr, GrammarError> {
+        self.next();
+        let mut args = Vec::new();
+        loop {
+            let token = self.peek();
+            let arg = match token.kind {
+                TokenKind::UpperName | TokenKind::Hex | TokenKind::LowerName => self.atom()?,
+                _ => return Err(self.unexpected("a symbol or a rule name, as the arguments of a template"))
+            };
+            args.push(arg);
+            match self.next().kind {
+                TokenKind::Comma => continue,
+                TokenKind::RightParen => break,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.unexpected("',' or ')'"))
+                }
+            }
+        }
+
+        let end = self.tokens[self.pos - 1].span;
+        Ok(Expr::new(ExprKind::Call(self.text(name).to_string(), args), name.span.to(end)))
+    }
+
+    fn atom(&mut self) -> Result<Expr, GrammarError> {
+        let token = self.peek();
+        let kind = match token.kind {
+            TokenKind::UpperName => ExprKind::Symbol(self.text(token).to_string()),
+            TokenKind::LowerName => {
+                self.next();
+                let next = self.peek();
+                if next.kind == TokenKind::LeftParen && next.span.start == token.span.end {
+                    return self.call(token)
+                }
+                return Ok(Expr::new(ExprKind::Rule(self.text(token).to_string()), token.span))
+            },
+            TokenKind::Hex => match u8::from_str_radix(&self.text(token)[2..], 16) {
+                Ok(byte) => ExprKind::Byte(byte),
+                Err(_) => return Err(GrammarError::new("invalid hex byte", token.span))
+            },
+            TokenKind::LeftParen => {
+                self.next();
+                let inner = self.union()?;
+                let close = self.expect(TokenKind::RightParen)?;
+                return Ok(Expr { span: token.span.to(close.span), ..inner })
+            },
+            _ => return Err(self.unexpected("a symbol, a rule name or '('"))
+        };
+
+        self.next();
+        Ok(Expr::new(kind, token.span))
+    }
+}
+
+// A single item stands for itself; several become a Seq or Union.
+fn collapse(mut items: Vec<Expr>, wrap: fn(Vec<Expr>) -> ExprKind) -> Expr {
+    if items.len() == 1 {
+        items.pop().unwrap()
+    } else {
+        let span = items[0].span.to(items[items.len() - 1].span);
+        Expr::new(wrap(items), span)
+    }
+}
+
+// Checks the names in a grammar: every symbol is declared once, every rule is defined once, every
+// reference names a rule, a parameter or a declared symbol, templates are used with as many
+// arguments as they have parameters and have no actions, @hidden and @inline rules have no actions,
+// no rule uses a label twice or labels an @inline or @hidden rule, @skip names a rule, and bytes
+// only appear in binary grammars.
+pub fn check(grammar: &Grammar) -> Result<(), GrammarError> {
+    if let Symbols::Named(decls) = &grammar.symbols {
+        for (i, decl) in decls.iter().enumerate() {
+            if decls[..i].iter().any(|other| other.name == decl.name) {
+                return Err(GrammarError::new(format!("symbol '{}' is declared more than once", decl.name), decl.span))
+            }
+        }
+    }
+
+    for (i, rule) in grammar.rules.iter().enumerate() {
+        if grammar.rules[..i].iter().any(|other| other.name == rule.name) {
+            return Err(GrammarError::new(format!("rule '{}' is defined more than once", rule.name), rule.name_span))
+        }
+        for (j, param) in rule.params.iter().enumerate() {
+            if rule.params[..j].contains(param) {
+                return Err(GrammarError::new(format!("parameter '{}' is listed more than once", param), rule.name_span))
+            }
+        }
+        if let (false, Some(action)) = (rule.params.is_empty(), &rule.action) {
+            return Err(GrammarError::new("templates cannot have actions", action.span))
+        }
+
+        // The values of @hidden rules are dropped, and @inline rules are never reduced at all.
+        if let (Visibility::Hidden | Visibility::Inline, Some(action)) = (rule.visibility, &rule.action) {
+            let annotation = if rule.visibility == Visibility::Hidden { "@hidden" } else { "@inline" };
+            return Err(GrammarError::new(format!("rule '{}' is {}, so its action would never run", rule.name, annotation), action.span))
+        }
+        check_expr(grammar, &rule.params, &rule.expr)?;
+        check_labels(grammar, &rule.expr, &mut Vec::new())?;
+    }
+
+    if let Some(skip) = &grammar.skip {
+        match grammar.rule(&skip.rule) {
+            Some(rule) if !rule.params.is_empty() => return Err(GrammarError::new("@skip needs a rule, not a template", skip.name_span)),
+            Some(_) => (),
+            None => return Err(GrammarError::new(format!("rule '{}' is not defined", skip.rule), skip.name_span))
+        }
+    }
+
+    Ok(())
+}
+
+// seen holds the labels found so far in the rule. The alternatives of a union are never captured
+// together, so they may use the same labels.
+fn check_labels(grammar: &Grammar, expr: &Expr, seen: &mut Vec<String>) -> Result<(), GrammarError> {
+    if let Some(label) = &expr.label {
+        if seen.contains(&label.name) {
+            return Err(GrammarError::new(format!("label '{}' is used more than once", label.name), label.span))
+        }
+        seen.push(label.name.clone());
+
+        // Trees have no node for @inline and @hidden rules, so such a label would never find one.
+        if let ExprKind::Rule(name) | ExprKind::Call(name, _) = &expr.kind {
+            let annotation = match grammar.rule(name).map(|rule| rule.visibility) {
+                Some(Visibility::Inline) => Some("@inline"),
+                Some(Visibility::Hidden) => Some("@hidden"),
+                _ => None
+            };
+            if let Some(annotation) = annotation {
+                return Err(GrammarError::new(format!("rule '{}' is {}, so it has no node to label", name, annotation), label.span))
+            }
+        }
+    }
+
+    match &expr.kind {
+        ExprKind::Union(alternatives) => {
+            let outside = seen.clone();
+            for alternative in alternatives {
+                let mut labels = outside.clone();
+                check_labels(grammar, alternative, &mut labels)?;
+                for label in labels {
+                    if !seen.contains(&label) {
+                        seen.push(label);
+                    }
+                }
+            }
+            Ok(())
+        },
+        ExprKind::Seq(items) => items.iter().try_for_each(|item| check_labels(grammar, item, seen)),
+        ExprKind::Opt(inner) | ExprKind::Star(inner) | ExprKind::Plus(inner) => check_labels(grammar, inner, seen),
+        ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Rule(_) | ExprKind::Call(_, _) | ExprKind::Range(_, _) => Ok(())
+    }
+}
+
+// params are the parameters of the rule the expression belongs to.
+fn check_expr(grammar: &Grammar, params: &[String], expr: &Expr) -> Result<(), GrammarError> {
+    match &expr.kind {
+        ExprKind::Symbol(name) => match grammar.symbols {
+            Symbols::Binary => Err(GrammarError::new("binary grammars use hex bytes instead of symbols", expr.span)),
+            Symbols::Named(_) => if grammar.symbol_index(name).is_some() {
+                Ok(())
+            } else {
+                Err(GrammarError::new(format!("symbol '{}' is not declared", name), expr.span))
+            }
+        },
+        ExprKind::Byte(_) => if grammar.is_binary() {
+            Ok(())
+        } else {
+            Err(GrammarError::new("hex bytes are only allowed in binary grammars", expr.span))
+        },
+        ExprKind::Rule(name) if params.contains(name) => Ok(()),
+        ExprKind::Rule(name) => match grammar.rule(name) {
+            Some(rule) if !rule.params.is_empty() => {
+                Err(GrammarError::new(format!("template '{}' needs {} arguments", name, rule.params.len()), expr.span))
+            },
+            Some(_) => Ok(()),
+            None => Err(GrammarError::new(format!("rule '{}' is not defined", name), expr.span))
+        },
+        ExprKind::Call(name, args) => {
+            match grammar.rule(name) {
+                Some(rule) if rule.params.len() == args.len() => (),
+                Some(rule) if rule.params.is_empty() => {
+                    return Err(GrammarError::new(format!("rule '{}' is not a template", name), expr.span))
+                },
+                Some(rule) => {
+                    let message = format!("template '{}' needs {} arguments, not {}", name, rule.params.len(), args.len());
+                    return Err(GrammarError::new(message, expr.span))
+                },
+                None => return Err(GrammarError::new(format!("template '{}' is not defined", name), expr.span))
+            }
+            args.iter().try_for_each(|arg| check_expr(grammar, params, arg))
+        },
+        ExprKind::Range(first, last) => {
+            check_expr(grammar, params, first)?;
+            check_expr(grammar, params, last)
+        },
+        ExprKind::Seq(items) | ExprKind::Union(items) => items.iter().try_for_each(|item| check_expr(grammar, params, item)),
+        ExprKind::Opt(inner) | ExprKind::Star(inner) | ExprKind::Plus(inner) => check_expr(grammar, params, inner)
+    }
+}
+
+// Reads a grammar without checking its names, for tools that only care about its layout.
+pub fn parse(source: &str) -> Result<Grammar, GrammarError> {
+    let tokens = tokenize(source)?
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Comment)
+        .collect();
+
+    Reader { source, tokens, pos: 0 }.file()
+}
+
+// Reads and checks a grammar that imports nothing.
+pub fn read(source: &str) -> Result<Grammar, GrammarError> {
+    let no_files = &mut |_: &Path| Err("imports are only resolved in grammars loaded from a file".to_string());
+    resolve(parse(source)?, Path::new(""), no_files)
+}
@@ -0,0 +1,122 @@
+// skip.rs
+//
+// Applies @skip. With
+//
+//     @skip ws;
+//
+// the input may have a ws after every symbol and token that the rules match, and before the first
+// one, without the rules saying so: root = A B; reads as root = ?ws A ?ws B ?ws;. A token is a call
+// to a rule marked @token, such as @token name = +letter;, which is read as one word. Nothing is
+// skipped inside tokens, the skip rule, or any rule they use, so rules such as letter above are
+// read as they are written. The other rules should leave out their own uses of the skip rule,
+// which would make their input ambiguous.
+//
+// Putting the skip after what it follows, instead of between the items of a sequence, means that
+// optional items never give it two places to go.
+//
+
+use super::analysis::{nullable_rules, rule_references};
+use super::*;
+
+// Inserts the skip rule into a checked grammar with its templates expanded.
+pub fn insert(mut grammar: Grammar) -> Result<Grammar, GrammarError> {
+    let Some(skip) = grammar.skip.clone() else { return Ok(grammar) };
+    let skip_index = grammar.rules.iter().position(|rule| rule.name == skip.rule).unwrap();
+
+    // The skip rule must consume something, or skipping it could be done in any number of ways.
+    if nullable_rules(&grammar)[skip_index] {
+        return Err(GrammarError::new(format!("rule '{}' can match nothing, so it cannot be skipped", skip.rule), skip.name_span))
+    }
+
+    // The rules read as they are written: the skip rule, the tokens, and the rules they use.
+    let references = rule_references(&grammar);
+    let mut lexical = vec![false; grammar.rules.len()];
+    let mut pending: Vec<usize> = (0..grammar.rules.len())
+        .filter(|&i| i == skip_index || grammar.rules[i].token)
+        .collect();
+    while let Some(i) = pending.pop() {
+        if !lexical[i] {
+            lexical[i] = true;
+            pending.extend(&references[i]);
+        }
+    }
+
+    let root = grammar.root().map(|root| root.name.clone());
+    let inserter = Inserter { grammar: &grammar, lexical: &lexical, skip: &skip };
+    let exprs: Vec<Option<Expr>> = grammar.rules.iter()
+        .enumerate()
+        .map(|(i, rule)| match lexical[i] {
+            true => None,
+            false if root.as_ref() == Some(&rule.name) => {
+                let mut items = vec![inserter.skip(rule.expr.span)];
+                match inserter.expr(rule.expr.clone()) {
+                    Expr { kind: ExprKind::Seq(body), label: None, .. } => items.extend(body),
+                    body => items.push(body)
+                }
+                Some(Expr::new(ExprKind::Seq(items), rule.expr.span))
+            },
+            false => Some(inserter.expr(rule.expr.clone()))
+        })
+        .collect();
+
+    for (rule, expr) in grammar.rules.iter_mut().zip(exprs) {
+        if let Some(expr) = expr {
+            rule.expr = expr;
+        }
+    }
+    Ok(grammar)
+}
+
+struct Inserter<'a> {
+    grammar: &'a Grammar,
+
+    // Whether each rule is read as it is written.
+    lexical: &'a [bool],
+    skip: &'a Skip
+}
+
+impl Inserter<'_> {
+    // An optional use of the skip rule.
+    fn skip(&self, span: Span) -> Expr {
+        let rule = Expr::new(ExprKind::Rule(self.skip.rule.clone()), span);
+        Expr::new(ExprKind::Opt(Box::new(rule)), span)
+    }
+
+    // Whether the input may be skipped after the expression.
+    fn is_word(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Range(_, _) => true,
+            ExprKind::Rule(name) => *name != self.skip.rule && self.grammar.rules.iter()
+                .position(|rule| rule.name == *name)
+                .is_some_and(|i| self.lexical[i]),
+            _ => false
+        }
+    }
+
+    fn expr(&self, expr: Expr) -> Expr {
+        if self.is_word(&expr) {
+            let span = expr.span;
+            return Expr::new(ExprKind::Seq(vec![expr, self.skip(span)]), span)
+        }
+
+        let kind = match expr.kind {
+            ExprKind::Seq(items) => {
+                // Items that became sequences are spliced in, to keep the rule flat.
+                let mut result = Vec::new();
+                for item in items {
+                    match self.expr(item) {
+                        Expr { kind: ExprKind::Seq(inner), label: None, .. } => result.extend(inner),
+                        item => result.push(item)
+                    }
+                }
+                ExprKind::Seq(result)
+            },
+            ExprKind::Union(items) => ExprKind::Union(items.into_iter().map(|item| self.expr(item)).collect()),
+            ExprKind::Opt(inner) => ExprKind::Opt(Box::new(self.expr(*inner))),
+            ExprKind::Star(inner) => ExprKind::Star(Box::new(self.expr(*inner))),
+            ExprKind::Plus(inner) => ExprKind::Plus(Box::new(self.expr(*inner))),
+            kind => kind
+        };
+        Expr { kind, ..expr }
+    }
+}
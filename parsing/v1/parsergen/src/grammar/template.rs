@@ -0,0 +1,115 @@
+// template.rs
+//
+// Expands rule templates. A template is a rule with parameters,
+//
+//     sep_list(item, sep) = item *(sep item);
+//
+// and each use of it with different arguments, e.g. sep_list(symbol_name, comma_sep), becomes a
+// rule of its own, named after the use: sep_list<symbol_name,comma_sep>. Arguments are symbols,
+// rule names or other uses of templates, which keeps those names readable. The templates
+// themselves are left out of the expanded grammar.
+//
+
+use super::*;
+
+// How deeply uses of templates may be nested inside the rules they expand to. A template that uses
+// itself with ever larger arguments, e.g. t(x) = x ?t(t(x));, would otherwise never finish.
+const MAX_DEPTH: usize = 32;
+
+// Expands the templates of a checked grammar.
+pub fn expand(grammar: Grammar) -> Result<Grammar, GrammarError> {
+    let (templates, mut rules): (Vec<RuleDef>, Vec<RuleDef>) = grammar.rules
+        .into_iter()
+        .partition(|rule| !rule.params.is_empty());
+
+    let mut expander = Expander { templates: &templates, instances: Vec::new() };
+    for rule in &mut rules {
+        let span = rule.expr.span;
+        let expr = std::mem::replace(&mut rule.expr, Expr::new(ExprKind::Seq(Vec::new()), span));
+        rule.expr = expander.expr(expr, 0)?;
+    }
+
+    rules.extend(expander.instances);
+    Ok(Grammar { rules, ..grammar })
+}
+
+struct Expander<'a> {
+    templates: &'a [RuleDef],
+
+    // The rules made from templates so far.
+    instances: Vec<RuleDef>
+}
+
+impl Expander<'_> {
+    // Replaces the uses of templates in the expression with references to their rules.
+    fn expr(&mut self, expr: Expr, depth: usize) -> Result<Expr, GrammarError> {
+        let kind = match expr.kind {
+            ExprKind::Call(name, _) if depth == MAX_DEPTH => {
+                return Err(GrammarError::new(format!("template '{}' uses itself with arguments that keep growing", name), expr.span))
+            },
+            ExprKind::Call(name, args) => {
+                let args = args.into_iter().map(|arg| self.expr(arg, depth)).collect::<Result<Vec<_>, _>>()?;
+                ExprKind::Rule(self.instance(&name, &args, depth)?)
+            },
+            ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Rule(_) | ExprKind::Range(_, _) => expr.kind,
+            ExprKind::Seq(items) => ExprKind::Seq(items.into_iter().map(|item| self.expr(item, depth)).collect::<Result<_, _>>()?),
+            ExprKind::Union(items) => ExprKind::Union(items.into_iter().map(|item| self.expr(item, depth)).collect::<Result<_, _>>()?),
+            ExprKind::Opt(inner) => ExprKind::Opt(Box::new(self.expr(*inner, depth)?)),
+            ExprKind::Star(inner) => ExprKind::Star(Box::new(self.expr(*inner, depth)?)),
+            ExprKind::Plus(inner) => ExprKind::Plus(Box::new(self.expr(*inner, depth)?))
+        };
+        Ok(Expr { kind, ..expr })
+    }
+
+    // The name of the rule for a use of a template, which is made the first time it is needed.
+    fn instance(&mut self, name: &str, args: &[Expr], depth: usize) -> Result<String, GrammarError> {
+        let arg_names: Vec<String> = args.iter()
+            .map(|arg| match &arg.kind {
+                ExprKind::Symbol(name) | ExprKind::Rule(name) => name.clone(),
+                ExprKind::Byte(byte) => format!("0x{:02X}", byte),
+                _ => unreachable!("the arguments of templates are names")
+            })
+            .collect();
+        let instance = format!("{}<{}>", name, arg_names.join(","));
+
+        if self.instances.iter().any(|rule| rule.name == instance) {
+            return Ok(instance)
+        }
+        // The rule is added before its body is expanded, so that a template that uses itself with
+        // the same arguments refers back to it.
+        let template = self.templates.iter().find(|template| template.name == name).unwrap();
+        let index = self.instances.len();
+        self.instances.push(RuleDef {
+            name: instance.clone(),
+            name_span: template.name_span,
+            params: Vec::new(),
+            expr: Expr::new(ExprKind::Seq(Vec::new()), template.expr.span),
+            action: None,
+            visibility: template.visibility,
+            token: template.token
+        });
+
+        let body = substitute(template.expr.clone(), &template.params, args);
+        self.instances[index].expr = self.expr(body, depth + 1)?;
+        Ok(instance)
+    }
+}
+
+// Replaces the parameters in the body of a template with the arguments.
+fn substitute(expr: Expr, params: &[String], args: &[Expr]) -> Expr {
+    let boxed = |inner: Box<Expr>| Box::new(substitute(*inner, params, args));
+    let kind = match expr.kind {
+        ExprKind::Rule(name) => match params.iter().position(|param| *param == name) {
+            Some(i) => args[i].kind.clone(),
+            None => ExprKind::Rule(name)
+        },
+        ExprKind::Call(name, call_args) => ExprKind::Call(name, call_args.into_iter().map(|arg| substitute(arg, params, args)).collect()),
+        ExprKind::Symbol(_) | ExprKind::Byte(_) | ExprKind::Range(_, _) => expr.kind,
+        ExprKind::Seq(items) => ExprKind::Seq(items.into_iter().map(|item| substitute(item, params, args)).collect()),
+        ExprKind::Union(items) => ExprKind::Union(items.into_iter().map(|item| substitute(item, params, args)).collect()),
+        ExprKind::Opt(inner) => ExprKind::Opt(boxed(inner)),
+        ExprKind::Star(inner) => ExprKind::Star(boxed(inner)),
+        ExprKind::Plus(inner) => ExprKind::Plus(boxed(inner))
+    };
+    Expr { kind, ..expr }
+}
@@ -0,0 +1,399 @@
+use crate::grammar::*;
+use crate::formatter::{self, expr_text};
+use crate::grammar::program::Program;
+use crate::importer::symbol_name;
+use crate::tester::{input_symbols, run, Outcome};
+use crate::tree::Tree;
+
+const PGLSF: &str = include_str!("../../../languages/pglsf.pglsf");
+const ASCII_TO_PGLSF: &str = include_str!("../../../languages/ascii_to_pglsf.pglsf");
+
+fn outcome(source: &str, input: &str) -> Outcome {
+    let program = Program::compile(&read(source).unwrap()).unwrap();
+    run(&program, &input_symbols(&program, input).unwrap())
+}
+
+#[test]
+fn test_read_languages() {
+    let pglsf = read(PGLSF).unwrap();
+    assert_eq!(pglsf.symbol_index("COLON"), Some(79));
+    assert_eq!(pglsf.root().unwrap().name, "root");
+    assert!(pglsf.rule("rule_union_expr_arg").is_some());
+
+    let ascii = read(ASCII_TO_PGLSF).unwrap();
+    assert!(ascii.is_binary());
+    assert_eq!(ascii.rules.len(), 81);
+}
+
+#[test]
+fn test_read_errors() {
+    let source = "symbols A;\ngrammar\n\troot = A B;\n";
+    let error = read(source).unwrap_err();
+    assert_eq!(error.message, "symbol 'B' is not declared");
+    assert_eq!(error.span.line_col(source), (3, 11));
+
+    assert!(read("symbols A;\ngrammar\n\troot = A\n").is_err());
+    assert!(read("symbols A, A;\ngrammar\n\troot = A;\n").is_err());
+    assert!(read("symbols A;\ngrammar\n\troot = 0x41;\n").is_err());
+    assert!(read("symbols binary;\ngrammar\n\troot = A;\n").is_err());
+}
+
+#[test]
+fn test_compile_rejects_left_recursion() {
+    let grammar = read("symbols A, PLUS;\ngrammar\n\texpr = sum | A;\n\tsum = expr PLUS A;\n").unwrap();
+    let error = Program::compile(&grammar).err().unwrap();
+    assert!(error.message.contains("rule 'expr' is left-recursive (expr -> sum -> expr)"));
+
+    let grammar = read("symbols A;\ngrammar\n\troot = *(?A);\n").unwrap();
+    assert!(Program::compile(&grammar).is_err());
+}
+
+#[test]
+fn test_pglsf_reads_itself() {
+    let program = Program::compile(&read(PGLSF).unwrap()).unwrap();
+    let text = |source: &str| {
+        let names: Vec<String> = source.chars().map(symbol_name).collect();
+        input_symbols(&program, &names.join(" ")).unwrap()
+    };
+
+    assert!(matches!(run(&program, &text(PGLSF)), Outcome::Accept(_)));
+    assert!(matches!(run(&program, &text("symbols\n\tA, B;\n\ngrammar\n\troot = A;\n\tfoo = B;\n")), Outcome::Accept(_)));
+    assert!(matches!(run(&program, &text("symbols\n\tA,\n\tB;\ngrammar\n\troot =\n\t\tA\n\t\t| B;\n")), Outcome::Accept(_)));
+
+    let imports = "import \"lib/common.pglsf\" as common;\nsymbols;\ngrammar\n\troot = common.word;\n\tcommon.space = common.tab;\n";
+    assert!(matches!(run(&program, &text(imports)), Outcome::Accept(_)));
+
+    let actions = "symbols\n\tA;\ngrammar\n\troot = +A => { if let [a] = &args[..] { \"}\\\"\".len() } else { 0 } };\n";
+    assert!(matches!(run(&program, &text(actions)), Outcome::Accept(_)));
+}
+
+#[test]
+fn test_run_outcomes() {
+    let source = "symbols A, B, C;\ngrammar\n\troot = +item ?C;\n\titem = A | B;\n";
+    assert!(matches!(outcome(source, "A B"), Outcome::Accept(_)));
+    assert!(matches!(outcome(source, "A B C"), Outcome::Accept(_)));
+    assert_eq!(outcome(source, "A C B"), Outcome::Reject(2));
+    assert_eq!(outcome(source, ""), Outcome::Reject(0));
+
+    let ambiguous = "symbols A;\ngrammar\n\troot = ?A ?A;\n";
+    assert_eq!(outcome(ambiguous, "A"), Outcome::Ambiguous);
+
+    // Both ways of reading A are done, so the B after it is rejected rather than ambiguous.
+    assert_eq!(outcome("symbols A, B;\ngrammar\n\troot = A | A;\n", "A B"), Outcome::Reject(1));
+}
+
+#[test]
+fn test_run_tree() {
+    let source = "symbols A, B;\ngrammar\n\troot = pair *pair;\n\tpair = A B;\n";
+    let expected = Tree::parse("(root (pair A B) (pair A B))").unwrap();
+    assert_eq!(outcome(source, "A B A B"), Outcome::Accept(expected));
+}
+
+#[test]
+fn test_run_binary() {
+    let source = "symbols binary;\ngrammar\n\tword = +letter;\n\tletter = 0x61 ... 0x7A;\n";
+    let expected = Tree::parse("(word (letter 0x68) (letter 0x69))").unwrap();
+    assert_eq!(outcome(source, "hi"), Outcome::Accept(expected));
+    assert_eq!(outcome(source, "hI"), Outcome::Reject(1));
+}
+
+// Resolves the imports of the first file, reading the others from the list.
+fn resolve_files(files: &[(&str, &str)]) -> Result<Grammar, GrammarError> {
+    let grammar = parse(files[0].1)?;
+    import::resolve(grammar, std::path::Path::new(files[0].0), &mut |path| {
+        files.iter()
+            .find(|(name, _)| std::path::Path::new(name) == path)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| "no such file".to_string())
+    })
+}
+
+const COMMON: &str = "symbols SPACE, LETTER;\ngrammar\n\tword = +LETTER ?space;\n\tspace = SPACE;\n";
+
+#[test]
+fn test_import() {
+    let main = "import \"lib/common.pglsf\" as common;\nsymbols COMMA;\ngrammar\n\troot = common.word *(COMMA common.word);\n";
+    let grammar = resolve_files(&[("main.pglsf", main), ("lib/common.pglsf", COMMON)]).unwrap();
+
+    assert_eq!(grammar.root().unwrap().name, "root");
+    assert_eq!(grammar.symbol_index("COMMA"), Some(0));
+    assert_eq!(grammar.symbol_index("LETTER"), Some(2));
+    assert!(grammar.rule("common.space").is_some());
+    assert_eq!(expr_text(&grammar.rule("common.word").unwrap().expr), "+LETTER ?common.space");
+
+    // Imported rules point at the import.
+    assert_eq!(grammar.rule("common.word").unwrap().name_span, Span::new(0, 36));
+
+    let program = Program::compile(&grammar).unwrap();
+    let input = |text: &str| input_symbols(&program, text).unwrap();
+    assert!(matches!(run(&program, &input("LETTER SPACE COMMA LETTER")), Outcome::Accept(_)));
+}
+
+#[test]
+fn test_import_range() {
+    // The range keeps the order of common.pglsf, although main.pglsf declares C before A.
+    let common = "symbols A, B, C;\ngrammar\n\tabc = A ... C;\n";
+    let main = "import \"common.pglsf\" as common;\nsymbols C, A;\ngrammar\n\troot = common.abc;\n";
+    let grammar = resolve_files(&[("main.pglsf", main), ("common.pglsf", common)]).unwrap();
+    assert_eq!(expr_text(&grammar.rule("common.abc").unwrap().expr), "A | B | C");
+
+    let program = Program::compile(&grammar).unwrap();
+    for name in ["A", "B", "C"] {
+        assert!(matches!(run(&program, &input_symbols(&program, name).unwrap()), Outcome::Accept(_)), "{}", name);
+    }
+}
+
+#[test]
+fn test_import_override() {
+    let main = "import \"common.pglsf\" as common;\nsymbols TAB;\ngrammar\n\troot = common.word;\n\tcommon.space = TAB;\n";
+    let grammar = resolve_files(&[("main.pglsf", main), ("common.pglsf", COMMON)]).unwrap();
+    assert_eq!(grammar.rules.len(), 3);
+    assert_eq!(expr_text(&grammar.rule("common.space").unwrap().expr), "TAB");
+
+    let program = Program::compile(&grammar).unwrap();
+    let input = |text: &str| input_symbols(&program, text).unwrap();
+    assert!(matches!(run(&program, &input("LETTER TAB")), Outcome::Accept(_)));
+    assert_eq!(run(&program, &input("LETTER SPACE")), Outcome::Reject(1));
+}
+
+#[test]
+fn test_import_errors() {
+    let error = |files: &[(&str, &str)]| resolve_files(files).unwrap_err().message;
+
+    let cycle = [
+        ("a.pglsf", "import \"b.pglsf\" as b;\nsymbols A;\ngrammar\n\troot = A;\n"),
+        ("b.pglsf", "import \"./a.pglsf\" as a;\nsymbols;\ngrammar\n\troot = a.root;\n")
+    ];
+    assert_eq!(error(&cycle), "in b.pglsf:1:1: import cycle: a.pglsf -> b.pglsf -> a.pglsf");
+
+    let twice = "import \"common.pglsf\" as c;\nimport \"common.pglsf\" as c;\nsymbols;\ngrammar\n\troot = c.word;\n";
+    assert_eq!(error(&[("main.pglsf", twice), ("common.pglsf", COMMON)]), "'c' is imported more than once");
+
+    let missing = "import \"missing.pglsf\" as m;\nsymbols;\ngrammar\n\troot = m.word;\n";
+    assert_eq!(error(&[("main.pglsf", missing)]), "cannot import \"missing.pglsf\": no such file");
+
+    let nothing = "import \"common.pglsf\" as c;\nsymbols;\ngrammar\n\troot = c.word;\n\tc.other = SPACE;\n";
+    assert_eq!(error(&[("main.pglsf", nothing), ("common.pglsf", COMMON)]), "rule 'c.other' overrides nothing, as no imported rule has that name");
+
+    let unknown = "import \"common.pglsf\" as c;\nsymbols;\ngrammar\n\troot = d.word;\n";
+    assert_eq!(error(&[("main.pglsf", unknown), ("common.pglsf", COMMON)]), "rule 'd.word' is not defined");
+
+    let broken = "symbols A;\ngrammar\n\troot = B;\n";
+    let main = "import \"broken.pglsf\" as c;\nsymbols;\ngrammar\n\troot = c.root;\n";
+    assert_eq!(error(&[("main.pglsf", main), ("broken.pglsf", broken)]), "in broken.pglsf:3:9: symbol 'B' is not declared");
+
+    let binary = "symbols binary;\ngrammar\n\troot = 0x41;\n";
+    let main = "import \"binary.pglsf\" as b;\nsymbols A;\ngrammar\n\troot = b.root;\n";
+    assert!(error(&[("main.pglsf", main), ("binary.pglsf", binary)]).starts_with("a binary grammar cannot"));
+
+    assert!(read("import \"common.pglsf\" as common;\nsymbols;\ngrammar\n\troot = common.word;\n").is_err());
+}
+
+#[test]
+fn test_templates() {
+    let source = "symbols A, B, COMMA;\ngrammar\n\troot = sep_list(item, COMMA);\n\titem = A | pair(B);\n\tsep_list(x, sep) = x *(sep x);\n\tpair(x) = x x;\n";
+    let grammar = read(source).unwrap();
+    let names: Vec<&str> = grammar.rules.iter().map(|rule| rule.name.as_str()).collect();
+    assert_eq!(names, vec!["root", "item", "sep_list<item,COMMA>", "pair<B>"]);
+    assert_eq!(expr_text(&grammar.rule("root").unwrap().expr), "sep_list<item,COMMA>");
+    assert_eq!(expr_text(&grammar.rule("sep_list<item,COMMA>").unwrap().expr), "item *(COMMA item)");
+
+    let program = Program::compile(&grammar).unwrap();
+    let input = |text: &str| input_symbols(&program, text).unwrap();
+    assert!(matches!(run(&program, &input("A COMMA B B COMMA A")), Outcome::Accept(_)));
+    assert_eq!(run(&program, &input("A COMMA B")), Outcome::Reject(3));
+
+    // Nested uses, and a template that uses itself with the same arguments.
+    let source = "symbols A, B;\ngrammar\n\troot = list(pair(A));\n\tlist(x) = x ?list(x);\n\tpair(x) = x B;\n";
+    let grammar = read(source).unwrap();
+    assert_eq!(expr_text(&grammar.rule("list<pair<A>>").unwrap().expr), "pair<A> ?list<pair<A>>");
+    assert_eq!(grammar.rules.len(), 3);
+}
+
+#[test]
+fn test_template_errors() {
+    let error = |source: &str| read(source).unwrap_err().message;
+    assert_eq!(error("symbols A;\ngrammar\n\troot = list(A, A);\n\tlist(x) = +x;\n"), "template 'list' needs 1 arguments, not 2");
+    assert_eq!(error("symbols A;\ngrammar\n\troot = list;\n\tlist(x) = +x;\n"), "template 'list' needs 1 arguments");
+    assert_eq!(error("symbols A;\ngrammar\n\troot = item(A);\n\titem = A;\n"), "rule 'item' is not a template");
+    assert_eq!(error("symbols A;\ngrammar\n\troot = list(A);\n"), "template 'list' is not defined");
+    assert!(error("symbols A;\ngrammar\n\troot = A;\n\tlist(x, x) = x;\n").contains("'x'"));
+    assert_eq!(
+        error("symbols A;\ngrammar\n\troot = grow(A);\n\tgrow(x) = x ?grow(pair(x));\n\tpair(x) = x x;\n"),
+        "template 'grow' uses itself with arguments that keep growing"
+    );
+}
+
+#[test]
+fn test_import_template() {
+    let common = "symbols;\ngrammar\n\tsep_list(item, sep) = item *(sep item);\n";
+    let main = "import \"common.pglsf\" as common;\nsymbols A, COMMA;\ngrammar\n\troot = common.sep_list(A, COMMA);\n";
+    let grammar = resolve_files(&[("main.pglsf", main), ("common.pglsf", common)]).unwrap();
+    assert_eq!(expr_text(&grammar.rule("common.sep_list<A,COMMA>").unwrap().expr), "A *(COMMA A)");
+}
+
+#[test]
+fn test_read_actions() {
+    let source = "symbols A, B;\ngrammar\n\troot = +A => { format!(\"{}}\", '}') };\n\tother = B;\n";
+    let grammar = read(source).unwrap();
+    let action = grammar.rule("root").unwrap().action.as_ref().unwrap();
+    assert_eq!(action.code, "{ format!(\"{}}\", '}') }");
+    assert_eq!(expr_text(&grammar.rule("root").unwrap().expr), "+A");
+    assert!(grammar.rule("other").unwrap().action.is_none());
+
+    assert_eq!(read("symbols A;\ngrammar\n\troot = A => { {} ;\n").unwrap_err().message, "unterminated action");
+    assert_eq!(read("symbols A;\ngrammar\n\troot = A => A;\n").unwrap_err().message, "expected an action, found 'A'");
+    assert_eq!(read("symbols A;\ngrammar\n\troot = list(A);\n\tlist(x) = +x => { 0 };\n").unwrap_err().message, "templates cannot have actions");
+}
+
+#[test]
+fn test_labels() {
+    let source = "symbols A, B, EQUALS, SPACE;\ngrammar\n\tassign = name:A ?SPACE EQUALS ?SPACE *value:item;\n\titem = B;\n";
+    let tree = match outcome(source, "A SPACE EQUALS B B") {
+        Outcome::Accept(tree) => tree,
+        other => panic!("{:?}", other)
+    };
+    assert_eq!(tree, Tree::parse("(assign name:A SPACE EQUALS value:(item B) value:(item B))").unwrap());
+    assert_eq!(tree.to_string(), "(assign name:A SPACE EQUALS value:(item B) value:(item B))");
+    assert_eq!(tree.field("name"), Some(&Tree::Leaf("A".to_string())));
+    assert_eq!(tree.fields("value").count(), 2);
+    assert_eq!(tree.field("other"), None);
+
+    // The same label may be used in different alternatives.
+    let either = "symbols A, B;\ngrammar\n\troot = x:A | (B x:first);\n\tfirst = A ... B;\n";
+    assert_eq!(outcome(either, "B A"), Outcome::Accept(Tree::parse("(root B x:(first A))").unwrap()));
+}
+
+#[test]
+fn test_label_errors() {
+    let error = |source: &str| read(source).unwrap_err().message;
+    assert_eq!(error("symbols A;\ngrammar\n\troot = x:A x:A;\n"), "label 'x' is used more than once");
+    assert_eq!(error("symbols A, B;\ngrammar\n\troot = x:A (B | x:B);\n"), "label 'x' is used more than once");
+    assert_eq!(error("symbols A;\ngrammar\n\troot = x:(A A);\n"), "only symbols, ranges and rules can be labelled");
+    assert_eq!(error("symbols A;\ngrammar\n\troot = x:?A;\n"), "a label goes after '?', '*' or '+', e.g. ?name:rule");
+    assert!(read("symbols A, B;\ngrammar\n\troot = x:A ... B ?(y:A ... B);\n").is_ok());
+}
+
+#[test]
+fn test_hidden_and_inline() {
+    let source = "symbols A, COMMA, SPACE;\ngrammar\n\troot = item *(sep item);\n\t@inline sep = ?space COMMA ?space;\n\t@hidden space = +SPACE;\n\titem = A;\n";
+    let expected = Tree::parse("(root (item A) COMMA (item A))").unwrap();
+    assert_eq!(outcome(source, "A SPACE COMMA SPACE SPACE A"), Outcome::Accept(expected));
+
+    // The machine returns no calls to sep.
+    let program = Program::compile(&read(source).unwrap()).unwrap();
+    let sep = &program.rules()[1];
+    let parsed = crate::tester::parse(&program, &input_symbols(&program, "A COMMA A").unwrap()).unwrap();
+    assert!(parsed.iter().all(|item| !matches!(item, crate::parse_machine::SymbolOrRule::Rule(rule) if rule.node() == sep.call || rule.node() == sep.exit)));
+
+    let hidden_root = read("symbols A;\ngrammar\n\t@hidden root = A;\n").unwrap();
+    assert_eq!(Program::compile(&hidden_root).err().unwrap().message, "the root rule cannot be @hidden or @inline");
+}
+
+#[test]
+fn test_skip() {
+    let source = "symbols A, B, SPACE;\ngrammar\n\t@skip space;\n\troot = word +B;\n\t@token word = +letter;\n\tletter = A;\n\t@hidden space = +SPACE;\n";
+    let grammar = read(source).unwrap();
+    assert_eq!(expr_text(&grammar.rule("root").unwrap().expr), "?space word ?space +(B ?space)");
+    assert_eq!(expr_text(&grammar.rule("word").unwrap().expr), "+letter");
+
+    let expected = Tree::parse("(root (word (letter A) (letter A)) B B)").unwrap();
+    assert_eq!(outcome(source, "SPACE A A SPACE SPACE B B SPACE"), Outcome::Accept(expected));
+    assert_eq!(outcome(source, "A SPACE A B"), Outcome::Reject(2));
+}
+
+#[test]
+fn test_annotation_errors() {
+    let error = |source: &str| read(source).unwrap_err().message;
+    assert_eq!(error("symbols A;\ngrammar\n\t@skip space;\n\troot = A;\n"), "rule 'space' is not defined");
+    assert_eq!(error("symbols A;\ngrammar\n\t@skip space;\n\troot = A;\n\tspace = ?A;\n"), "rule 'space' can match nothing, so it cannot be skipped");
+    assert_eq!(error("symbols A;\ngrammar\n\troot = A;\n\t@skip root;\n"), "@skip goes before the first rule");
+    assert_eq!(error("symbols A;\ngrammar\n\t@hidden @inline root = A;\n"), "a rule cannot be both @hidden and @inline");
+    assert_eq!(error("symbols A;\ngrammar\n\t@token @token root = A;\n"), "@token is given more than once");
+    assert_eq!(error("symbols A;\ngrammar\n\t@visible root = A;\n"), "unknown annotation '@visible'");
+    assert_eq!(error("symbols A;\ngrammar\n\troot = x:item;\n\t@inline item = A;\n"), "rule 'item' is @inline, so it has no node to label");
+    assert_eq!(error("symbols A;\ngrammar\n\troot = x:item;\n\t@hidden item = A;\n"), "rule 'item' is @hidden, so it has no node to label");
+}
+
+// Whether every form of the grammar accepts the same inputs of up to four symbols.
+fn same_language(source: &str, symbols: &[&str]) {
+    let accepts = |program: &Program, input: &[&str]| {
+        !matches!(run(program, &input_symbols(program, &input.join(" ")).unwrap()), Outcome::Reject(_))
+    };
+    let compile = |text: String| Program::compile(&read(&text).unwrap()).unwrap();
+
+    let grammar = read(source).unwrap();
+    let original = Program::compile(&grammar).unwrap();
+    let forms = [
+        compile(formatter::grammar_text(&transform::to_bnf(&grammar).to_grammar())),
+        compile(formatter::grammar_text(&transform::remove_units(transform::remove_epsilon(transform::to_bnf(&grammar))).to_grammar())),
+        compile(formatter::grammar_text(&transform::to_cnf(&grammar).to_grammar())),
+        compile(formatter::grammar_text(&transform::to_gnf(&grammar).to_grammar()))
+    ];
+
+    let mut inputs: Vec<Vec<&str>> = vec![Vec::new()];
+    let mut shorter = 0;
+    for _ in 0..4 {
+        let longer: Vec<Vec<&str>> = inputs[shorter..].iter()
+            .flat_map(|input| symbols.iter().map(move |symbol| input.iter().copied().chain([*symbol]).collect()))
+            .collect();
+        shorter = inputs.len();
+        inputs.extend(longer);
+    }
+    for input in &inputs {
+        for form in &forms {
+            assert_eq!(accepts(form, input), accepts(&original, input), "{:?}", input);
+        }
+    }
+}
+
+#[test]
+fn test_transform_bnf() {
+    let grammar = read("symbols A, B, C;\ngrammar\n\troot = +item ?C;\n\titem = A | B *(A B);\n").unwrap();
+    let text = formatter::grammar_text(&transform::to_bnf(&grammar).to_grammar());
+    assert_eq!(text, "symbols\n\tA, B, C;\n\ngrammar\n\troot = root_plus1 root_opt1;\n\troot_plus1 = item | item root_plus1;\n\troot_opt1 = ?C;\n\titem = A | B item_star1;\n\titem_star1 = ?(A B item_star1);\n");
+    assert_eq!(formatter::format(&text).unwrap(), text);
+
+    let no_epsilon = transform::remove_epsilon(transform::to_bnf(&grammar));
+    assert_eq!(no_epsilon.rule("root").unwrap().alternatives.len(), 2);
+    assert!(no_epsilon.rules.iter().all(|rule| rule.alternatives.iter().all(|alternative| !alternative.is_empty())));
+
+    // Template instances get names pglsf can write, and a root that can match nothing keeps an
+    // empty alternative that no rule uses.
+    let grammar = read("symbols A, COMMA;\ngrammar\n\troot = ?pair(A, COMMA) *root;\n\tpair(x, sep) = x sep x;\n").unwrap();
+    let bnf = transform::remove_epsilon(transform::to_bnf(&grammar));
+    assert!(bnf.rule("pair_a_comma").is_some());
+    assert_eq!(bnf.rules[0].name, "root");
+    assert!(bnf.rules[0].alternatives.contains(&Vec::new()));
+    assert!(bnf.rules[1..].iter().flat_map(|rule| rule.alternatives.iter().flatten()).all(|term| *term != transform::Term::Rule("root".to_string())));
+}
+
+#[test]
+fn test_transform_normal_forms() {
+    use transform::Term;
+
+    let source = "symbols A, B, C;\ngrammar\n\troot = +item ?C;\n\titem = A | B *(A B) | (C ... A) B;\n";
+    let grammar = read(source).unwrap();
+    let cnf = transform::to_cnf(&grammar);
+    for rule in &cnf.rules {
+        for alternative in &rule.alternatives {
+            assert!(matches!(alternative.as_slice(), [Term::Rule(_), Term::Rule(_)] | [Term::Symbol(_)]), "{}: {:?}", rule.name, alternative);
+        }
+    }
+    let gnf = transform::to_gnf(&grammar);
+    for rule in &gnf.rules {
+        for alternative in &rule.alternatives {
+            assert!(matches!(alternative[0], Term::Symbol(_)) && alternative[1..].iter().all(|term| matches!(term, Term::Rule(_))), "{}: {:?}", rule.name, alternative);
+        }
+    }
+    same_language(source, &["A", "B", "C"]);
+    same_language("symbols A, B;\ngrammar\n\troot = ?(A root B) *pair;\n\tpair = ?A B;\n", &["A", "B"]);
+
+    // Left recursion is gone in Greibach normal form, so the grammar compiles.
+    let left_recursive = read("symbols NUM, PLUS;\ngrammar\n\texpr = expr PLUS term | term;\n\tterm = NUM;\n").unwrap();
+    assert!(Program::compile(&left_recursive).is_err());
+    let gnf = read(&formatter::grammar_text(&transform::to_gnf(&left_recursive).to_grammar())).unwrap();
+    let program = Program::compile(&gnf).unwrap();
+    assert!(matches!(run(&program, &input_symbols(&program, "NUM PLUS NUM PLUS NUM").unwrap()), Outcome::Accept(_)));
+    assert_eq!(run(&program, &input_symbols(&program, "NUM PLUS").unwrap()), Outcome::Reject(2));
+}
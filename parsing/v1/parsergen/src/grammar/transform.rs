@@ -0,0 +1,575 @@
+// transform.rs
+//
+// parsergen transform <grammar.pglsf> bnf|no-epsilon|no-units|cnf|gnf
+//
+// Rewrites a grammar into the forms textbooks use, keeping the language it matches: BNF, where
+// every rule is a list of alternatives and every alternative a sequence of symbols and rules; BNF
+// without empty alternatives; BNF without alternatives that are just another rule; and the Chomsky
+// and Greibach normal forms. The results can be turned back into a Grammar and written out as
+// .pglsf.
+//
+// '?', '*', '+', ranges and nested unions are moved into helper rules named after the rule they
+// are in, which is what the ParseMachine does with them in effect:
+//
+//     comment_text = first *(whitespace | letter) rest;
+//
+// becomes
+//
+//     comment_text = first comment_text_star1 rest;
+//     comment_text_star1 = ?(whitespace comment_text_star1 | letter comment_text_star1);
+//
+// pglsf has no empty sequence, so a rule with an empty alternative is written with '?' around the
+// others, as above. Only the language is kept: labels, actions and annotations are dropped, and the
+// trees of a transformed grammar are those of its new rules.
+//
+
+use std::collections::HashSet;
+use std::process::ExitCode;
+
+use super::*;
+use crate::formatter;
+
+// A symbol or rule in an alternative.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Term {
+    Symbol(String),
+    Byte(u8),
+    Rule(String)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BnfRule {
+    pub name: String,
+
+    // An empty alternative matches nothing.
+    pub alternatives: Vec<Vec<Term>>
+}
+
+#[derive(Clone, Debug)]
+pub struct Bnf {
+    pub symbols: Symbols,
+
+    // The root rule comes first.
+    pub rules: Vec<BnfRule>
+}
+
+impl Bnf {
+    pub fn rule(&self, name: &str) -> Option<&BnfRule> {
+        self.rules.iter().find(|rule| rule.name == name)
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.name == name)
+    }
+
+    fn is_taken(&self, name: &str) -> bool {
+        self.index(name).is_some()
+    }
+
+    // For every rule, whether it can match nothing.
+    pub fn nullable(&self) -> Vec<bool> {
+        let mut nullable = vec![false; self.rules.len()];
+        loop {
+            let mut changed = false;
+            for (i, rule) in self.rules.iter().enumerate() {
+                if !nullable[i] && rule.alternatives.iter().any(|alternative| alternative.iter().all(|term| match term {
+                    Term::Rule(name) => self.index(name).is_some_and(|j| nullable[j]),
+                    Term::Symbol(_) | Term::Byte(_) => false
+                })) {
+                    nullable[i] = true;
+                    changed = true;
+                }
+            }
+            if !changed { return nullable }
+        }
+    }
+
+    // Gives the root rule a new body when other rules use it, so that it appears on no right hand
+    // side: root = A ?root; becomes root = root_body; root_body = A ?root_body;. The root keeps its
+    // name, which is what makes it the root.
+    fn separate_root(mut self) -> Bnf {
+        let root = self.rules[0].name.clone();
+        let uses_root = |alternative: &Vec<Term>| alternative.iter().any(|term| *term == Term::Rule(root.clone()));
+        if !self.rules.iter().any(|rule| rule.alternatives.iter().any(uses_root)) {
+            return self
+        }
+
+        let body = fresh_name(&format!("{}_body", root), |name| self.is_taken(name));
+        for rule in &mut self.rules {
+            for term in rule.alternatives.iter_mut().flatten() {
+                if *term == Term::Rule(root.clone()) {
+                    *term = Term::Rule(body.clone());
+                }
+            }
+        }
+        self.rules[0].name = body.clone();
+        self.rules.insert(0, BnfRule { name: root, alternatives: vec![vec![Term::Rule(body)]] });
+        self
+    }
+
+    // Leaves out the alternatives that use rules which match no input at all, then those rules and
+    // the rules the root no longer reaches. The root is always kept.
+    fn clean(mut self) -> Bnf {
+        let mut generating = vec![false; self.rules.len()];
+        loop {
+            let mut changed = false;
+            for (i, rule) in self.rules.iter().enumerate() {
+                if !generating[i] && rule.alternatives.iter().any(|alternative| alternative.iter().all(|term| match term {
+                    Term::Rule(name) => self.index(name).is_some_and(|j| generating[j]),
+                    Term::Symbol(_) | Term::Byte(_) => true
+                })) {
+                    generating[i] = true;
+                    changed = true;
+                }
+            }
+            if !changed { break }
+        }
+
+        let names: Vec<String> = self.rules.iter().map(|rule| rule.name.clone()).collect();
+        let usable = |term: &Term| match term {
+            Term::Rule(name) => names.iter().position(|other| other == name).is_some_and(|j| generating[j]),
+            Term::Symbol(_) | Term::Byte(_) => true
+        };
+        for rule in &mut self.rules {
+            rule.alternatives.retain(|alternative| alternative.iter().all(usable));
+        }
+
+        let mut reached = vec![false; self.rules.len()];
+        let mut pending = vec![0];
+        while let Some(i) = pending.pop() {
+            if !reached[i] {
+                reached[i] = true;
+                for term in self.rules[i].alternatives.iter().flatten() {
+                    if let Term::Rule(name) = term {
+                        pending.extend(self.index(name));
+                    }
+                }
+            }
+        }
+
+        let mut i = 0;
+        self.rules.retain(|_| {
+            i += 1;
+            reached[i - 1]
+        });
+        self
+    }
+
+    // The grammar with these rules, which formatter::grammar_text writes out as .pglsf.
+    pub fn to_grammar(&self) -> Grammar {
+        let expr = |kind: ExprKind| Expr::new(kind, Span::default());
+        let term = |term: &Term| match term {
+            Term::Symbol(name) => expr(ExprKind::Symbol(name.clone())),
+            Term::Byte(byte) => expr(ExprKind::Byte(*byte)),
+            Term::Rule(name) => expr(ExprKind::Rule(name.clone()))
+        };
+
+        let rules = self.rules.iter().map(|rule| {
+            let mut alternatives: Vec<Expr> = rule.alternatives.iter()
+                .filter(|alternative| !alternative.is_empty())
+                .map(|alternative| match alternative.as_slice() {
+                    [single] => term(single),
+                    items => expr(ExprKind::Seq(items.iter().map(term).collect()))
+                })
+                .collect();
+            let body = match alternatives.len() {
+                1 => alternatives.pop().unwrap(),
+                _ => expr(ExprKind::Union(alternatives))
+            };
+            let body = match rule.alternatives.iter().any(Vec::is_empty) {
+                true => expr(ExprKind::Opt(Box::new(body))),
+                false => body
+            };
+            RuleDef {
+                name: rule.name.clone(),
+                name_span: Span::default(),
+                params: Vec::new(),
+                expr: body,
+                action: None,
+                visibility: Visibility::Visible,
+                token: false
+            }
+        }).collect();
+
+        Grammar { imports: Vec::new(), symbols: self.symbols.clone(), skip: None, rules }
+    }
+}
+
+// The name, or if it is taken the first of name2, name3, ... that is not.
+fn fresh_name(name: &str, is_taken: impl Fn(&str) -> bool) -> String {
+    let mut candidate = name.to_string();
+    let mut n = 2;
+    while is_taken(&candidate) {
+        candidate = format!("{}{}", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+// The first of prefix1, prefix2, ... that is not taken, for rules there may be several of.
+fn numbered_name(prefix: &str, is_taken: impl Fn(&str) -> bool) -> String {
+    (1..).map(|n| format!("{}{}", prefix, n)).find(|name| !is_taken(name)).unwrap()
+}
+
+// The name of a rule as pglsf can write it. Template instances such as list<item,COMMA> become
+// list_item_comma.
+fn rule_name(name: &str) -> String {
+    let mut result = String::new();
+    for c in name.chars() {
+        match c {
+            'a'..='z' | '0'..='9' | '_' | '.' => result.push(c),
+            'A'..='Z' => result.push(c.to_ascii_lowercase()),
+            _ => if !result.ends_with('_') {
+                result.push('_')
+            }
+        }
+    }
+    result.trim_end_matches('_').to_string()
+}
+
+// Alternatives without the repeats, in the order they first appear.
+fn dedup(alternatives: Vec<Vec<Term>>) -> Vec<Vec<Term>> {
+    let mut seen = HashSet::new();
+    alternatives.into_iter().filter(|alternative| seen.insert(alternative.clone())).collect()
+}
+
+// Moves the operators of one rule at a time into helper rules.
+struct Lowering<'a> {
+    grammar: &'a Grammar,
+
+    // The names of the rules and of the helpers made so far.
+    names: Vec<String>,
+
+    // The rule being lowered, and the helpers made for it.
+    rule: String,
+    helpers: Vec<BnfRule>
+}
+
+impl Lowering<'_> {
+    fn helper_name(&mut self, kind: &str) -> String {
+        let name = numbered_name(&format!("{}_{}", self.rule, kind), |name| self.names.iter().any(|other| other == name));
+        self.names.push(name.clone());
+        name
+    }
+
+    // The alternatives an expression matches, where it is a whole rule.
+    fn alternatives(&mut self, expr: &Expr) -> Vec<Vec<Term>> {
+        match &expr.kind {
+            ExprKind::Union(items) => items.iter().flat_map(|item| self.alternatives(item)).collect(),
+            ExprKind::Opt(inner) => {
+                let mut alternatives = vec![Vec::new()];
+                alternatives.extend(self.alternatives(inner));
+                alternatives
+            },
+            _ => vec![self.terms(expr)]
+        }
+    }
+
+    // The terms an expression matches, where it is part of a sequence.
+    fn terms(&mut self, expr: &Expr) -> Vec<Term> {
+        let kind = match expr.expanded() {
+            Expanded::Symbol(name) => return vec![Term::Symbol(name.clone())],
+            Expanded::Byte(byte) => return vec![Term::Byte(byte)],
+            Expanded::Rule(name) => return vec![Term::Rule(rule_name(name))],
+            Expanded::Seq(items) => return items.iter().flat_map(|item| self.terms(item)).collect(),
+            Expanded::Range(_, _) => "range",
+            Expanded::Union(_) => "group",
+            Expanded::Opt(_) => "opt",
+            Expanded::Star(_) => "star",
+            Expanded::Plus(_) => "plus"
+        };
+
+        // The helper is named before the ones inside it.
+        let name = self.helper_name(kind);
+        let alternatives = match &expr.kind {
+            ExprKind::Range(first, last) => self.range(first, last),
+            // *x is ?(x *x), and +x is x ?(+x).
+            ExprKind::Star(inner) | ExprKind::Plus(inner) => {
+                let once = self.alternatives(inner);
+                let again = once.iter().map(|alternative| {
+                    let mut alternative = alternative.clone();
+                    alternative.push(Term::Rule(name.clone()));
+                    alternative
+                });
+                match expr.kind {
+                    ExprKind::Star(_) => std::iter::once(Vec::new()).chain(again).collect(),
+                    _ => once.iter().cloned().chain(again).collect()
+                }
+            },
+            _ => self.alternatives(expr)
+        };
+        self.helpers.push(BnfRule { name: name.clone(), alternatives: dedup(alternatives) });
+        vec![Term::Rule(name)]
+    }
+
+    // One alternative for every symbol of the range.
+    fn range(&self, first: &Expr, last: &Expr) -> Vec<Vec<Term>> {
+        match (&first.kind, &last.kind, &self.grammar.symbols) {
+            (ExprKind::Byte(first), ExprKind::Byte(last), _) => (*first.min(last)..=*first.max(last))
+                .map(|byte| vec![Term::Byte(byte)])
+                .collect(),
+            (ExprKind::Symbol(first), ExprKind::Symbol(last), Symbols::Named(decls)) => {
+                let (first, last) = (self.grammar.symbol_index(first).unwrap(), self.grammar.symbol_index(last).unwrap());
+                decls[first.min(last)..=first.max(last)].iter()
+                    .map(|decl| vec![Term::Symbol(decl.name.clone())])
+                    .collect()
+            },
+            _ => unreachable!("the ends of a range are both symbols or both bytes")
+        }
+    }
+}
+
+// A checked grammar with its templates expanded, in BNF. Groups and operators become helper rules
+// that follow the rule they came from.
+pub fn to_bnf(grammar: &Grammar) -> Bnf {
+    let root = grammar.root().map(|root| root.name.clone());
+    let ordered = grammar.rules.iter()
+        .filter(|rule| Some(&rule.name) == root.as_ref())
+        .chain(grammar.rules.iter().filter(|rule| Some(&rule.name) != root.as_ref()));
+
+    let mut lowering = Lowering {
+        grammar,
+        names: grammar.rules.iter().map(|rule| rule_name(&rule.name)).collect(),
+        rule: String::new(),
+        helpers: Vec::new()
+    };
+    let mut rules = Vec::new();
+    for rule in ordered {
+        lowering.rule = rule_name(&rule.name);
+        let alternatives = lowering.alternatives(&rule.expr);
+        rules.push(BnfRule { name: lowering.rule.clone(), alternatives: dedup(alternatives) });
+        rules.append(&mut lowering.helpers);
+    }
+    Bnf { symbols: grammar.symbols.clone(), rules }
+}
+
+// The grammar without empty alternatives. Every alternative that uses rules which can match
+// nothing is kept once with and once without each of them. If the root could match nothing, it
+// keeps an empty alternative, and then no other rule uses it.
+pub fn remove_epsilon(bnf: Bnf) -> Bnf {
+    let root_nullable = bnf.nullable()[0];
+    let mut bnf = match root_nullable {
+        true => bnf.separate_root(),
+        false => bnf
+    };
+
+    let nullable = bnf.nullable();
+    let is_nullable = |term: &Term| match term {
+        Term::Rule(name) => bnf.index(name).is_some_and(|i| nullable[i]),
+        Term::Symbol(_) | Term::Byte(_) => false
+    };
+    let mut rules = Vec::new();
+    for rule in &bnf.rules {
+        let mut alternatives = Vec::new();
+        for alternative in &rule.alternatives {
+            let mut variants: Vec<Vec<Term>> = vec![Vec::new()];
+            for term in alternative {
+                let without = match is_nullable(term) {
+                    true => variants.clone(),
+                    false => Vec::new()
+                };
+                for variant in &mut variants {
+                    variant.push(term.clone());
+                }
+                variants.extend(without);
+            }
+            alternatives.extend(variants.into_iter().filter(|variant| !variant.is_empty()));
+        }
+        rules.push(BnfRule { name: rule.name.clone(), alternatives: dedup(alternatives) });
+    }
+    bnf.rules = rules;
+
+    let mut bnf = bnf.clean();
+    if root_nullable {
+        bnf.rules[0].alternatives.push(Vec::new());
+    }
+    bnf
+}
+
+// The grammar without alternatives that are just another rule: those are replaced by the other
+// alternatives of the rules they lead to, however many steps away.
+pub fn remove_units(mut bnf: Bnf) -> Bnf {
+    let unit = |alternative: &Vec<Term>| match alternative.as_slice() {
+        [Term::Rule(name)] => Some(name.clone()),
+        _ => None
+    };
+
+    let mut rules = Vec::new();
+    for rule in &bnf.rules {
+        let mut reached = vec![rule.name.clone()];
+        let mut alternatives = Vec::new();
+        let mut i = 0;
+        while i < reached.len() {
+            let Some(other) = bnf.rule(&reached[i]) else { break };
+            for alternative in &other.alternatives {
+                match unit(alternative) {
+                    Some(name) => if !reached.contains(&name) {
+                        reached.push(name)
+                    },
+                    None => alternatives.push(alternative.clone())
+                }
+            }
+            i += 1;
+        }
+        rules.push(BnfRule { name: rule.name.clone(), alternatives: dedup(alternatives) });
+    }
+    bnf.rules = rules;
+    bnf.clean()
+}
+
+// The grammar in Chomsky normal form: every alternative is two rules or one symbol. Only the root
+// may have an empty alternative, and no rule uses the root.
+pub fn to_cnf(grammar: &Grammar) -> Bnf {
+    let mut bnf = remove_units(remove_epsilon(to_bnf(grammar).separate_root()));
+
+    // The symbols in longer alternatives get rules of their own, e.g. letter_a_symbol = LETTER_A;.
+    let mut symbol_rules: Vec<BnfRule> = Vec::new();
+    for i in 0..bnf.rules.len() {
+        let mut alternatives = std::mem::take(&mut bnf.rules[i].alternatives);
+        for alternative in alternatives.iter_mut().filter(|alternative| alternative.len() > 1) {
+            for term in alternative.iter_mut() {
+                if let Term::Rule(_) = term {
+                    continue
+                }
+                let existing = symbol_rules.iter().find(|rule| rule.alternatives[0][0] == *term);
+                let name = match existing {
+                    Some(rule) => rule.name.clone(),
+                    None => {
+                        let base = match term {
+                            Term::Symbol(name) => format!("{}_symbol", name.to_ascii_lowercase()),
+                            Term::Byte(byte) => format!("byte_{:02x}", byte),
+                            Term::Rule(_) => unreachable!()
+                        };
+                        let name = fresh_name(&base, |name| bnf.is_taken(name) || symbol_rules.iter().any(|rule| rule.name == name));
+                        symbol_rules.push(BnfRule { name: name.clone(), alternatives: vec![vec![term.clone()]] });
+                        name
+                    }
+                };
+                *term = Term::Rule(name);
+            }
+        }
+        bnf.rules[i].alternatives = alternatives;
+    }
+    bnf.rules.extend(symbol_rules);
+
+    // Longer alternatives are split in two from the left: A = B C D; becomes A = B a_rest1;
+    // a_rest1 = C D;. Rests that are the same share a rule.
+    let first_rest = bnf.rules.len();
+    let mut i = 0;
+    while i < bnf.rules.len() {
+        for j in 0..bnf.rules[i].alternatives.len() {
+            if bnf.rules[i].alternatives[j].len() > 2 {
+                let rest = bnf.rules[i].alternatives[j].split_off(1);
+                let name = match bnf.rules[first_rest..].iter().find(|rule| rule.alternatives[0] == rest) {
+                    Some(rule) => rule.name.clone(),
+                    None => {
+                        let name = numbered_name(&format!("{}_rest", bnf.rules[i].name), |name| bnf.is_taken(name));
+                        bnf.rules.push(BnfRule { name: name.clone(), alternatives: vec![rest] });
+                        name
+                    }
+                };
+                bnf.rules[i].alternatives[j].push(Term::Rule(name));
+            }
+        }
+        i += 1;
+    }
+    bnf
+}
+
+// Replaces the alternatives that start with the rule by the rule's alternatives, each followed by
+// the rest.
+fn substitute_first(alternatives: Vec<Vec<Term>>, rule: &BnfRule) -> Vec<Vec<Term>> {
+    let mut result = Vec::new();
+    for alternative in alternatives {
+        match alternative.first() {
+            Some(Term::Rule(name)) if *name == rule.name => for start in &rule.alternatives {
+                result.push(start.iter().chain(&alternative[1..]).cloned().collect());
+            },
+            _ => result.push(alternative)
+        }
+    }
+    dedup(result)
+}
+
+// The grammar in Greibach normal form: every alternative is a symbol followed by rules. Only the
+// root may have an empty alternative. Left recursion is removed on the way, so the result can be
+// compiled into a Program even when the grammar could not be.
+pub fn to_gnf(grammar: &Grammar) -> Bnf {
+    let mut bnf = to_cnf(grammar);
+    let count = bnf.rules.len();
+
+    // Substitute each rule into the later ones where they start with it, so that every alternative
+    // starts with a symbol or a later rule, and remove the left recursion that is left. A = A x | y;
+    // becomes A = y | y a_tail; a_tail = x | x a_tail;.
+    let mut tails: Vec<BnfRule> = Vec::new();
+    for i in 0..count {
+        for j in 0..i {
+            let alternatives = std::mem::take(&mut bnf.rules[i].alternatives);
+            bnf.rules[i].alternatives = substitute_first(alternatives, &bnf.rules[j]);
+        }
+
+        let name = bnf.rules[i].name.clone();
+        let (recursive, others): (Vec<Vec<Term>>, Vec<Vec<Term>>) = std::mem::take(&mut bnf.rules[i].alternatives)
+            .into_iter()
+            .partition(|alternative| alternative.first() == Some(&Term::Rule(name.clone())));
+        if recursive.is_empty() {
+            bnf.rules[i].alternatives = others;
+            continue
+        }
+
+        let tail = fresh_name(&format!("{}_tail", name), |name| bnf.is_taken(name) || tails.iter().any(|rule| rule.name == name));
+        let with_tail = |alternatives: &[Vec<Term>]| alternatives.iter()
+            .map(|alternative| alternative.iter().cloned().chain([Term::Rule(tail.clone())]).collect::<Vec<Term>>())
+            .collect::<Vec<Vec<Term>>>();
+        let rest: Vec<Vec<Term>> = recursive.into_iter().map(|alternative| alternative[1..].to_vec()).collect();
+        bnf.rules[i].alternatives = others.iter().cloned().chain(with_tail(&others)).collect();
+        tails.push(BnfRule { name: tail.clone(), alternatives: rest.iter().cloned().chain(with_tail(&rest)).collect() });
+    }
+    bnf.rules.extend(tails);
+
+    // Now substitute backwards, from the last rule, until every alternative starts with a symbol.
+    // The tails come last, and start with the rules before them.
+    for i in (0..count).rev().chain(count..bnf.rules.len()) {
+        while let Some(j) = bnf.rules[i].alternatives.iter().find_map(|alternative| match alternative.first() {
+            Some(Term::Rule(name)) => bnf.index(name),
+            _ => None
+        }) {
+            let rule = bnf.rules[j].clone();
+            let alternatives = std::mem::take(&mut bnf.rules[i].alternatives);
+            bnf.rules[i].alternatives = substitute_first(alternatives, &rule);
+        }
+    }
+    bnf.clean()
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let (grammar_path, form) = match args {
+        [grammar_path, form] => (grammar_path.as_str(), form.as_str()),
+        _ => {
+            eprintln!("usage: parsergen transform <grammar.pglsf> bnf|no-epsilon|no-units|cnf|gnf");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let grammar = match load(grammar_path) {
+        Ok((grammar, _)) => grammar,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let bnf = match form {
+        "bnf" => to_bnf(&grammar),
+        "no-epsilon" => remove_epsilon(to_bnf(&grammar)),
+        "no-units" => remove_units(remove_epsilon(to_bnf(&grammar))),
+        "cnf" => to_cnf(&grammar),
+        "gnf" => to_gnf(&grammar),
+        _ => {
+            eprintln!("unknown form '{}': expected bnf, no-epsilon, no-units, cnf or gnf", form);
+            return ExitCode::FAILURE
+        }
+    };
+    print!("{}", formatter::grammar_text(&bnf.to_grammar()));
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,626 @@
+// importer.rs
+//
+// parsergen import --from ebnf|abnf|peg <grammar> [--symbols] [<out.pglsf>]
+//
+// Translates grammars written in other notations into .pglsf: W3C EBNF, as in the XML
+// specification; ABNF, from RFC 5234 with the %s and %i strings of RFC 7405; and PEG as pest writes
+// it. Each notation is read into rules of Items, which work on characters, and the characters are
+// then turned into symbols. By default the grammar is binary, and a character is the bytes of its
+// UTF-8 encoding. With --symbols, every character the grammar mentions is declared as a symbol
+// instead, e.g. LETTER_A, in the order of their code points, so that ranges keep working.
+//
+// What pglsf cannot express is reported with where it is in the source, and nothing is written
+// then: ABNF's prose values, PEG's predicates and stack, the exceptions of EBNF, and ranges of
+// characters that do not fit the alphabet. A PEG choice is ordered, but becomes a union, which
+// tries every alternative; a grammar that relied on the order becomes ambiguous where it did.
+//
+
+pub mod ebnf;
+pub mod abnf;
+pub mod peg;
+
+use std::fs;
+use std::process::ExitCode;
+
+use crate::formatter;
+use crate::grammar::{self, Expr, ExprKind, Grammar, GrammarError, Label, RuleDef, Skip, Span, SymbolDecl, Symbols, Visibility};
+
+// An expression of an imported grammar, on characters rather than symbols.
+#[derive(Clone, Debug)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub span: Span
+}
+
+impl Item {
+    pub fn new(kind: ItemKind, span: Span) -> Self {
+        Item { kind, span }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ItemKind {
+    // The characters of the string, one after the other. Letters match either case when the flag
+    // is set.
+    Text(String, bool),
+
+    // One character from the ranges, or when the flag is set, one from none of them.
+    Class(Vec<(char, char)>, bool),
+
+    // Any one character.
+    Any,
+
+    Rule(String),
+    Seq(Vec<Item>),
+    Union(Vec<Item>),
+    Opt(Box<Item>),
+    Star(Box<Item>),
+    Plus(Box<Item>),
+
+    // From min to max times the item, or at least min times without a max.
+    Repeat(Box<Item>, usize, Option<usize>),
+
+    // An item captured as a field of the tree, e.g. #name = ident in PEG.
+    Label(String, Box<Item>)
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedRule {
+    // As it is written in the source; pglsf_name gives the name it gets.
+    pub name: String,
+    pub name_span: Span,
+    pub item: Item,
+    pub visibility: Visibility,
+    pub token: bool
+}
+
+// A grammar read from another notation. The first rule is the root.
+#[derive(Clone, Debug, Default)]
+pub struct Imported {
+    pub rules: Vec<ImportedRule>,
+
+    // The rule to give to @skip, if any.
+    pub skip: Option<String>,
+
+    // The constructs pglsf cannot express.
+    pub issues: Vec<GrammarError>
+}
+
+impl Imported {
+    pub fn rule(&self, name: &str) -> Option<&ImportedRule> {
+        self.rules.iter().find(|rule| rule.name == name)
+    }
+}
+
+// The name of an imported rule in pglsf: lowercase, with '_' for the characters a name cannot have,
+// e.g. hex-digit becomes hex_digit and ASCII_DIGIT becomes ascii_digit.
+pub fn pglsf_name(name: &str) -> String {
+    let mut result: String = name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_'
+        })
+        .collect();
+    if !result.starts_with(|c: char| c.is_ascii_lowercase()) {
+        result.insert_str(0, "r_");
+    }
+    result
+}
+
+// The name of the symbol for a character, when characters are declared as symbols. Those that
+// pglsf.pglsf declares get the same names.
+pub fn symbol_name(c: char) -> String {
+    let name = match c {
+        'A'..='Z' => return format!("ULETTER_{}", c),
+        'a'..='z' => return format!("LETTER_{}", c.to_ascii_uppercase()),
+        '0'..='9' => return format!("DIGIT_{}", c),
+        ' ' => "SPACE",
+        '\t' => "TAB",
+        '\n' => "NEWLINE",
+        '\r' => "CARRIAGE_RETURN",
+        '!' => "EXCLAMATION_MARK",
+        '"' => "QUOTE",
+        '#' => "POUND_SIGN",
+        '$' => "DOLLAR_SIGN",
+        '%' => "PERCENT_SIGN",
+        '&' => "AMPERSAND",
+        '\'' => "APOSTROPHE",
+        '(' => "LEFT_PAREN",
+        ')' => "RIGHT_PAREN",
+        '*' => "ASTERISK",
+        '+' => "PLUS_SIGN",
+        ',' => "COMMA",
+        '-' => "DASH",
+        '.' => "PERIOD",
+        '/' => "SLASH",
+        ':' => "COLON",
+        ';' => "SEMICOLON",
+        '<' => "LESS_THAN",
+        '=' => "EQUAL_SIGN",
+        '>' => "GREATER_THAN",
+        '?' => "QUESTION_MARK",
+        '@' => "AT_SIGN",
+        '[' => "LEFT_BRACKET",
+        '\\' => "BACKSLASH",
+        ']' => "RIGHT_BRACKET",
+        '^' => "CARET",
+        '_' => "UNDERSCORE",
+        '`' => "BACKTICK",
+        '{' => "LEFT_BRACE",
+        '|' => "PIPE",
+        '}' => "RIGHT_BRACE",
+        '~' => "TILDE",
+        _ => return format!("CHAR_{:04X}", c as u32)
+    };
+    name.to_string()
+}
+
+// The most symbols a range may declare with --symbols.
+const MAX_RANGE: u32 = 256;
+
+// How the characters of an imported grammar become symbols.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Alphabet {
+    // The bytes of their UTF-8 encoding.
+    Binary,
+
+    // A declared symbol for every character the grammar mentions.
+    Named
+}
+
+// Turns the characters of rules into symbols.
+struct Lowering {
+    alphabet: Alphabet,
+
+    // With Alphabet::Named, the characters that are declared, in order.
+    chars: Vec<char>,
+
+    issues: Vec<GrammarError>
+}
+
+impl Lowering {
+    fn expr(kind: ExprKind) -> Expr {
+        Expr::new(kind, Span::default())
+    }
+
+    fn issue(&mut self, message: impl Into<String>, span: Span) {
+        self.issues.push(GrammarError::new(message, span));
+    }
+
+    // The symbols of one character: a declared symbol, or the bytes that encode it.
+    fn char(&self, c: char) -> Expr {
+        match self.alphabet {
+            Alphabet::Named => Self::expr(ExprKind::Symbol(symbol_name(c))),
+            Alphabet::Binary => {
+                let mut bytes = [0; 4];
+                let mut items: Vec<Expr> = c.encode_utf8(&mut bytes).bytes().map(|byte| Self::expr(ExprKind::Byte(byte))).collect();
+                match items.len() {
+                    1 => items.pop().unwrap(),
+                    _ => Self::expr(ExprKind::Seq(items))
+                }
+            }
+        }
+    }
+
+    // The alternatives for the ranges of characters of a class.
+    fn ranges(&mut self, ranges: &[(char, char)], span: Span) -> Vec<Expr> {
+        let mut alternatives = Vec::new();
+        for &(first, last) in ranges {
+            match first == last {
+                true => alternatives.push(self.char(first)),
+                false if self.alphabet == Alphabet::Binary && !last.is_ascii() => {
+                    self.issue(format!("the range {:?}-{:?} goes past ASCII, so it cannot be written over bytes; try --symbols", first, last), span)
+                },
+                false => alternatives.push(Self::expr(ExprKind::Range(Box::new(self.char(first)), Box::new(self.char(last)))))
+            }
+        }
+        alternatives
+    }
+
+    // The ranges of the alphabet that are in none of the ranges.
+    fn complement(&mut self, ranges: &[(char, char)], span: Span) -> Vec<Expr> {
+        let excluded = |c: char| ranges.iter().any(|&(first, last)| first <= c && c <= last);
+        let chars: Vec<char> = match self.alphabet {
+            Alphabet::Named => self.chars.clone(),
+            Alphabet::Binary => {
+                if ranges.iter().any(|(_, last)| !last.is_ascii()) {
+                    self.issue("a negated class with characters past ASCII cannot be written over bytes; try --symbols", span);
+                    return Vec::new()
+                }
+                (0..=255u8).map(char::from).collect()
+            }
+        };
+
+        // Runs of characters that are next to each other in the alphabet.
+        let mut alternatives = Vec::new();
+        let mut run: Option<(char, char)> = None;
+        for c in chars.into_iter().chain([char::MAX]) {
+            match (run, c != char::MAX && !excluded(c)) {
+                (Some((first, _)), true) => run = Some((first, c)),
+                (None, true) => run = Some((c, c)),
+                (Some(range), false) => {
+                    alternatives.push(match range.0 == range.1 {
+                        true => self.byte_or_char(range.0),
+                        false => Self::expr(ExprKind::Range(Box::new(self.byte_or_char(range.0)), Box::new(self.byte_or_char(range.1))))
+                    });
+                    run = None;
+                },
+                (None, false) => ()
+            }
+        }
+        alternatives
+    }
+
+    // A character of the alphabet itself: a byte of a binary grammar stands for itself, not for
+    // the encoding of the character with its value.
+    fn byte_or_char(&self, c: char) -> Expr {
+        match self.alphabet {
+            Alphabet::Binary => Self::expr(ExprKind::Byte(c as u32 as u8)),
+            Alphabet::Named => self.char(c)
+        }
+    }
+
+    // The items one after the other, with the sequences among them taken apart. None if there are
+    // none.
+    fn seq(items: Vec<Expr>) -> Option<Expr> {
+        let mut flat = Vec::new();
+        for item in items {
+            match item.kind {
+                ExprKind::Seq(inner) => flat.extend(inner),
+                _ => flat.push(item)
+            }
+        }
+        match flat.len() {
+            0 => None,
+            1 => flat.pop(),
+            _ => Some(Self::expr(ExprKind::Seq(flat)))
+        }
+    }
+
+    // The same for alternatives and unions.
+    fn union(alternatives: Vec<Expr>) -> Option<Expr> {
+        let mut flat = Vec::new();
+        for alternative in alternatives {
+            match alternative.kind {
+                ExprKind::Union(inner) if alternative.label.is_none() => flat.extend(inner),
+                _ => flat.push(alternative)
+            }
+        }
+        match flat.len() {
+            0 => None,
+            1 => flat.pop(),
+            _ => Some(Self::expr(ExprKind::Union(flat)))
+        }
+    }
+
+    // The expression for an item, or None for one that matches nothing, which pglsf can only
+    // write with '?'.
+    fn lower(&mut self, item: &Item) -> Option<Expr> {
+        match &item.kind {
+            ItemKind::Text(text, case_insensitive) => {
+                let items: Vec<Expr> = text.chars()
+                    .map(|c| match *case_insensitive && c.is_ascii_alphabetic() {
+                        true => Self::expr(ExprKind::Union(vec![self.char(c.to_ascii_lowercase()), self.char(c.to_ascii_uppercase())])),
+                        false => self.char(c)
+                    })
+                    .collect();
+                Self::seq(items)
+            },
+            ItemKind::Class(ranges, negated) => {
+                let issues = self.issues.len();
+                let alternatives = match negated {
+                    true => self.complement(ranges, item.span),
+                    false => self.ranges(ranges, item.span)
+                };
+                if alternatives.is_empty() && self.issues.len() == issues {
+                    self.issue("the class matches no character", item.span);
+                }
+                // An empty class is never written, since it was reported.
+                Self::union(alternatives).or(Some(Self::expr(ExprKind::Seq(Vec::new()))))
+            },
+            ItemKind::Any => match self.alphabet {
+                Alphabet::Binary => Some(Self::expr(ExprKind::Range(Box::new(Self::expr(ExprKind::Byte(0))), Box::new(Self::expr(ExprKind::Byte(255)))))),
+                Alphabet::Named => {
+                    let all = self.complement(&[], item.span);
+                    Self::union(all)
+                }
+            },
+            ItemKind::Rule(name) => Some(Self::expr(ExprKind::Rule(pglsf_name(name)))),
+            ItemKind::Seq(items) => {
+                let items: Vec<Expr> = items.iter().filter_map(|item| self.lower(item)).collect();
+                Self::seq(items)
+            },
+            ItemKind::Union(items) => {
+                let lowered: Vec<Option<Expr>> = items.iter().map(|item| self.lower(item)).collect();
+                let empty = lowered.iter().any(Option::is_none);
+                let body = Self::union(lowered.into_iter().flatten().collect())?;
+                match empty {
+                    true => Some(Self::expr(ExprKind::Opt(Box::new(body)))),
+                    false => Some(body)
+                }
+            },
+            ItemKind::Opt(inner) => Some(Self::expr(ExprKind::Opt(Box::new(self.lower(inner)?)))),
+            ItemKind::Star(inner) => Some(Self::expr(ExprKind::Star(Box::new(self.lower(inner)?)))),
+            ItemKind::Plus(inner) => Some(Self::expr(ExprKind::Plus(Box::new(self.lower(inner)?)))),
+            ItemKind::Repeat(inner, min, max) => {
+                let body = self.lower(inner)?;
+                let mut items = vec![body.clone(); *min];
+                match max {
+                    // x{2,} is x +x.
+                    None => match items.pop() {
+                        Some(last) => items.push(Self::expr(ExprKind::Plus(Box::new(last)))),
+                        None => items.push(Self::expr(ExprKind::Star(Box::new(body))))
+                    },
+                    // x{1,3} is x ?(x ?x).
+                    Some(max) => {
+                        let mut optional: Option<Expr> = None;
+                        for _ in *min..*max {
+                            let inner = match optional {
+                                Some(rest) => Self::expr(ExprKind::Seq(vec![body.clone(), rest])),
+                                None => body.clone()
+                            };
+                            optional = Some(Self::expr(ExprKind::Opt(Box::new(inner))));
+                        }
+                        items.extend(optional);
+                    }
+                }
+                Self::seq(items)
+            },
+            ItemKind::Label(name, inner) => {
+                let mut expr = self.lower(inner)?;
+                match expr.kind {
+                    ExprKind::Rule(_) | ExprKind::Symbol(_) | ExprKind::Byte(_) => expr.label = Some(Label { name: pglsf_name(name), span: Span::default() }),
+                    _ => self.issue(format!("label '{}' is not on a rule or a single symbol, which is all pglsf can label", name), item.span)
+                }
+                Some(expr)
+            }
+        }
+    }
+}
+
+// Every character an item mentions, with the whole of its ranges.
+fn collect_chars(item: &Item, chars: &mut Vec<char>, issues: &mut Vec<GrammarError>) {
+    match &item.kind {
+        ItemKind::Text(text, case_insensitive) => for c in text.chars() {
+            chars.push(c);
+            if *case_insensitive {
+                chars.push(c.to_ascii_lowercase());
+                chars.push(c.to_ascii_uppercase());
+            }
+        },
+        ItemKind::Class(ranges, _) => for &(first, last) in ranges {
+            match (last as u32).saturating_sub(first as u32) >= MAX_RANGE {
+                true => issues.push(GrammarError::new(format!("the range {:?}-{:?} would need more than {} symbols", first, last, MAX_RANGE), item.span)),
+                false => chars.extend(first..=last)
+            }
+        },
+        ItemKind::Any | ItemKind::Rule(_) => (),
+        ItemKind::Seq(items) | ItemKind::Union(items) => for item in items {
+            collect_chars(item, chars, issues);
+        },
+        ItemKind::Opt(inner) | ItemKind::Star(inner) | ItemKind::Plus(inner) | ItemKind::Repeat(inner, _, _) | ItemKind::Label(_, inner) => {
+            collect_chars(inner, chars, issues)
+        }
+    }
+}
+
+// Every rule an item uses, with where.
+fn rule_uses<'a>(item: &'a Item, uses: &mut Vec<(&'a str, Span)>) {
+    match &item.kind {
+        ItemKind::Rule(name) => uses.push((name, item.span)),
+        ItemKind::Text(_, _) | ItemKind::Class(_, _) | ItemKind::Any => (),
+        ItemKind::Seq(items) | ItemKind::Union(items) => for item in items {
+            rule_uses(item, uses);
+        },
+        ItemKind::Opt(inner) | ItemKind::Star(inner) | ItemKind::Plus(inner) | ItemKind::Repeat(inner, _, _) | ItemKind::Label(_, inner) => {
+            rule_uses(inner, uses)
+        }
+    }
+}
+
+// The grammar for the imported rules, or every issue with them. The spans of the issues are in the
+// source the rules were read from.
+pub fn to_grammar(imported: &Imported, alphabet: Alphabet) -> Result<Grammar, Vec<GrammarError>> {
+    let mut issues = imported.issues.clone();
+    if imported.rules.is_empty() {
+        issues.push(GrammarError::new("the grammar has no rules", Span::default()));
+    }
+    for rule in &imported.rules {
+        let mut uses = Vec::new();
+        rule_uses(&rule.item, &mut uses);
+        for (name, span) in uses {
+            if imported.rule(name).is_none() {
+                issues.push(GrammarError::new(format!("rule '{}' is not defined", name), span));
+            }
+        }
+    }
+
+    let mut chars = Vec::new();
+    if alphabet == Alphabet::Named {
+        for rule in &imported.rules {
+            collect_chars(&rule.item, &mut chars, &mut issues);
+        }
+        chars.sort();
+        chars.dedup();
+    }
+    let mut lowering = Lowering { alphabet, chars, issues };
+
+    let mut rules = Vec::new();
+    for (i, rule) in imported.rules.iter().enumerate() {
+        let Some(expr) = lowering.lower(&rule.item) else {
+            lowering.issue(format!("rule '{}' only matches nothing, which pglsf cannot write", rule.name), rule.name_span);
+            continue
+        };
+        rules.push(RuleDef {
+            name: pglsf_name(&rule.name),
+            name_span: Span::default(),
+            params: Vec::new(),
+            expr,
+            action: None,
+            // The root is always visible.
+            visibility: if i == 0 { Visibility::Visible } else { rule.visibility },
+            token: rule.token
+        });
+    }
+    if !lowering.issues.is_empty() {
+        return Err(lowering.issues)
+    }
+
+    let symbols = match alphabet {
+        Alphabet::Binary => Symbols::Binary,
+        Alphabet::Named => Symbols::Named(lowering.chars.iter().map(|&c| SymbolDecl { name: symbol_name(c), span: Span::default() }).collect())
+    };
+    let skip = imported.skip.as_ref().map(|rule| Skip { rule: pglsf_name(rule), name_span: Span::default(), span: Span::default() });
+    Ok(Grammar { imports: Vec::new(), symbols, skip, rules })
+}
+
+// Reads characters from the source of an imported grammar.
+struct Cursor<'a> {
+    source: &'a str,
+    pos: usize
+}
+
+impl<'a> Cursor<'a> {
+    fn new(source: &'a str) -> Self {
+        Cursor { source, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    // Reads the text if the source continues with it.
+    fn eat(&mut self, text: &str) -> bool {
+        match self.rest().starts_with(text) {
+            true => {
+                self.pos += text.len();
+                true
+            },
+            false => false
+        }
+    }
+
+    // Reads characters while they satisfy the predicate.
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.source[start..self.pos]
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.pos)
+    }
+
+    // An error at the next character.
+    fn error(&self, message: impl Into<String>) -> GrammarError {
+        let len = self.peek().map_or(0, char::len_utf8);
+        GrammarError::new(message, Span::new(self.pos, self.pos + len))
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), GrammarError> {
+        match self.eat(text) {
+            true => Ok(()),
+            false => Err(self.error(format!("expected '{}'", text)))
+        }
+    }
+
+    // A number in the radix, such as the digits of %x41.
+    fn number(&mut self, radix: u32) -> Result<u32, GrammarError> {
+        let start = self.pos;
+        let digits = self.take_while(|c| c.is_digit(radix));
+        u32::from_str_radix(digits, radix).map_err(|_| GrammarError::new("expected a number", Span::new(start, start + 1)))
+    }
+
+    // The character with the code point, which must be a valid one.
+    fn code_point(&self, value: u32, start: usize) -> Result<char, GrammarError> {
+        char::from_u32(value).ok_or_else(|| GrammarError::new(format!("{:#X} is not a character", value), self.span_from(start)))
+    }
+}
+
+// The grammar in the notation, translated into the text of a .pglsf file.
+pub fn import(source: &str, from: &str, alphabet: Alphabet) -> Result<String, Vec<GrammarError>> {
+    let imported = match from {
+        "ebnf" => ebnf::parse(source),
+        "abnf" => abnf::parse(source),
+        "peg" => peg::parse(source),
+        _ => return Err(vec![GrammarError::new(format!("unknown notation '{}': expected ebnf, abnf or peg", from), Span::default())])
+    };
+    let text = formatter::grammar_text(&to_grammar(&imported.map_err(|error| vec![error])?, alphabet)?);
+
+    // The translation should always read; if it does not, say so rather than write it.
+    match grammar::read(&text) {
+        Ok(_) => Ok(text),
+        Err(error) => Err(vec![GrammarError::new(format!("the translated grammar does not read: {}", error.message), Span::default())])
+    }
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let usage = || {
+        eprintln!("usage: parsergen import --from ebnf|abnf|peg <grammar> [--symbols] [<out.pglsf>]");
+        ExitCode::FAILURE
+    };
+    let mut from = None;
+    let mut alphabet = Alphabet::Binary;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => match args.next() {
+                Some(notation) => from = Some(notation.as_str()),
+                None => return usage()
+            },
+            "--symbols" => alphabet = Alphabet::Named,
+            _ => paths.push(arg.as_str())
+        }
+    }
+    let (Some(from), [path, out_path @ ..]) = (from, paths.as_slice()) else { return usage() };
+    if out_path.len() > 1 || !matches!(from, "ebnf" | "abnf" | "peg") {
+        return usage()
+    }
+
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            return ExitCode::FAILURE
+        }
+    };
+    let text = match import(&source, from, alphabet) {
+        Ok(text) => text,
+        Err(issues) => {
+            for issue in issues {
+                eprintln!("{}", issue.describe(path, &source));
+            }
+            return ExitCode::FAILURE
+        }
+    };
+
+    match out_path.first() {
+        Some(out_path) => match fs::write(out_path, text) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("{}: {}", out_path, error);
+                ExitCode::FAILURE
+            }
+        },
+        None => {
+            print!("{}", text);
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
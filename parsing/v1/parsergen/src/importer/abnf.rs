@@ -0,0 +1,287 @@
+// abnf.rs
+//
+// Reads grammars in the ABNF of RFC 5234:
+//
+//     date = year "-" month "-" day
+//     year = 4DIGIT
+//     month = %x30 %x31-39 / %x31 %x30-32
+//     day = 2DIGIT ; comments run to the end of the line
+//     time = [hour ":" minute] *(":" second)
+//
+// A rule continues on the lines that start with whitespace, and =/ adds alternatives to a rule
+// defined before. Names are not case-sensitive, and neither are "strings", apart from the %s"..."
+// of RFC 7405. The core rules of RFC 5234, such as ALPHA, DIGIT and CRLF, are added when they are
+// used and the grammar does not define them itself. Prose values, <like this>, are reported.
+//
+
+use super::*;
+
+// Appendix B.1 of RFC 5234.
+const CORE_RULES: &str = "\
+ALPHA = %x41-5A / %x61-7A
+BIT = \"0\" / \"1\"
+CHAR = %x01-7F
+CR = %x0D
+CRLF = CR LF
+CTL = %x00-1F / %x7F
+DIGIT = %x30-39
+DQUOTE = %x22
+HEXDIG = DIGIT / \"A\" / \"B\" / \"C\" / \"D\" / \"E\" / \"F\"
+HTAB = %x09
+LF = %x0A
+LWSP = *(WSP / CRLF WSP)
+OCTET = %x00-FF
+SP = %x20
+VCHAR = %x21-7E
+WSP = SP / HTAB
+";
+
+struct Reader<'a> {
+    cursor: Cursor<'a>,
+    issues: Vec<GrammarError>
+}
+
+pub fn parse(source: &str) -> Result<Imported, GrammarError> {
+    let mut imported = read(source)?;
+
+    // The core rules the grammar uses, however indirectly.
+    let core = read(CORE_RULES).unwrap();
+    let mut pending: Vec<String> = imported.rules.iter().flat_map(|rule| used_rules(&rule.item)).collect();
+    while let Some(name) = pending.pop() {
+        if imported.rule(&name).is_none() {
+            if let Some(rule) = core.rule(&name) {
+                let mut rule = rule.clone();
+                // The spans are in CORE_RULES, so they would point at nothing in the source.
+                rule.name_span = Span::default();
+                pending.extend(used_rules(&rule.item));
+                imported.rules.push(rule);
+            }
+        }
+    }
+    Ok(imported)
+}
+
+// The names of the rules the item uses.
+fn used_rules(item: &Item) -> Vec<String> {
+    let mut uses = Vec::new();
+    rule_uses(item, &mut uses);
+    uses.into_iter().map(|(name, _)| name.to_string()).collect()
+}
+
+// The rules, with their names in lowercase, since ABNF does not tell them apart by case.
+fn read(source: &str) -> Result<Imported, GrammarError> {
+    let mut reader = Reader { cursor: Cursor::new(source), issues: Vec::new() };
+    let mut rules: Vec<ImportedRule> = Vec::new();
+    loop {
+        reader.skip_lines();
+        if reader.cursor.peek().is_none() {
+            return Ok(Imported { rules, skip: None, issues: reader.issues })
+        }
+
+        let start = reader.cursor.pos;
+        let name = reader.name()?;
+        let name_span = reader.cursor.span_from(start);
+        reader.skip();
+        let incremental = reader.cursor.eat("=/");
+        if !incremental {
+            reader.cursor.expect("=")?;
+        }
+        let item = reader.alternation()?;
+
+        match rules.iter_mut().find(|rule| rule.name == name) {
+            Some(rule) if incremental => {
+                let span = rule.item.span.to(item.span);
+                let mut alternatives = match std::mem::replace(&mut rule.item.kind, ItemKind::Any) {
+                    ItemKind::Union(alternatives) => alternatives,
+                    kind => vec![Item::new(kind, rule.item.span)]
+                };
+                alternatives.push(item);
+                rule.item = Item::new(ItemKind::Union(alternatives), span);
+            },
+            Some(_) => return Err(GrammarError::new(format!("rule '{}' is defined twice", name), name_span)),
+            None if incremental => return Err(GrammarError::new(format!("rule '{}' is not defined before =/", name), name_span)),
+            None => rules.push(ImportedRule { name, name_span, item, visibility: Visibility::Visible, token: false })
+        }
+    }
+}
+
+impl Reader<'_> {
+    // Skips the blank lines and comment lines between rules.
+    fn skip_lines(&mut self) {
+        loop {
+            self.cursor.take_while(char::is_whitespace);
+            match self.cursor.peek() {
+                Some(';') => {
+                    self.cursor.take_while(|c| c != '\n');
+                },
+                _ => return
+            }
+        }
+    }
+
+    // Skips whitespace and comments within a rule, which go on as long as the lines start with
+    // whitespace. Returns whether anything was skipped.
+    fn skip(&mut self) -> bool {
+        let start = self.cursor.pos;
+        loop {
+            self.cursor.take_while(|c| c == ' ' || c == '\t');
+            match self.cursor.peek() {
+                Some(';') => {
+                    self.cursor.take_while(|c| c != '\n');
+                },
+                Some('\r' | '\n') => {
+                    let line_end = self.cursor.pos;
+                    self.cursor.eat("\r");
+                    self.cursor.eat("\n");
+                    if !matches!(self.cursor.peek(), Some(' ' | '\t' | '\r' | '\n' | ';')) {
+                        self.cursor.pos = line_end;
+                        return self.cursor.pos > start
+                    }
+                },
+                _ => return self.cursor.pos > start
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, GrammarError> {
+        match self.cursor.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            true => Ok(self.cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '-').to_ascii_lowercase()),
+            false => Err(self.cursor.error("expected a rule name"))
+        }
+    }
+
+    // Concatenations separated by '/'.
+    fn alternation(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        self.skip();
+        let mut alternatives = vec![self.concatenation()?];
+        loop {
+            self.skip();
+            if !self.cursor.eat("/") {
+                break
+            }
+            self.skip();
+            alternatives.push(self.concatenation()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => Item::new(ItemKind::Union(alternatives), self.cursor.span_from(start))
+        })
+    }
+
+    // Repetitions separated by whitespace.
+    fn concatenation(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let mut items = vec![self.repetition()?];
+        loop {
+            let end = self.cursor.pos;
+            let skipped = self.skip();
+            if !skipped || matches!(self.cursor.peek(), None | Some('/' | ')' | ']' | '\r' | '\n')) {
+                self.cursor.pos = end;
+                break
+            }
+            items.push(self.repetition()?);
+        }
+        Ok(match items.len() {
+            1 => items.pop().unwrap(),
+            _ => Item::new(ItemKind::Seq(items), self.cursor.span_from(start))
+        })
+    }
+
+    // An element with an optional repeat before it: 3DIGIT, 1*ALPHA, *2("," item).
+    fn repetition(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let min = self.decimal();
+        let (min, max) = match self.cursor.eat("*") {
+            true => (min.unwrap_or(0), self.decimal()),
+            false => match min {
+                Some(count) => (count, Some(count)),
+                None => return self.element()
+            }
+        };
+        let element = self.element()?;
+        if max.is_some_and(|max| max < min) {
+            return Err(GrammarError::new("the repeat allows fewer than its minimum", self.cursor.span_from(start)))
+        }
+        let kind = match (min, max) {
+            (0, None) => ItemKind::Star(Box::new(element)),
+            (1, None) => ItemKind::Plus(Box::new(element)),
+            (0, Some(1)) => ItemKind::Opt(Box::new(element)),
+            _ => ItemKind::Repeat(Box::new(element), min, max)
+        };
+        Ok(Item::new(kind, self.cursor.span_from(start)))
+    }
+
+    fn decimal(&mut self) -> Option<usize> {
+        self.cursor.take_while(|c| c.is_ascii_digit()).parse().ok()
+    }
+
+    fn element(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let kind = match self.cursor.peek() {
+            Some('(') => {
+                self.cursor.bump();
+                let inner = self.alternation()?;
+                self.skip();
+                self.cursor.expect(")")?;
+                return Ok(inner)
+            },
+            Some('[') => {
+                self.cursor.bump();
+                let inner = self.alternation()?;
+                self.skip();
+                self.cursor.expect("]")?;
+                ItemKind::Opt(Box::new(inner))
+            },
+            Some('"') => self.string(true)?,
+            Some('%') => {
+                self.cursor.bump();
+                match self.cursor.bump().map(|c| c.to_ascii_lowercase()) {
+                    Some('s') => self.string(false)?,
+                    Some('i') => self.string(true)?,
+                    Some('x') => self.numbers(16, start)?,
+                    Some('d') => self.numbers(10, start)?,
+                    Some('b') => self.numbers(2, start)?,
+                    _ => return Err(GrammarError::new("expected x, d, b, s or i after '%'", self.cursor.span_from(start)))
+                }
+            },
+            Some('<') => {
+                self.cursor.take_while(|c| c != '>' && c != '\n');
+                self.cursor.expect(">")?;
+                self.issues.push(GrammarError::new("a prose value describes its input in words, which pglsf cannot", self.cursor.span_from(start)));
+                ItemKind::Text(String::new(), false)
+            },
+            Some(c) if c.is_ascii_alphabetic() => ItemKind::Rule(self.name()?),
+            _ => return Err(self.cursor.error("expected an element"))
+        };
+        Ok(Item::new(kind, self.cursor.span_from(start)))
+    }
+
+    fn string(&mut self, case_insensitive: bool) -> Result<ItemKind, GrammarError> {
+        self.cursor.expect("\"")?;
+        let text = self.cursor.take_while(|c| c != '"' && c != '\n').to_string();
+        self.cursor.expect("\"")?;
+        Ok(ItemKind::Text(text, case_insensitive))
+    }
+
+    // The numbers after %x, %d or %b: one character, a range such as %x41-5A, or a string such as
+    // %x0D.0A.
+    fn numbers(&mut self, radix: u32, start: usize) -> Result<ItemKind, GrammarError> {
+        let first = self.cursor.number(radix)?;
+        let first = self.cursor.code_point(first, start)?;
+        if self.cursor.eat("-") {
+            let last = self.cursor.number(radix)?;
+            let last = self.cursor.code_point(last, start)?;
+            if last < first {
+                return Err(GrammarError::new("the range is backwards", self.cursor.span_from(start)))
+            }
+            return Ok(ItemKind::Class(vec![(first, last)], false))
+        }
+        let mut text = first.to_string();
+        while self.cursor.eat(".") {
+            let next = self.cursor.number(radix)?;
+            text.push(self.cursor.code_point(next, start)?);
+        }
+        Ok(ItemKind::Text(text, false))
+    }
+}
@@ -0,0 +1,209 @@
+// ebnf.rs
+//
+// Reads grammars in the EBNF of the W3C, as the XML specification writes them:
+//
+//     document ::= prolog element Misc*
+//     Char ::= #x9 | #xA | #xD | [#x20-#xD7FF]
+//     PubidChar ::= #x20 | #xD | #xA | [a-zA-Z0-9] | [-'()+,./:=?;!*#@$_%]
+//
+// A rule ends where the next one starts, with a name followed by '::='. Comments are /* ... */,
+// and the [ wfc: ... ] and [ vc: ... ] notes after a rule are skipped. The exceptions A - B, which
+// match what A does unless B does too, are reported.
+//
+
+use super::*;
+
+struct Reader<'a> {
+    cursor: Cursor<'a>,
+    issues: Vec<GrammarError>
+}
+
+pub fn parse(source: &str) -> Result<Imported, GrammarError> {
+    let mut reader = Reader { cursor: Cursor::new(source), issues: Vec::new() };
+    let mut rules = Vec::new();
+    reader.skip()?;
+    while reader.cursor.peek().is_some() {
+        rules.push(reader.rule()?);
+    }
+    Ok(Imported { rules, skip: None, issues: reader.issues })
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Reader<'_> {
+    // Skips whitespace, comments and notes.
+    fn skip(&mut self) -> Result<(), GrammarError> {
+        loop {
+            self.cursor.take_while(char::is_whitespace);
+            let rest = self.cursor.rest();
+            let note = rest.starts_with('[') && ["wfc:", "vc:"].iter().any(|kind| rest[1..].trim_start().starts_with(kind));
+            let end = match () {
+                _ if rest.starts_with("/*") => rest.find("*/").map(|i| i + 2),
+                _ if note => rest.find(']').map(|i| i + 1),
+                _ => return Ok(())
+            };
+            match end {
+                Some(end) => self.cursor.pos += end,
+                None => return Err(self.cursor.error(if note { "unterminated note" } else { "unterminated comment" }))
+            }
+        }
+    }
+
+    fn name(&mut self) -> Option<String> {
+        match self.cursor.peek().is_some_and(is_name_start) {
+            true => Some(self.cursor.take_while(is_name_char).to_string()),
+            false => None
+        }
+    }
+
+    // Whether the next rule starts here.
+    fn at_rule_start(&mut self) -> bool {
+        let start = self.cursor.pos;
+        let found = self.name().is_some() && {
+            self.cursor.take_while(char::is_whitespace);
+            self.cursor.rest().starts_with("::=")
+        };
+        self.cursor.pos = start;
+        found
+    }
+
+    fn rule(&mut self) -> Result<ImportedRule, GrammarError> {
+        let start = self.cursor.pos;
+        let Some(name) = self.name() else { return Err(self.cursor.error("expected a rule name")) };
+        let name_span = self.cursor.span_from(start);
+        self.skip()?;
+        self.cursor.expect("::=")?;
+        let item = self.expr()?;
+        Ok(ImportedRule { name, name_span, item, visibility: Visibility::Visible, token: false })
+    }
+
+    // Alternatives separated by '|'.
+    fn expr(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let mut alternatives = vec![self.seq()?];
+        while self.cursor.eat("|") {
+            alternatives.push(self.seq()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => Item::new(ItemKind::Union(alternatives), self.cursor.span_from(start))
+        })
+    }
+
+    // Items one after the other, up to the end of the alternative. Skips what follows it.
+    fn seq(&mut self) -> Result<Item, GrammarError> {
+        self.skip()?;
+        let start = self.cursor.pos;
+        let mut items = Vec::new();
+        while !matches!(self.cursor.peek(), None | Some('|' | ')')) && !self.at_rule_start() {
+            items.push(self.difference()?);
+            self.skip()?;
+        }
+        Ok(match items.len() {
+            0 => return Err(self.cursor.error("expected an expression")),
+            1 => items.pop().unwrap(),
+            _ => Item::new(ItemKind::Seq(items), self.cursor.span_from(start))
+        })
+    }
+
+    // A - B, which is reported, or just A.
+    fn difference(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let item = self.postfix()?;
+        let end = self.cursor.pos;
+        self.skip()?;
+        if !self.cursor.eat("-") {
+            self.cursor.pos = end;
+            return Ok(item)
+        }
+        self.skip()?;
+        self.postfix()?;
+        self.issues.push(GrammarError::new("the exception A - B cannot be written in pglsf", self.cursor.span_from(start)));
+        Ok(item)
+    }
+
+    fn postfix(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let mut item = self.primary()?;
+        loop {
+            let kind = match self.cursor.peek() {
+                Some('?') => ItemKind::Opt(Box::new(item)),
+                Some('*') => ItemKind::Star(Box::new(item)),
+                Some('+') => ItemKind::Plus(Box::new(item)),
+                _ => return Ok(item)
+            };
+            self.cursor.bump();
+            item = Item::new(kind, self.cursor.span_from(start));
+        }
+    }
+
+    fn primary(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let kind = match self.cursor.peek() {
+            Some('(') => {
+                self.cursor.bump();
+                let inner = self.expr()?;
+                self.cursor.expect(")")?;
+                return Ok(inner)
+            },
+            Some(quote @ ('"' | '\'')) => {
+                self.cursor.bump();
+                let text = self.cursor.take_while(|c| c != quote && c != '\n').to_string();
+                self.cursor.expect(&quote.to_string())?;
+                ItemKind::Text(text, false)
+            },
+            Some('[') => {
+                self.cursor.bump();
+                let negated = self.cursor.eat("^");
+                let mut ranges = Vec::new();
+                while !self.cursor.eat("]") {
+                    let first = self.class_char()?;
+                    let last = match self.cursor.rest().starts_with('-') && !self.cursor.rest().starts_with("-]") {
+                        true => {
+                            self.cursor.bump();
+                            self.class_char()?
+                        },
+                        false => first
+                    };
+                    if last < first {
+                        return Err(GrammarError::new("the range is backwards", self.cursor.span_from(start)))
+                    }
+                    ranges.push((first, last));
+                }
+                ItemKind::Class(ranges, negated)
+            },
+            Some('#') => ItemKind::Text(self.hex_char()?.to_string(), false),
+            _ => match self.name() {
+                Some(name) => ItemKind::Rule(name),
+                None => return Err(self.cursor.error("expected an expression"))
+            }
+        };
+        Ok(Item::new(kind, self.cursor.span_from(start)))
+    }
+
+    // A character such as #x41.
+    fn hex_char(&mut self) -> Result<char, GrammarError> {
+        let start = self.cursor.pos;
+        self.cursor.expect("#x")?;
+        let value = self.cursor.number(16)?;
+        self.cursor.code_point(value, start)
+    }
+
+    // A character of a class.
+    fn class_char(&mut self) -> Result<char, GrammarError> {
+        match self.cursor.peek() {
+            Some('#') if self.cursor.rest().starts_with("#x") => self.hex_char(),
+            Some('\n') | None => Err(self.cursor.error("unterminated class")),
+            Some(c) => {
+                self.cursor.bump();
+                Ok(c)
+            }
+        }
+    }
+}
@@ -0,0 +1,364 @@
+// peg.rs
+//
+// Reads PEG grammars as pest writes them:
+//
+//     file = { SOI ~ (entry ~ NEWLINE)* ~ EOI }
+//     entry = { #key = name ~ "=" ~ value }
+//     name = @{ ASCII_ALPHA ~ (ASCII_ALPHANUMERIC | "_")* }
+//     value = _{ number | ^"true" | ^"false" }
+//     number = @{ ASCII_NONZERO_DIGIT ~ ASCII_DIGIT{0, 8} }
+//     WHITESPACE = _{ " " | "\t" }
+//
+// Silent rules (_) become @inline, since what they match still goes to the rule that used them,
+// and atomic ones (@ and $) become @token. When the grammar defines WHITESPACE or COMMENT, which
+// pest reads between the items of the other rules, they are given to @skip through a rule of
+// their own; pglsf also skips before the first item and after the last, where pest does not.
+// SOI and EOI are left out, since pglsf always reads the whole input, and the ASCII built-ins
+// become classes. Predicates (& and !), the stack (PUSH, POP and the like) and the other built-ins
+// are reported.
+//
+
+use super::*;
+
+struct Reader<'a> {
+    cursor: Cursor<'a>,
+    issues: Vec<GrammarError>
+}
+
+pub fn parse(source: &str) -> Result<Imported, GrammarError> {
+    let mut reader = Reader { cursor: Cursor::new(source), issues: Vec::new() };
+    let mut rules = Vec::new();
+    reader.skip()?;
+    while reader.cursor.peek().is_some() {
+        rules.push(reader.rule()?);
+        reader.skip()?;
+    }
+
+    // WHITESPACE and COMMENT are skipped through one rule that reads any number of them.
+    let skipped: Vec<Item> = ["WHITESPACE", "COMMENT"].iter()
+        .filter(|name| rules.iter().any(|rule: &ImportedRule| rule.name == **name))
+        .map(|name| Item::new(ItemKind::Rule(name.to_string()), Span::default()))
+        .collect();
+    let skip = match skipped.is_empty() {
+        true => None,
+        false => {
+            let mut name = "skipped".to_string();
+            while rules.iter().any(|rule| pglsf_name(&rule.name) == name) {
+                name.insert(0, '_');
+            }
+            let item = Item::new(ItemKind::Plus(Box::new(Item::new(ItemKind::Union(skipped), Span::default()))), Span::default());
+            rules.push(ImportedRule { name: name.clone(), name_span: Span::default(), item, visibility: Visibility::Hidden, token: false });
+            Some(name)
+        }
+    };
+    Ok(Imported { rules, skip, issues: reader.issues })
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// The class of a built-in rule, e.g. ASCII_DIGIT, or None if it is not one.
+fn builtin(name: &str) -> Option<ItemKind> {
+    let class = |ranges: &[(char, char)]| Some(ItemKind::Class(ranges.to_vec(), false));
+    match name {
+        "ANY" => Some(ItemKind::Any),
+        "ASCII_DIGIT" => class(&[('0', '9')]),
+        "ASCII_NONZERO_DIGIT" => class(&[('1', '9')]),
+        "ASCII_BIN_DIGIT" => class(&[('0', '1')]),
+        "ASCII_OCT_DIGIT" => class(&[('0', '7')]),
+        "ASCII_HEX_DIGIT" => class(&[('0', '9'), ('A', 'F'), ('a', 'f')]),
+        "ASCII_ALPHA_LOWER" => class(&[('a', 'z')]),
+        "ASCII_ALPHA_UPPER" => class(&[('A', 'Z')]),
+        "ASCII_ALPHA" => class(&[('A', 'Z'), ('a', 'z')]),
+        "ASCII_ALPHANUMERIC" => class(&[('0', '9'), ('A', 'Z'), ('a', 'z')]),
+        "ASCII" => class(&[('\0', '\x7F')]),
+        "NEWLINE" => Some(ItemKind::Union(vec![
+            Item::new(ItemKind::Text("\n".to_string(), false), Span::default()),
+            Item::new(ItemKind::Text("\r\n".to_string(), false), Span::default()),
+            Item::new(ItemKind::Text("\r".to_string(), false), Span::default())
+        ])),
+        _ => None
+    }
+}
+
+impl Reader<'_> {
+    // Skips whitespace and comments, including the /// and //! doc comments.
+    fn skip(&mut self) -> Result<(), GrammarError> {
+        loop {
+            self.cursor.take_while(char::is_whitespace);
+            if self.cursor.rest().starts_with("//") {
+                self.cursor.take_while(|c| c != '\n');
+            } else if self.cursor.rest().starts_with("/*") {
+                match self.cursor.rest().find("*/") {
+                    Some(end) => self.cursor.pos += end + 2,
+                    None => return Err(self.cursor.error("unterminated comment"))
+                }
+            } else {
+                return Ok(())
+            }
+        }
+    }
+
+    fn issue(&mut self, message: impl Into<String>, start: usize) {
+        self.issues.push(GrammarError::new(message, self.cursor.span_from(start)));
+    }
+
+    fn name(&mut self) -> Result<String, GrammarError> {
+        match self.cursor.peek().is_some_and(is_name_start) {
+            true => Ok(self.cursor.take_while(is_name_char).to_string()),
+            false => Err(self.cursor.error("expected a rule name"))
+        }
+    }
+
+    fn rule(&mut self) -> Result<ImportedRule, GrammarError> {
+        let start = self.cursor.pos;
+        let name = self.name()?;
+        let name_span = self.cursor.span_from(start);
+        self.skip()?;
+        self.cursor.expect("=")?;
+        self.skip()?;
+        let (visibility, token) = match self.cursor.peek() {
+            Some('_') => (Visibility::Inline, false),
+            Some('@' | '$') => (Visibility::Visible, true),
+            // Non-atomic: skipping goes on inside an atomic rule, which pglsf cannot do.
+            Some('!') => {
+                let start = self.cursor.pos;
+                self.cursor.bump();
+                self.issue("non-atomic rules cannot be written in pglsf", start);
+                (Visibility::Visible, false)
+            },
+            _ => (Visibility::Visible, false)
+        };
+        if visibility != Visibility::Visible || token {
+            self.cursor.bump();
+            self.skip()?;
+        }
+        self.cursor.expect("{")?;
+        let item = self.choice()?;
+        self.cursor.expect("}")?;
+        Ok(ImportedRule { name, name_span, item, visibility, token })
+    }
+
+    // Sequences separated by '|'. A '|' may come before the first one too.
+    fn choice(&mut self) -> Result<Item, GrammarError> {
+        self.skip()?;
+        let start = self.cursor.pos;
+        if self.cursor.eat("|") {
+            self.skip()?;
+        }
+        let mut alternatives = vec![self.sequence()?];
+        while self.cursor.eat("|") {
+            self.skip()?;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => Item::new(ItemKind::Union(alternatives), self.cursor.span_from(start))
+        })
+    }
+
+    // Items separated by '~'. Skips what follows the sequence.
+    fn sequence(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let mut items = vec![self.prefixed()?];
+        self.skip()?;
+        while self.cursor.eat("~") {
+            self.skip()?;
+            items.push(self.prefixed()?);
+            self.skip()?;
+        }
+
+        // SOI and EOI match nothing, and are left out.
+        items.retain(|item| !matches!(&item.kind, ItemKind::Text(text, _) if text.is_empty()));
+        Ok(match items.len() {
+            1 => items.pop().unwrap(),
+            _ => Item::new(ItemKind::Seq(items), self.cursor.span_from(start))
+        })
+    }
+
+    // An item, possibly with a predicate before it, which is reported.
+    fn prefixed(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        if self.cursor.eat("&") || self.cursor.eat("!") {
+            self.skip()?;
+            self.postfixed()?;
+            self.issue("predicates look ahead without reading, which pglsf cannot", start);
+            return Ok(Item::new(ItemKind::Text(String::new(), false), self.cursor.span_from(start)))
+        }
+        self.postfixed()
+    }
+
+    fn postfixed(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let mut item = self.term()?;
+        loop {
+            let end = self.cursor.pos;
+            self.skip()?;
+            let kind = match self.cursor.peek() {
+                Some('?') => ItemKind::Opt(Box::new(item)),
+                Some('*') => ItemKind::Star(Box::new(item)),
+                Some('+') => ItemKind::Plus(Box::new(item)),
+                Some('{') => {
+                    self.cursor.bump();
+                    let (min, max) = self.bounds()?;
+                    item = Item::new(ItemKind::Repeat(Box::new(item), min, max), self.cursor.span_from(start));
+                    continue
+                },
+                _ => {
+                    self.cursor.pos = end;
+                    return Ok(item)
+                }
+            };
+            self.cursor.bump();
+            item = Item::new(kind, self.cursor.span_from(start));
+        }
+    }
+
+    // The bounds of a repetition after its '{': {n}, {n,}, {,m} or {n,m}.
+    fn bounds(&mut self) -> Result<(usize, Option<usize>), GrammarError> {
+        let start = self.cursor.pos;
+        let number = |reader: &mut Self| {
+            reader.cursor.take_while(char::is_whitespace);
+            let number = reader.cursor.take_while(|c| c.is_ascii_digit()).parse().ok();
+            reader.cursor.take_while(char::is_whitespace);
+            number
+        };
+        let min = number(self);
+        let bounds = match self.cursor.eat(",") {
+            true => (min.unwrap_or(0), number(self)),
+            false => match min {
+                Some(count) => (count, Some(count)),
+                None => return Err(self.cursor.error("expected a number"))
+            }
+        };
+        self.cursor.expect("}")?;
+        if bounds.1.is_some_and(|max| max < bounds.0) {
+            return Err(GrammarError::new("the repetition allows fewer than its minimum", self.cursor.span_from(start)))
+        }
+        Ok(bounds)
+    }
+
+    fn term(&mut self) -> Result<Item, GrammarError> {
+        let start = self.cursor.pos;
+        let kind = match self.cursor.peek() {
+            Some('(') => {
+                self.cursor.bump();
+                let inner = self.choice()?;
+                self.cursor.expect(")")?;
+                return Ok(inner)
+            },
+            Some('#') => {
+                self.cursor.bump();
+                let label = self.name()?;
+                self.skip()?;
+                self.cursor.expect("=")?;
+                self.skip()?;
+                ItemKind::Label(label, Box::new(self.term()?))
+            },
+            Some('"') => ItemKind::Text(self.string()?, false),
+            Some('^') => {
+                self.cursor.bump();
+                ItemKind::Text(self.string()?, true)
+            },
+            Some('\'') => {
+                let first = self.char()?;
+                self.skip()?;
+                self.cursor.expect("..")?;
+                self.skip()?;
+                let last = self.char()?;
+                if last < first {
+                    return Err(GrammarError::new("the range is backwards", self.cursor.span_from(start)))
+                }
+                ItemKind::Class(vec![(first, last)], false)
+            },
+            _ => {
+                let name = self.name()?;
+                match name.as_str() {
+                    "SOI" | "EOI" => ItemKind::Text(String::new(), false),
+                    "PUSH" | "POP" | "POP_ALL" | "PEEK" | "PEEK_ALL" | "DROP" => {
+                        // The argument of PUSH, and the slice of PEEK, are read and left out.
+                        self.skip()?;
+                        if self.cursor.eat("(") {
+                            self.choice()?;
+                            self.cursor.expect(")")?;
+                        } else if self.cursor.eat("[") {
+                            self.cursor.take_while(|c| c != ']');
+                            self.cursor.expect("]")?;
+                        }
+                        self.issue("the stack of pest cannot be written in pglsf", start);
+                        ItemKind::Text(String::new(), false)
+                    },
+                    // The other built-ins, such as XID_START, show up as rules that are not defined.
+                    _ => builtin(&name).unwrap_or(ItemKind::Rule(name))
+                }
+            }
+        };
+        Ok(Item::new(kind, self.cursor.span_from(start)))
+    }
+
+    // A string such as "a\n", without its quotes and with its escapes replaced.
+    fn string(&mut self) -> Result<String, GrammarError> {
+        self.cursor.expect("\"")?;
+        let mut text = String::new();
+        loop {
+            match self.cursor.peek() {
+                Some('"') => {
+                    self.cursor.bump();
+                    return Ok(text)
+                },
+                Some('\\') => text.push(self.escape()?),
+                Some('\n') | None => return Err(self.cursor.error("unterminated string")),
+                Some(c) => {
+                    self.cursor.bump();
+                    text.push(c);
+                }
+            }
+        }
+    }
+
+    // A character such as 'a' or '\''.
+    fn char(&mut self) -> Result<char, GrammarError> {
+        self.cursor.expect("'")?;
+        let c = match self.cursor.peek() {
+            Some('\\') => self.escape()?,
+            Some(c) if c != '\'' && c != '\n' => {
+                self.cursor.bump();
+                c
+            },
+            _ => return Err(self.cursor.error("expected a character"))
+        };
+        self.cursor.expect("'")?;
+        Ok(c)
+    }
+
+    fn escape(&mut self) -> Result<char, GrammarError> {
+        let start = self.cursor.pos;
+        self.cursor.expect("\\")?;
+        let c = match self.cursor.bump() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '"' | '\'')) => c,
+            Some('x') => {
+                let digits = self.cursor.rest().get(..2).unwrap_or("");
+                let value = u32::from_str_radix(digits, 16).map_err(|_| self.cursor.error("expected two hex digits"))?;
+                self.cursor.pos += 2;
+                return self.cursor.code_point(value, start)
+            },
+            Some('u') => {
+                self.cursor.expect("{")?;
+                let value = self.cursor.number(16)?;
+                self.cursor.expect("}")?;
+                return self.cursor.code_point(value, start)
+            },
+            _ => return Err(GrammarError::new("unknown escape", self.cursor.span_from(start)))
+        };
+        Ok(c)
+    }
+}
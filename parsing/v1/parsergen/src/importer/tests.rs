@@ -0,0 +1,91 @@
+use crate::importer::*;
+use crate::grammar::program::Program;
+use crate::tester::{input_symbols, run, Outcome};
+
+fn accepts(text: &str, input: &str) -> bool {
+    let program = Program::compile(&grammar::read(text).unwrap()).unwrap();
+    matches!(run(&program, &input_symbols(&program, input).unwrap()), Outcome::Accept(_))
+}
+
+// The messages of the issues, each with the line and column it is at.
+fn issues(source: &str, from: &str) -> Vec<String> {
+    import(source, from, Alphabet::Binary).unwrap_err()
+        .iter()
+        .map(|issue| issue.describe("in", source))
+        .collect()
+}
+
+#[test]
+fn test_import_ebnf() {
+    let source = "/* attributes */\nattrs ::= attr (S attr)*\nattr ::= Name '=' '\"' [^<\"]* '\"'\nName ::= [a-zA-Z_] [-a-zA-Z0-9_]*\nS ::= (#x20 | #x9)+ [ vc: at least one ]\n";
+    let text = import(source, "ebnf", Alphabet::Binary).unwrap();
+    assert_eq!(text, "symbols\n\tbinary;\n\ngrammar\n\tattrs = attr *(s attr);\n\tattr = name 0x3D 0x22 *(0x00 ... 0x21 | 0x23 ... 0x3B | 0x3D ... 0xFF) 0x22;\n\tname =\n\t\t(0x61 ... 0x7A | 0x41 ... 0x5A | 0x5F)\n\t\t*(0x2D | 0x61 ... 0x7A | 0x41 ... 0x5A | 0x30 ... 0x39 | 0x5F);\n\ts = +(0x20 | 0x09);\n");
+    assert!(accepts(&text, "id=\"a b\" x-1=\"\""));
+    assert!(!accepts(&text, "id=\"a<b\""));
+}
+
+#[test]
+fn test_import_abnf() {
+    let source = "; a date\ndate = year \"-\" month \"-\" day\nyear = 4DIGIT\nmonth = %x30 %x31-39 / %x31 %x30-32\nday = 2DIGIT ; the day\n      [ %s\"Z\" ]\nDate =/ \"now\"\n";
+    let text = import(source, "abnf", Alphabet::Binary).unwrap();
+    assert_eq!(text, "symbols\n\tbinary;\n\ngrammar\n\tdate = year 0x2D month 0x2D day | (0x6E | 0x4E) (0x6F | 0x4F) (0x77 | 0x57);\n\tyear = digit digit digit digit;\n\tmonth = 0x30 0x31 ... 0x39 | 0x31 0x30 ... 0x32;\n\tday = digit digit ?0x5A;\n\tdigit = 0x30 ... 0x39;\n");
+    assert!(accepts(&text, "2024-05-17Z"));
+    assert!(accepts(&text, "NoW"));
+    assert!(!accepts(&text, "2024-13-01"));
+    assert!(!accepts(&text, "2024-05-17z"));
+
+    // Repeats with bounds, and a core rule that uses another.
+    let text = import("pin = 2*3DIGIT *1(\"-\" 2DIGIT)\nline = *WSP CRLF\n", "abnf", Alphabet::Binary).unwrap();
+    assert!(text.contains("\tpin = digit digit ?digit ?(0x2D digit digit);\n"));
+    assert!(text.contains("\tcrlf = cr lf;\n"));
+    assert!(accepts(&text, "123-45"));
+    assert!(!accepts(&text, "1234"));
+}
+
+#[test]
+fn test_import_peg() {
+    let source = "// pairs\nfile = { SOI ~ (pair ~ (\",\" ~ pair)*)? ~ EOI }\npair = { #key = name ~ \"=\" ~ value }\nname = @{ ASCII_ALPHA ~ (ASCII_ALPHANUMERIC | \"_\")* }\nvalue = _{ number | ^\"yes\" }\nnumber = @{ '1'..'9' ~ ASCII_DIGIT{0,2} }\nWHITESPACE = _{ \" \" }\n";
+    let text = import(source, "peg", Alphabet::Binary).unwrap();
+    assert!(text.contains("\t@skip skipped;\n"));
+    assert!(text.contains("\tpair = key:name 0x3D value;\n"));
+    assert!(text.contains("\t@token number = 0x31 ... 0x39 ?(0x30 ... 0x39 ?(0x30 ... 0x39));\n"));
+    assert!(text.contains("\t@inline value = number | (0x79 | 0x59) (0x65 | 0x45) (0x73 | 0x53);\n"));
+    assert!(text.contains("\t@hidden skipped = +whitespace;\n"));
+    assert!(accepts(&text, "a = 1, b_2=YES , c =999"));
+    assert!(!accepts(&text, "a = 1 0"));
+    assert!(!accepts(&text, "a = 1000"));
+}
+
+#[test]
+fn test_import_symbols() {
+    let text = import("s ::= 'ab' [^b] | [#x20-#x21]\n", "ebnf", Alphabet::Named).unwrap();
+    assert_eq!(text, "symbols\n\tSPACE, EXCLAMATION_MARK, LETTER_A, LETTER_B;\n\ngrammar\n\ts = LETTER_A LETTER_B SPACE ... LETTER_A | SPACE ... EXCLAMATION_MARK;\n");
+    assert!(accepts(&text, "LETTER_A LETTER_B EXCLAMATION_MARK"));
+    assert!(!accepts(&text, "LETTER_A LETTER_B LETTER_B"));
+
+    assert_eq!(symbol_name('q'), "LETTER_Q");
+    assert_eq!(symbol_name('('), "LEFT_PAREN");
+    assert_eq!(symbol_name('é'), "CHAR_00E9");
+    assert_eq!(pglsf_name("path-abempty"), "path_abempty");
+    assert_eq!(pglsf_name("_x"), "r__x");
+}
+
+#[test]
+fn test_import_issues() {
+    assert_eq!(issues("a ::= (b - 'x') 'y'\nb ::= [#x100-#x17F]\n", "ebnf"), [
+        "in:1:8: the exception A - B cannot be written in pglsf",
+        "in:2:7: the range 'Ā'-'ſ' goes past ASCII, so it cannot be written over bytes; try --symbols"
+    ]);
+    assert!(import("a ::= [#x100-#x17F]\n", "ebnf", Alphabet::Named).is_ok());
+    assert_eq!(issues("a = <prose> b\n", "abnf"), [
+        "in:1:5: a prose value describes its input in words, which pglsf cannot",
+        "in:1:13: rule 'b' is not defined"
+    ]);
+    assert_eq!(issues("a = { !\"x\" ~ PUSH(\"y\") ~ b }\nb = { #l = (\"p\" ~ \"q\") }\n", "peg"), [
+        "in:1:7: predicates look ahead without reading, which pglsf cannot",
+        "in:1:14: the stack of pest cannot be written in pglsf",
+        "in:2:7: label 'l' is not on a rule or a single symbol, which is all pglsf can label"
+    ]);
+    assert_eq!(issues("a = { \"x\" \n", "peg"), ["in:2:1: expected '}'"]);
+    assert_eq!(issues("a = \"x\"\na =/ \"y\"\nb =/ \"z\"\n", "abnf"), ["in:3:1: rule 'b' is not defined before =/"]);
+}
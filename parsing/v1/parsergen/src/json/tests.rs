@@ -0,0 +1,40 @@
+use crate::json::*;
+
+#[test]
+fn test_parse() {
+    let value = Json::parse(r#" {"id": 3, "method": "a/b", "params": {"list": [true, false, null, -1.5e1]}} "#).unwrap();
+    assert_eq!(value.get("id").as_u64(), Some(3));
+    assert_eq!(value.get("method").as_str(), Some("a/b"));
+    assert_eq!(
+        value.get("params").get("list"),
+        &Json::Array(vec![Json::Bool(true), Json::Bool(false), Json::Null, Json::Number(-15.0)])
+    );
+    assert_eq!(value.get("missing"), &Json::Null);
+}
+
+#[test]
+fn test_parse_strings() {
+    assert_eq!(Json::parse(r#""a\"\\\/\n\t\u0041\u00e9""#).unwrap(), Json::string("a\"\\/\n\tAé"));
+    assert_eq!(Json::parse(r#""\ud83d\ude00""#).unwrap(), Json::string("😀"));
+    assert_eq!(Json::parse("\"ü\"").unwrap(), Json::string("ü"));
+}
+
+#[test]
+fn test_parse_errors() {
+    for text in ["", "{", "[1,]", "{\"a\" 1}", "\"open", "nul", "1 2", "\"\\x\""] {
+        assert!(Json::parse(text).is_err(), "{}", text);
+    }
+}
+
+#[test]
+fn test_display() {
+    let value = Json::object([
+        ("text", Json::string("line\n\"quoted\"\u{1}")),
+        ("number", Json::from(42usize)),
+        ("items", Json::from(vec![Json::Null, Json::Bool(true)])),
+        ("empty", Json::Object(Vec::new()))
+    ]);
+    let text = value.to_string();
+    assert_eq!(text, r#"{"text":"line\n\"quoted\"\u0001","number":42,"items":[null,true],"empty":{}}"#);
+    assert_eq!(Json::parse(&text).unwrap(), value);
+}
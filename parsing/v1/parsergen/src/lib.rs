@@ -0,0 +1,27 @@
+// lib.rs
+//
+// The parts of parsergen shared by its binaries: parsergen itself, with its subcommands, and
+// parsergen-lsp, which serves .pglsf files to editors.
+//
+
+pub mod parse_machine;
+pub mod list;
+pub mod chain;
+pub mod arena;
+pub mod grammar;
+pub mod tree;
+pub mod tester;
+pub mod rng;
+pub mod fuzzer;
+pub mod coverage;
+pub mod diagram;
+pub mod formatter;
+pub mod json;
+pub mod lsp;
+pub mod actions;
+pub mod table;
+pub mod lr;
+pub mod descent;
+pub mod importer;
+pub mod exporter;
+pub mod bench;
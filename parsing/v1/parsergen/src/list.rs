@@ -4,8 +4,20 @@
 //
 // Implements a persistent list data structure. A stripped down implementation of Chain.
 //
+// The head of a list is its first item: iter, to_vec and Debug go from the head to the end, and
+// collecting an iterator into a list keeps its order. Extending a list conses each item in turn,
+// so the items end up before the list, in reverse.
+//
+// The nodes are shared through Rc, or through Arc with the sync feature, which makes a list Send
+// and Sync where its items are.
+//
 
+use std::fmt;
+use std::hash::{Hash, Hasher};
+#[cfg(not(feature = "sync"))]
 use std::rc::Rc;
+#[cfg(feature = "sync")]
+use std::sync::Arc as Rc;
 
 enum ListNode<T> {
     NonFinal(T, Rc<ListNode<T>>),
@@ -29,13 +41,6 @@ impl <T> ListNode<T> {
         }
     }
 
-    fn head_move(self) -> T {
-        match self {
-            NonFinal(head, _) => head,
-            Final(head) => head
-        }
-    }
-
     fn tail(&self) -> Rc<ListNode<T>> {
         match &self {
             NonFinal(_, tail) => tail.clone(),
@@ -44,20 +49,6 @@ impl <T> ListNode<T> {
     }
 }
 
-impl <T> std::cmp::PartialEq for ListNode<T> where T: PartialEq {    
-    fn eq(&self, other: &Self) -> bool {
-        match (self.is_final(), other.is_final()) {
-            (true, true) => self.head() == other.head(),
-            (false, false) => if self.head() == other.head() {
-                self.tail().eq(&other.tail())
-            } else {
-                false
-            },
-            _ => false
-        }
-    }
-}
-
 pub enum ListState<'a, T> {
     NonEmptyList(&'a T, List<T>),
     EmptyList
@@ -73,10 +64,10 @@ impl<T> List<T> {
     pub const EMPTY: List<T> = List::<T> { root: None };
 
     pub fn is_empty(&self) -> bool {
-        if let None = self.root { true } else { false }
+        self.root.is_none()
     }
 
-    pub fn state(&self) -> ListState<T> {
+    pub fn state(&self) -> ListState<'_, T> {
         match &self.root {
             Some(node_ref) => if node_ref.is_final() {
                 NonEmptyList(node_ref.head(), List::EMPTY)
@@ -87,38 +78,185 @@ impl<T> List<T> {
         }
     }
 
-    pub fn cons(item: T, tail: List<T>) -> List<T> {
-        match tail.root {
+    pub fn cons(item: T, mut tail: List<T>) -> List<T> {
+        match tail.root.take() {
             Some(node_ref) => List { root: Some(Rc::new(NonFinal(item, node_ref))) },
             None => List { root: Some(Rc::new(Final(item))) }
         }
     }
 
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { node: self.root.as_deref() }
+    }
+
+    // The number of items, counted by walking the list.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    // The items in reverse, in a list of their own.
+    pub fn rev(&self) -> List<T>
+        where T: Clone
+    {
+        let mut result = List::EMPTY;
+        result.extend(self.iter().cloned());
+        result
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+        where T: Clone
+    {
+        self.iter().cloned().collect()
+    }
+
     pub fn reverse_into_vec(self) -> Vec<T>
         where T: Clone
     {
         let mut old_list: List<T> = self;
         let mut result: Vec<T> = Vec::new();
-        loop {
-            match old_list.state() {
-                NonEmptyList(head, tail) => {
-                    result.push(head.clone());
-                    old_list = tail;
-                },
-                EmptyList => break
-            }
+        while let NonEmptyList(head, tail) = old_list.state() {
+            result.push(head.clone());
+            old_list = tail;
         }
 
+        // The head of the list is the last item pushed, so the vec is reversed to put it last.
+        result.reverse();
         result
     }
 }
 
 impl<T> Clone for List<T> {
     fn clone(&self) -> Self {
-        return Self{ root: self.root.clone() }
+        Self{ root: self.root.clone() }
     }
 }
 
 impl<T> Default for List<T> {
     fn default() -> Self{ List::EMPTY }
 }
+
+impl<T> Drop for List<T> {
+    // Frees the nodes no other list shares in a loop, as dropping them one inside the other would
+    // take stack for every node. into_inner gives the node to whichever handle is dropped last,
+    // even when two threads drop theirs at once, where try_unwrap could fail for both.
+    fn drop(&mut self) {
+        let mut next = self.root.take();
+        while let Some(node_ref) = next {
+            next = match Rc::into_inner(node_ref) {
+                Some(NonFinal(_, tail)) => Some(tail),
+                _ => None
+            };
+        }
+    }
+}
+
+impl<T> PartialEq for List<T> where T: PartialEq {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(node_ref), Some(other_node_ref)) if Rc::ptr_eq(node_ref, other_node_ref) => true,
+            _ => self.iter().eq(other.iter())
+        }
+    }
+}
+
+impl<T> Eq for List<T> where T: Eq {}
+
+impl<T> Hash for List<T> where T: Hash {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // The length goes first, as it does for slices, so that lists of lists hash apart.
+        state.write_usize(self.len());
+        for item in self {
+            item.hash(state);
+        }
+    }
+}
+
+impl<T> fmt::Debug for List<T> where T: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items: Vec<T> = iter.into_iter().collect();
+        items.into_iter().rev().fold(List::EMPTY, |tail, item| List::cons(item, tail))
+    }
+}
+
+impl<T> Extend<T> for List<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            *self = List::cons(item, std::mem::take(self));
+        }
+    }
+}
+
+// Borrows the items of a list, from the head to the end.
+pub struct Iter<'a, T> {
+    node: Option<&'a ListNode<T>>
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.node?;
+        self.node = match node {
+            NonFinal(_, tail) => Some(tail),
+            Final(_) => None
+        };
+        Some(node.head())
+    }
+}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Iter { node: self.node }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+// Takes the items of a list, from the head to the end. Items of nodes that no other list shares
+// are moved out; the others are cloned.
+pub struct IntoIter<T> {
+    list: List<T>
+}
+
+impl<T> Iterator for IntoIter<T> where T: Clone {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let node_ref = self.list.root.take()?;
+        let (item, tail) = match Rc::try_unwrap(node_ref) {
+            Ok(NonFinal(item, tail)) => (item, Some(tail)),
+            Ok(Final(item)) => (item, None),
+            Err(node_ref) => match node_ref.as_ref() {
+                NonFinal(item, tail) => (item.clone(), Some(tail.clone())),
+                Final(item) => (item.clone(), None)
+            }
+        };
+        self.list.root = tail;
+        Some(item)
+    }
+}
+
+impl<T> IntoIterator for List<T> where T: Clone {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { list: self }
+    }
+}
+
+#[cfg(test)]
+mod tests;
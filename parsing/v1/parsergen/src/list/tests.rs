@@ -0,0 +1,151 @@
+use crate::list::*;
+use crate::rng::Rng;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn test_iter_list() {
+    let list = List::cons(1, List::cons(2, List::cons(4, List::EMPTY)));
+    assert_eq!(list.iter().copied().collect::<Vec<_>>(), [1, 2, 4]);
+    assert_eq!(list.len(), 3);
+    assert_eq!(list.to_vec(), [1, 2, 4]);
+    assert_eq!(list.rev().to_vec(), [4, 2, 1]);
+    assert_eq!(list.clone().reverse_into_vec(), [4, 2, 1]);
+    assert_eq!(format!("{:?}", list), "[1, 2, 4]");
+
+    let mut sum = 0;
+    for item in &list {
+        sum += item;
+    }
+    assert_eq!(sum, 7);
+
+    let empty: List<i32> = List::EMPTY;
+    assert_eq!(empty.len(), 0);
+    assert_eq!(empty.iter().next(), None);
+    assert_eq!(format!("{:?}", empty), "[]");
+}
+
+#[test]
+fn test_collect_list() {
+    let list: List<i32> = (1..=4).collect();
+    assert_eq!(list.to_vec(), [1, 2, 3, 4]);
+
+    // Extending prepends each item in turn.
+    let mut extended = list.clone();
+    extended.extend([5, 6]);
+    assert_eq!(extended.to_vec(), [6, 5, 1, 2, 3, 4]);
+    assert_eq!(list.len(), 4);
+
+    // Items are moved out where no other list shares them, and cloned where one does.
+    let shared: List<String> = ["a", "b"].iter().map(|s| s.to_string()).collect();
+    let longer = List::cons("c".to_string(), shared.clone());
+    assert_eq!(longer.into_iter().collect::<Vec<_>>(), ["c", "a", "b"]);
+    assert_eq!(shared.into_iter().collect::<Vec<_>>(), ["a", "b"]);
+}
+
+#[test]
+fn test_compare_list() {
+    let list: List<i32> = (1..=3).collect();
+    let same = List::cons(1, List::cons(2, List::cons(3, List::EMPTY)));
+    let tail = List::cons(2, List::cons(3, List::EMPTY));
+    assert_eq!(list, same);
+    assert_eq!(list, list.clone());
+    assert_ne!(list, tail);
+    assert_ne!(list, List::EMPTY);
+    assert_eq!(hash_of(&list), hash_of(&same));
+    assert_ne!(hash_of(&list), hash_of(&tail));
+}
+
+#[test]
+fn test_long_list() {
+    // Long lists are compared and dropped without recursing, which would overflow the stack.
+    let list: List<u32> = (0..2_000_000).collect();
+    let same: List<u32> = (0..2_000_000).collect();
+    let other = List::cons(1, list.clone());
+    assert_eq!(list, same);
+    assert_ne!(list, other);
+    assert_eq!(other.len(), 2_000_001);
+    drop(list);
+    drop(same);
+    assert_eq!(other.iter().nth(1), Some(&0));
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn test_send_list() {
+    let list: List<u32> = (1..=4).collect();
+    let shared = list.clone();
+    let sum = std::thread::spawn(move || shared.iter().sum::<u32>()).join().unwrap();
+    assert_eq!(sum, 10);
+    assert_eq!(list.len(), 4);
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn test_drop_shared_long_list() {
+    // Two threads drop the last two handles to a long list at once. Whichever drops last frees the
+    // nodes, without recursing.
+    for _ in 0..5 {
+        let list: List<u32> = (0..1_000_000).collect();
+        let barrier = std::sync::Barrier::new(2);
+        std::thread::scope(|scope| {
+            for handle in [list.clone(), list] {
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    barrier.wait();
+                    drop(handle);
+                });
+            }
+        });
+    }
+}
+
+#[test]
+fn test_list_properties() {
+    let mut rng = Rng::new(0);
+    for _ in 0..500 {
+        let model: Vec<u32> = (0..rng.below(30)).map(|_| rng.below(10) as u32).collect();
+        let list: List<u32> = model.iter().copied().collect();
+
+        // The items come back out in order, whichever way they are taken out.
+        assert_eq!(list.len(), model.len());
+        assert_eq!(list.is_empty(), model.is_empty());
+        assert_eq!(list.to_vec(), model);
+        assert_eq!(list.clone().into_iter().collect::<Vec<_>>(), model);
+        assert_eq!(list.rev().rev().to_vec(), model);
+        assert_eq!(list.rev().to_vec(), model.iter().rev().copied().collect::<Vec<_>>());
+        assert_eq!(list.clone().reverse_into_vec(), model.iter().rev().copied().collect::<Vec<_>>());
+        assert_eq!(format!("{:?}", list), format!("{:?}", model));
+
+        // Walking the list with state visits every item once, and ends exactly at the end.
+        let mut rest = list.clone();
+        for item in &model {
+            rest = match rest.state() {
+                NonEmptyList(head, tail) => {
+                    assert_eq!(head, item);
+                    tail
+                },
+                EmptyList => panic!("the list ended early")
+            };
+        }
+        assert!(rest.is_empty());
+
+        // Consing an item and taking it off again gives back an equal list.
+        let item = rng.below(10) as u32;
+        match List::cons(item, list.clone()).state() {
+            NonEmptyList(&head, tail) => {
+                assert_eq!(head, item);
+                assert_eq!(tail, list);
+                assert_eq!(hash_of(&tail), hash_of(&list));
+            },
+            EmptyList => panic!()
+        }
+    }
+}
@@ -0,0 +1,672 @@
+// lr.rs
+//
+// parsergen lr <grammar.pglsf> [--canonical]
+//
+// A bottom-up parser for grammars that do not need the ParseMachine's branching. A compiled Program
+// is read as a context-free grammar, with a production for each way a rule can expand, and an
+// LALR(1) table is built for it, or with --canonical a canonical LR(1) table, which has more states
+// but accepts every LR(1) grammar. Grammars with shift/reduce or reduce/reduce
+// conflicts get no table; the conflicts are reported instead, each with an input that reaches it.
+//
+// An LrMachine reads symbols the way a ParseMachine does and returns the same ReadResults, so both
+// are Parsers and code written against one works with the other. The symbols returned for an
+// accepted input are exactly those the ParseMachine would return, Call, Exit and choice nodes
+// included, so that trees, coverage and actions come out the same. They are only returned once the
+// input is accepted, as a bottom-up parser does not know which rules it is in until then.
+//
+// Each production remembers the nodes it went through, and they are put back in the returned
+// symbols when it is reduced. The nodes themselves are not symbols of the LR grammar, or the parser
+// would have to decide which of them it is in before reading what tells them apart.
+//
+
+use std::collections::HashMap;
+use std::process::ExitCode;
+
+use crate::chain::Chain;
+use crate::grammar::lookahead::{children, first_sets, SymbolSet};
+use crate::grammar::program::{Node, NodeId, Program, ProgramRule, ProgramSymbolOrRule};
+use crate::grammar::{self, Span};
+use crate::parse_machine::{ParseRule, Parser, ProcessResult, ReadResult, RejectReason, SymbolOrRule};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LrMode {
+    Lalr,
+    Canonical
+}
+
+// A symbol on the right-hand side of a production: an input symbol, or a node to be reduced.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Item {
+    Symbol(u32),
+    Node(NodeId)
+}
+
+// What goes into the returned symbols for a production, in order.
+#[derive(Copy, Clone, Debug)]
+enum Part {
+    // The item of the right-hand side with the given index.
+    Item(usize),
+
+    // A node that matches nothing.
+    Mark(NodeId)
+}
+
+#[derive(Clone, Debug)]
+struct Production {
+    // The node the production reduces to, or None for the start production.
+    node: Option<NodeId>,
+    items: Vec<Item>,
+    parts: Vec<Part>
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Action {
+    Error,
+    Shift(usize),
+    Reduce(usize),
+    Accept
+}
+
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    // "shift/reduce" or "reduce/reduce".
+    pub kind: &'static str,
+
+    // The next symbol, or None for the end of the input.
+    pub symbol: Option<u32>,
+
+    // The nodes that could be reduced, the first one being where the conflict is reported.
+    pub nodes: Vec<NodeId>,
+
+    // Symbols that lead to the conflict, just before reading symbol.
+    pub example: Vec<u32>
+}
+
+impl Conflict {
+    // E.g. "shift/reduce conflict in rule 'item' on C, e.g. after B".
+    pub fn describe(&self, program: &Program) -> String {
+        let rule = rule_of(program, self.nodes[0]).map_or(String::new(), |rule| format!(" in rule '{}'", program.rules()[rule].name));
+        let symbol = self.symbol.map_or("the end of the input".to_string(), |symbol| program.symbol_name(symbol));
+        let example = match self.example.is_empty() {
+            true => "at the start".to_string(),
+            false => format!("after {}", self.example.iter().map(|&symbol| program.symbol_name(symbol)).collect::<Vec<_>>().join(" "))
+        };
+        format!("{} conflict{} on {}, e.g. {}", self.kind, rule, symbol, example)
+    }
+
+    pub fn span(&self, program: &Program) -> Span {
+        program.span(self.nodes[0])
+    }
+
+    // The rules of the nodes that could be reduced, each listed once.
+    pub fn rules(&self, program: &Program) -> Vec<usize> {
+        let mut rules = Vec::new();
+        for rule in self.nodes.iter().filter_map(|&node| rule_of(program, node)) {
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+        rules
+    }
+}
+
+// The rule whose body the node is part of.
+fn rule_of(program: &Program, node: NodeId) -> Option<usize> {
+    program.rules().iter().position(|rule| {
+        let mut pending = vec![rule.body];
+        while let Some(other) = pending.pop() {
+            if other == node { return true }
+            if !matches!(program.nodes()[other as usize], Node::Repeat(_)) {
+                pending.extend(children(&program.nodes()[other as usize]));
+            }
+        }
+        false
+    })
+}
+
+pub struct LrTable<'a> {
+    program: &'a Program,
+    productions: Vec<Production>,
+
+    // For every state, the action for each symbol, the end of the input coming last.
+    actions: Vec<Vec<Action>>,
+
+    // For every state, the state reached by reducing to each node.
+    gotos: Vec<Vec<(NodeId, usize)>>
+}
+
+// The items of a state that are not there by closure, as (production, position) pairs, with the
+// symbols that may follow each.
+#[derive(Clone)]
+struct Kernel {
+    items: Vec<(usize, usize)>,
+    lookaheads: Vec<SymbolSet>
+}
+
+impl<'a> LrTable<'a> {
+    pub fn build(program: &'a Program, mode: LrMode) -> Result<Self, Vec<Conflict>> {
+        let end = program.symbol_count();
+        let productions = productions(program);
+        let bodies: Vec<NodeId> = program.rules().iter().map(|rule| rule.body).collect();
+        let (first, nullable) = first_sets(program.nodes(), &bodies);
+
+        let mut by_node: Vec<Vec<usize>> = vec![Vec::new(); program.nodes().len()];
+        for (i, production) in productions.iter().enumerate() {
+            if let Some(node) = production.node {
+                by_node[node as usize].push(i);
+            }
+        }
+
+        // Adds to a kernel the items it implies, with their lookaheads.
+        let closure = |kernel: &Kernel| -> Vec<((usize, usize), SymbolSet)> {
+            let mut items: Vec<((usize, usize), SymbolSet)> = kernel.items.iter().copied().zip(kernel.lookaheads.iter().cloned()).collect();
+            let mut pending: Vec<usize> = (0..items.len()).collect();
+            while let Some(i) = pending.pop() {
+                let ((production, position), _) = items[i];
+                let Some(&Item::Node(node)) = productions[production].items.get(position) else { continue };
+
+                // What may follow the node: the rest of the production, and what follows it.
+                let mut after = SymbolSet::default();
+                let mut rest_nullable = true;
+                for item in &productions[production].items[position + 1..] {
+                    match *item {
+                        Item::Symbol(symbol) => {
+                            after.insert(symbol);
+                            rest_nullable = false;
+                        },
+                        Item::Node(node) => {
+                            after.extend(&first[node as usize]);
+                            rest_nullable = nullable[node as usize];
+                        }
+                    }
+                    if !rest_nullable { break }
+                }
+                if rest_nullable {
+                    let lookahead = items[i].1.clone();
+                    after.extend(&lookahead);
+                }
+
+                for &other in &by_node[node as usize] {
+                    match items.iter().position(|(item, _)| *item == (other, 0)) {
+                        Some(j) => if items[j].1.extend(&after) {
+                            pending.push(j);
+                        },
+                        None => {
+                            items.push(((other, 0), after.clone()));
+                            pending.push(items.len() - 1);
+                        }
+                    }
+                }
+            }
+            items
+        };
+
+        let mut start_lookahead = SymbolSet::default();
+        start_lookahead.insert(end);
+        let mut kernels = vec![Kernel { items: vec![(0, 0)], lookaheads: vec![start_lookahead] }];
+        let mut transitions: Vec<Vec<(Item, usize)>> = vec![Vec::new()];
+        let mut pending = vec![0];
+
+        // The states by their items, and for canonical LR(1) their lookaheads too.
+        let key = |kernel: &Kernel| match mode {
+            LrMode::Lalr => (kernel.items.clone(), Vec::new()),
+            LrMode::Canonical => (kernel.items.clone(), kernel.lookaheads.clone())
+        };
+        let mut states = HashMap::from([(key(&kernels[0]), 0)]);
+
+        while let Some(state) = pending.pop() {
+            // The kernels reached by moving past each item.
+            let mut targets: Vec<(Item, Kernel)> = Vec::new();
+            for ((production, position), lookahead) in closure(&kernels[state]) {
+                let Some(&item) = productions[production].items.get(position) else { continue };
+                let target = match targets.iter().position(|(other, _)| *other == item) {
+                    Some(i) => &mut targets[i].1,
+                    None => {
+                        targets.push((item, Kernel { items: Vec::new(), lookaheads: Vec::new() }));
+                        &mut targets.last_mut().unwrap().1
+                    }
+                };
+                match target.items.iter().position(|&other| other == (production, position + 1)) {
+                    Some(i) => {
+                        target.lookaheads[i].extend(&lookahead);
+                    },
+                    None => {
+                        target.items.push((production, position + 1));
+                        target.lookaheads.push(lookahead);
+                    }
+                }
+            }
+
+            transitions[state].clear();
+            for (item, mut kernel) in targets {
+                // Kernels are compared in a fixed order.
+                let mut order: Vec<usize> = (0..kernel.items.len()).collect();
+                order.sort_by_key(|&i| kernel.items[i]);
+                kernel = Kernel {
+                    items: order.iter().map(|&i| kernel.items[i]).collect(),
+                    lookaheads: order.iter().map(|&i| kernel.lookaheads[i].clone()).collect()
+                };
+
+                let target = match states.get(&key(&kernel)).copied() {
+                    // LALR merges states with the same items, so the merged state has to be
+                    // looked at again if it gained lookaheads.
+                    Some(i) => {
+                        let mut changed = false;
+                        for (lookahead, new) in kernels[i].lookaheads.iter_mut().zip(&kernel.lookaheads) {
+                            changed |= lookahead.extend(new);
+                        }
+                        if changed && !pending.contains(&i) {
+                            pending.push(i);
+                        }
+                        i
+                    },
+                    None => {
+                        states.insert(key(&kernel), kernels.len());
+                        kernels.push(kernel);
+                        transitions.push(Vec::new());
+                        pending.push(kernels.len() - 1);
+                        kernels.len() - 1
+                    }
+                };
+                transitions[state].push((item, target));
+            }
+        }
+
+        // Fill in the tables, collecting the conflicts.
+        let mut actions = vec![vec![Action::Error; end as usize + 1]; kernels.len()];
+        let mut gotos = vec![Vec::new(); kernels.len()];
+        let mut conflicts: Vec<(usize, u32, Vec<usize>, &'static str)> = Vec::new();
+        for (state, kernel) in kernels.iter().enumerate() {
+            for &(item, target) in &transitions[state] {
+                match item {
+                    Item::Symbol(symbol) => actions[state][symbol as usize] = Action::Shift(target),
+                    Item::Node(node) => gotos[state].push((node, target))
+                }
+            }
+
+            for ((production, position), lookahead) in closure(kernel) {
+                if position < productions[production].items.len() { continue }
+                for symbol in lookahead.iter() {
+                    let action = match production {
+                        0 => Action::Accept,
+                        _ => Action::Reduce(production)
+                    };
+                    match actions[state][symbol as usize] {
+                        Action::Error => actions[state][symbol as usize] = action,
+                        Action::Shift(_) => conflicts.push((state, symbol, vec![production], "shift/reduce")),
+                        Action::Reduce(other) if other != production => {
+                            match conflicts.iter_mut().find(|conflict| (conflict.0, conflict.1, conflict.3) == (state, symbol, "reduce/reduce")) {
+                                Some(conflict) => conflict.2.push(production),
+                                None => conflicts.push((state, symbol, vec![other, production], "reduce/reduce"))
+                            }
+                        },
+                        _ => ()
+                    }
+                }
+            }
+        }
+
+        if conflicts.is_empty() {
+            return Ok(LrTable { program, productions, actions, gotos })
+        }
+
+        // The shortest input that reaches each state, found by a breadth first search.
+        let shortest = shortest_inputs(program, &productions);
+        let mut examples: Vec<Option<Vec<u32>>> = vec![None; kernels.len()];
+        examples[0] = Some(Vec::new());
+        let mut queue = std::collections::VecDeque::from([0]);
+        while let Some(state) = queue.pop_front() {
+            for &(item, target) in &transitions[state] {
+                if examples[target].is_none() {
+                    let mut example = examples[state].clone().unwrap();
+                    match item {
+                        Item::Symbol(symbol) => example.push(symbol),
+                        Item::Node(node) => example.extend(shortest[node as usize].clone().unwrap_or_default())
+                    }
+                    examples[target] = Some(example);
+                    queue.push_back(target);
+                }
+            }
+        }
+
+        Err(conflicts.into_iter()
+            .map(|(state, symbol, reductions, kind)| Conflict {
+                kind,
+                symbol: if symbol == end { None } else { Some(symbol) },
+                nodes: reductions.iter().filter_map(|&production| productions[production].node).collect(),
+                example: examples[state].clone().unwrap_or_default()
+            })
+            .collect())
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn machine(&self) -> LrMachine<'_, 'a> {
+        LrMachine { table: self, stack: vec![(0, None)], values: Vec::new(), terminal: false }
+    }
+
+    fn goto(&self, state: usize, node: NodeId) -> usize {
+        self.gotos[state].iter().find(|(other, _)| *other == node).unwrap().1
+    }
+}
+
+// A rule body whose sequences and unions would expand to more ways than this is split up, with its
+// parts reduced on their own. A sequence of ten optionals would otherwise make a thousand ways.
+const MAX_WAYS: usize = 64;
+
+// What a node matches, in order, in one way it can expand.
+#[derive(Copy, Clone, Debug)]
+enum Element {
+    Mark(NodeId),
+    Symbol(u32),
+
+    // A node with productions of its own.
+    Node(NodeId)
+}
+
+// The productions of the program. Rule bodies and repetitions get productions of their own, and the
+// sequences, unions and optionals inside them are multiplied out into one production per way, so
+// that the choice between them is made as late as possible: when the production is reduced.
+fn productions(program: &Program) -> Vec<Production> {
+    let nodes = program.nodes();
+    let root = &program.rules()[program.root_index()];
+    let mut result = vec![Production {
+        node: None,
+        items: vec![Item::Node(root.body)],
+        parts: vec![Part::Mark(root.call), Part::Item(0), Part::Mark(root.exit)]
+    }];
+
+    let mut done = vec![false; nodes.len()];
+    let mut pending = vec![root.body];
+    while let Some(node) = pending.pop() {
+        if std::mem::replace(&mut done[node as usize], true) { continue }
+
+        let ways = match &nodes[node as usize] {
+            Node::Repeat(body) => {
+                let mut ways: Vec<Vec<Element>> = ways(program, *body, &mut pending).into_iter()
+                    .map(|way| [vec![Element::Mark(node)], way, vec![Element::Node(node)]].concat())
+                    .collect();
+                ways.push(vec![Element::Mark(node)]);
+                ways
+            },
+            _ => ways(program, node, &mut pending)
+        };
+
+        for way in ways {
+            let mut production = Production { node: Some(node), items: Vec::new(), parts: Vec::new() };
+            for element in way {
+                match element {
+                    Element::Mark(other) => production.parts.push(Part::Mark(other)),
+                    Element::Symbol(symbol) => {
+                        production.parts.push(Part::Item(production.items.len()));
+                        production.items.push(Item::Symbol(symbol));
+                    },
+                    Element::Node(other) => {
+                        production.parts.push(Part::Item(production.items.len()));
+                        production.items.push(Item::Node(other));
+                    }
+                }
+            }
+            result.push(production);
+        }
+    }
+    result
+}
+
+// The ways a node can expand. Nodes that get productions of their own are added to pending.
+fn ways(program: &Program, node: NodeId, pending: &mut Vec<NodeId>) -> Vec<Vec<Element>> {
+    // The ways of a child, or the child itself if it has too many.
+    fn child(program: &Program, child: NodeId, pending: &mut Vec<NodeId>) -> Vec<Vec<Element>> {
+        match ways(program, child, pending) {
+            ways if ways.len() > MAX_WAYS => {
+                pending.push(child);
+                vec![vec![Element::Node(child)]]
+            },
+            ways => ways
+        }
+    }
+    let mark = Element::Mark(node);
+
+    match &program.nodes()[node as usize] {
+        Node::Exit(_) | Node::Label(_) | Node::Skip => vec![vec![mark]],
+        Node::Symbol(symbol) => vec![vec![mark, Element::Symbol(*symbol)]],
+        Node::Call(rule) => {
+            let rule = &program.rules()[*rule];
+            pending.push(rule.body);
+            vec![vec![mark, Element::Node(rule.body), Element::Mark(rule.exit)]]
+        },
+        Node::Seq(items) => {
+            let mut ways = vec![vec![mark]];
+            for &item in items {
+                let item_ways = child(program, item, pending);
+                ways = ways.iter()
+                    .flat_map(|way| item_ways.iter().map(move |item_way| [way.clone(), item_way.clone()].concat()))
+                    .collect();
+                if ways.len() > MAX_WAYS { break }
+            }
+            ways
+        },
+        Node::Union(alternatives) | Node::Range(alternatives) => alternatives.iter()
+            .flat_map(|&alternative| child(program, alternative, pending))
+            .map(|way| [vec![mark], way].concat())
+            .collect(),
+        Node::Opt(body, skip) | Node::Star(body, skip) => child(program, *body, pending).into_iter()
+            .chain([vec![Element::Mark(*skip)]])
+            .map(|way| [vec![mark], way].concat())
+            .collect(),
+        Node::Plus(body, repeat) => {
+            pending.push(*repeat);
+            child(program, *body, pending).into_iter()
+                .map(|way| [vec![mark], way, vec![Element::Node(*repeat)]].concat())
+                .collect()
+        },
+        Node::Repeat(_) => {
+            pending.push(node);
+            vec![vec![Element::Node(node)]]
+        }
+    }
+}
+
+// For every node, one of the shortest inputs it matches, if it matches any.
+fn shortest_inputs(program: &Program, productions: &[Production]) -> Vec<Option<Vec<u32>>> {
+    let mut shortest: Vec<Option<Vec<u32>>> = vec![None; program.nodes().len()];
+    loop {
+        let mut changed = false;
+        for production in productions {
+            let Some(node) = production.node else { continue };
+            let mut input = Vec::new();
+            let complete = production.items.iter().all(|item| match *item {
+                Item::Symbol(symbol) => {
+                    input.push(symbol);
+                    true
+                },
+                Item::Node(other) => match &shortest[other as usize] {
+                    Some(other) => {
+                        input.extend(other);
+                        true
+                    },
+                    None => false
+                }
+            });
+            if complete && shortest[node as usize].as_ref().is_none_or(|other| input.len() < other.len()) {
+                shortest[node as usize] = Some(input);
+                changed = true;
+            }
+        }
+
+        if !changed { return shortest }
+    }
+}
+
+// A symbol that was read, or a production that was reduced along with the values of its items.
+enum Value {
+    Symbol(u32),
+    Reduced(usize, Vec<usize>)
+}
+
+pub struct LrMachine<'t, 'a> {
+    table: &'t LrTable<'a>,
+
+    // The states entered, each with the value that entered it.
+    stack: Vec<(usize, Option<usize>)>,
+    values: Vec<Value>,
+    terminal: bool
+}
+
+impl<'a> LrMachine<'_, 'a> {
+    // Takes the actions for the symbol, or for the end of the input, until it is shifted or
+    // accepted. Returns false on an error.
+    fn step(&mut self, symbol: u32) -> Result<Option<usize>, ()> {
+        loop {
+            let state = self.stack.last().unwrap().0;
+            match self.table.actions[state][symbol as usize] {
+                Action::Shift(target) => {
+                    self.values.push(Value::Symbol(symbol));
+                    self.stack.push((target, Some(self.values.len() - 1)));
+                    return Ok(None)
+                },
+                Action::Reduce(production) => {
+                    let count = self.table.productions[production].items.len();
+                    let items = self.stack.split_off(self.stack.len() - count).into_iter().map(|(_, value)| value.unwrap()).collect();
+                    self.values.push(Value::Reduced(production, items));
+
+                    let node = self.table.productions[production].node.unwrap();
+                    let target = self.table.goto(self.stack.last().unwrap().0, node);
+                    self.stack.push((target, Some(self.values.len() - 1)));
+                },
+                Action::Accept => {
+                    let root = self.stack.last().unwrap().1.unwrap();
+                    self.values.push(Value::Reduced(0, vec![root]));
+                    return Ok(Some(self.values.len() - 1))
+                },
+                Action::Error => return Err(())
+            }
+        }
+    }
+
+    // The symbols a ParseMachine would have returned for the value, in order.
+    fn symbols(&self, root: usize) -> Chain<ProgramSymbolOrRule<'a>> {
+        enum Work {
+            Value(usize),
+            Node(NodeId)
+        }
+
+        let program = self.table.program;
+        let mut result = Vec::new();
+        let mut pending = vec![Work::Value(root)];
+        while let Some(work) = pending.pop() {
+            let value = match work {
+                Work::Value(value) => value,
+                Work::Node(node) => {
+                    let rule = program.node_rule(node);
+                    if rule.is_recorded() {
+                        result.push(SymbolOrRule::Rule(rule));
+                    }
+                    continue
+                }
+            };
+
+            match &self.values[value] {
+                Value::Symbol(symbol) => result.push(SymbolOrRule::Symbol(*symbol)),
+                Value::Reduced(production, items) => {
+                    let production = &self.table.productions[*production];
+                    for part in production.parts.iter().rev() {
+                        pending.push(match *part {
+                            Part::Item(i) => Work::Value(items[i]),
+                            Part::Mark(node) => Work::Node(node)
+                        });
+                    }
+                }
+            }
+        }
+        result.into_iter().collect()
+    }
+
+    fn reject(&mut self) -> ReadResult<u32, ProgramRule<'a>> {
+        self.terminal = true;
+        ReadResult::Rejected { reason: RejectReason::Error }
+    }
+}
+
+impl<'a> Parser<u32, ProgramRule<'a>> for LrMachine<'_, 'a> {
+    fn read(&mut self, input: u32) -> ReadResult<u32, ProgramRule<'a>> {
+        if self.terminal {
+            return ReadResult::Rejected { reason: RejectReason::AlreadyTerminal }
+        }
+        if self.step(input).is_err() {
+            return self.reject()
+        }
+
+        // Like the ParseMachine, accept as soon as nothing but the end of the input can follow.
+        let state = self.stack.last().unwrap().0;
+        let end = self.table.program.symbol_count() as usize;
+        match self.table.actions[state][..end].iter().all(|&action| action == Action::Error) {
+            true => self.finish(),
+            false => ReadResult::Processed { result: ProcessResult::Awaiting, symbols: Chain::EMPTY }
+        }
+    }
+
+    fn finish(&mut self) -> ReadResult<u32, ProgramRule<'a>> {
+        if self.terminal {
+            return ReadResult::Rejected { reason: RejectReason::AlreadyTerminal }
+        }
+        match self.step(self.table.program.symbol_count()) {
+            Ok(Some(root)) => {
+                self.terminal = true;
+                ReadResult::Processed { result: ProcessResult::Accepted, symbols: self.symbols(root) }
+            },
+            _ => self.reject()
+        }
+    }
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let (grammar_path, mode) = match args {
+        [grammar_path] => (grammar_path.as_str(), LrMode::Lalr),
+        [grammar_path, flag] if flag == "--canonical" => (grammar_path.as_str(), LrMode::Canonical),
+        _ => {
+            eprintln!("usage: parsergen lr <grammar.pglsf> [--canonical]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let (grammar, source) = match grammar::load(grammar_path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let program = match Program::compile(&grammar) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}", error.describe(grammar_path, &source));
+            return ExitCode::FAILURE
+        }
+    };
+
+    let kind = match mode {
+        LrMode::Lalr => "LALR(1)",
+        LrMode::Canonical => "LR(1)"
+    };
+    match LrTable::build(&program, mode) {
+        Ok(table) => {
+            println!("{}: {} with {} states", grammar_path, kind, table.state_count());
+            ExitCode::SUCCESS
+        },
+        Err(conflicts) => {
+            for conflict in &conflicts {
+                let (line, col) = conflict.span(&program).line_col(&source);
+                eprintln!("{}:{}:{}: {}", grammar_path, line, col, conflict.describe(&program));
+            }
+            eprintln!("{}: not {}, {} conflicts", grammar_path, kind, conflicts.len());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
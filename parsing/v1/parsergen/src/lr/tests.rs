@@ -0,0 +1,74 @@
+use crate::lr::*;
+use crate::grammar::read;
+use crate::tester::{input_symbols, parse, parse_with, Outcome};
+
+const GRAMMAR: &str = "\
+symbols A, B, C, D;
+grammar
+	root = *pair ?last:C;
+	pair = first:A (B | item) +D;
+	@inline item = C ?C;
+	@hidden extra = D;
+";
+
+// The symbols returned for an input, written out so that they can be compared.
+fn written(program: &Program, parsed: Result<Vec<ProgramSymbolOrRule>, Outcome>) -> Result<Vec<String>, String> {
+    match parsed {
+        Ok(parsed) => Ok(parsed.iter()
+            .map(|item| match item {
+                SymbolOrRule::Symbol(symbol) => program.symbol_name(*symbol),
+                SymbolOrRule::Rule(rule) => format!("#{}", rule.node())
+            })
+            .collect()),
+        Err(outcome) => Err(outcome.to_string())
+    }
+}
+
+#[test]
+fn test_same_symbols_as_parse_machine() {
+    let program = Program::compile(&read(GRAMMAR).unwrap()).unwrap();
+    for mode in [LrMode::Lalr, LrMode::Canonical] {
+        let table = LrTable::build(&program, mode).unwrap();
+        for input in ["", "C", "A B D", "A C D D A C C D C", "A B", "A B D B", "A D", "C C"] {
+            let input = input_symbols(&program, input).unwrap();
+            assert_eq!(written(&program, parse_with(table.machine(), &input)), written(&program, parse(&program, &input)));
+        }
+    }
+}
+
+#[test]
+fn test_accepts_before_the_end() {
+    let program = Program::compile(&read("symbols A, B;\ngrammar\n\troot = A B;\n").unwrap()).unwrap();
+    let table = LrTable::build(&program, LrMode::Lalr).unwrap();
+    let mut machine = table.machine();
+    assert!(matches!(machine.read(0), ReadResult::Processed { result: ProcessResult::Awaiting, .. }));
+    assert!(matches!(machine.read(1), ReadResult::Processed { result: ProcessResult::Accepted, .. }));
+    assert!(matches!(machine.read(1), ReadResult::Rejected { reason: RejectReason::AlreadyTerminal }));
+}
+
+#[test]
+fn test_conflicts() {
+    let program = Program::compile(&read("symbols A, B;\ngrammar\n\troot = B *A *A;\n").unwrap()).unwrap();
+    let conflicts = LrTable::build(&program, LrMode::Lalr).err().unwrap();
+    let described: Vec<String> = conflicts.iter().map(|conflict| conflict.describe(&program)).collect();
+    assert_eq!(described[..2], [
+        "shift/reduce conflict in rule 'root' on A, e.g. after B A",
+        "reduce/reduce conflict in rule 'root' on the end of the input, e.g. after B A"
+    ]);
+}
+
+#[test]
+fn test_canonical() {
+    // LR(1), but merging the states after A E and B E makes e and f clash.
+    let source = "\
+symbols A, B, C, D, E;
+grammar
+	root = A e C | A f D | B f C | B e D;
+	e = E;
+	f = E;
+";
+    let program = Program::compile(&read(source).unwrap()).unwrap();
+    let conflicts = LrTable::build(&program, LrMode::Lalr).err().unwrap();
+    assert_eq!(conflicts[0].kind, "reduce/reduce");
+    assert!(LrTable::build(&program, LrMode::Canonical).is_ok());
+}
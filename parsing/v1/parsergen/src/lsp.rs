@@ -0,0 +1,458 @@
+// lsp.rs
+//
+// parsergen-lsp
+//
+// A Language Server Protocol server for .pglsf files, speaking JSON-RPC over stdin and stdout. It
+// offers:
+// - diagnostics: errors from reading and checking a grammar and the files it imports, and a
+//   warning when a ParseMachine cannot run it, e.g. because it is left-recursive,
+// - go to definition and find references, for rules and symbols,
+// - rename of rules and symbols,
+// - hover, showing the expansion of a rule,
+// - completion of the declared symbols and the rules.
+// Documents are synchronized in full on every change.
+//
+
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::formatter::{expr_text, rule_head};
+use crate::grammar::program::Program;
+use crate::grammar::{self, Expr, ExprKind, Grammar, Span, Symbols};
+use crate::json::Json;
+
+// Error codes from JSON-RPC and LSP.
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const REQUEST_FAILED: i32 = -32803;
+
+// Severities of diagnostics.
+const ERROR: usize = 1;
+const WARNING: usize = 2;
+
+// Kinds of completion items.
+const FUNCTION: usize = 3;
+const CONSTANT: usize = 21;
+
+// The LSP position of a byte offset: a 0-based line, and a column counted in UTF-16 code units.
+pub fn position(source: &str, offset: usize) -> Json {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line = before.matches('\n').count();
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    Json::object([("line", line.into()), ("character", character.into())])
+}
+
+// The byte offset of an LSP position. A position past the end of its line is taken as the end of
+// the line.
+pub fn offset(source: &str, position: &Json) -> Option<usize> {
+    let line = position.get("line").as_u64()? as usize;
+    let character = position.get("character").as_u64()? as usize;
+    let line_start = match line {
+        0 => 0,
+        line => source.match_indices('\n').nth(line - 1)?.0 + 1
+    };
+
+    let mut units = 0;
+    for (i, c) in source[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + i)
+        }
+        units += c.len_utf16();
+    }
+    Some(source.len())
+}
+
+fn range(source: &str, span: Span) -> Json {
+    Json::object([("start", position(source, span.start)), ("end", position(source, span.end))])
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NameKind {
+    Rule,
+    Symbol
+}
+
+// A place where a rule or a symbol is named.
+#[derive(Clone, Debug)]
+pub struct Occurrence {
+    pub name: String,
+    pub kind: NameKind,
+    pub span: Span,
+
+    // Whether this is where the rule is defined or the symbol declared.
+    pub definition: bool
+}
+
+// Every place a rule or a symbol is named in the grammar, in no particular order. The parameters of
+// templates are neither.
+pub fn occurrences(grammar: &Grammar) -> Vec<Occurrence> {
+    fn collect(expr: &Expr, params: &[String], result: &mut Vec<Occurrence>) {
+        match &expr.kind {
+            ExprKind::Symbol(name) => result.push(Occurrence { name: name.clone(), kind: NameKind::Symbol, span: expr.span, definition: false }),
+            ExprKind::Rule(name) if params.contains(name) => (),
+            ExprKind::Rule(name) => result.push(Occurrence { name: name.clone(), kind: NameKind::Rule, span: expr.span, definition: false }),
+            ExprKind::Call(name, args) => {
+                let span = Span::new(expr.span.start, expr.span.start + name.len());
+                result.push(Occurrence { name: name.clone(), kind: NameKind::Rule, span, definition: false });
+                for arg in args {
+                    collect(arg, params, result);
+                }
+            },
+            ExprKind::Byte(_) => (),
+            ExprKind::Range(first, last) => {
+                collect(first, params, result);
+                collect(last, params, result);
+            },
+            ExprKind::Seq(items) | ExprKind::Union(items) => for item in items {
+                collect(item, params, result);
+            },
+            ExprKind::Opt(inner) | ExprKind::Star(inner) | ExprKind::Plus(inner) => collect(inner, params, result)
+        }
+    }
+
+    let mut result = Vec::new();
+    if let Symbols::Named(decls) = &grammar.symbols {
+        for decl in decls {
+            result.push(Occurrence { name: decl.name.clone(), kind: NameKind::Symbol, span: decl.span, definition: true });
+        }
+    }
+    if let Some(skip) = &grammar.skip {
+        result.push(Occurrence { name: skip.rule.clone(), kind: NameKind::Rule, span: skip.name_span, definition: false });
+    }
+    for rule in &grammar.rules {
+        result.push(Occurrence { name: rule.name.clone(), kind: NameKind::Rule, span: rule.name_span, definition: true });
+        collect(&rule.expr, &rule.params, &mut result);
+    }
+    result
+}
+
+// The diagnostics for the source of a grammar. Imports are resolved if the path of its file is
+// known.
+pub fn diagnostics(path: Option<&Path>, source: &str) -> Vec<Json> {
+    let diagnostic = |span, severity: usize, message: String| Json::object([
+        ("range", range(source, span)),
+        ("severity", severity.into()),
+        ("source", "parsergen".into()),
+        ("message", message.into())
+    ]);
+
+    let grammar = match path {
+        Some(path) => grammar::read_from(path, source),
+        None => grammar::read(source)
+    };
+
+    match grammar {
+        Err(error) => vec![diagnostic(error.span, ERROR, error.message)],
+        Ok(grammar) => match Program::compile(&grammar) {
+            Err(error) => vec![diagnostic(error.span, WARNING, format!("{}, so a ParseMachine cannot run this grammar", error.message))],
+            Ok(_) => Vec::new()
+        }
+    }
+}
+
+fn is_name(name: &str, kind: NameKind) -> bool {
+    let first = match kind {
+        NameKind::Rule => name.starts_with(|c: char| c.is_ascii_lowercase()),
+        NameKind::Symbol => name.starts_with(|c: char| c.is_ascii_uppercase())
+    };
+    first && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+}
+
+fn response(id: &Json, result: Json) -> Json {
+    Json::object([("jsonrpc", "2.0".into()), ("id", id.clone()), ("result", result)])
+}
+
+fn error_response(id: &Json, code: i32, message: &str) -> Json {
+    let error = Json::object([("code", Json::Number(code as f64)), ("message", message.into())]);
+    Json::object([("jsonrpc", "2.0".into()), ("id", id.clone()), ("error", error)])
+}
+
+// The result of a request, or the code and message of its error.
+type RequestResult<T> = Result<T, (i32, String)>;
+
+// A document, read for a request about a position in it.
+struct Lookup<'a> {
+    text: &'a str,
+    grammar: Grammar,
+    occurrences: Vec<Occurrence>,
+
+    // The occurrence at the position, if any.
+    at: Option<usize>
+}
+
+#[derive(Default)]
+pub struct Server {
+    // The open documents, by URI.
+    documents: Vec<(String, String)>,
+
+    // Whether a shutdown request has been received.
+    pub shut_down: bool
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server::default()
+    }
+
+    fn text(&self, uri: &str) -> Option<&str> {
+        self.documents.iter().find(|(other, _)| other == uri).map(|(_, text)| text.as_str())
+    }
+
+    fn publish(&self, uri: &str) -> Json {
+        let path = uri.strip_prefix("file://").map(Path::new);
+        let diagnostics = self.text(uri).map_or(Vec::new(), |text| diagnostics(path, text));
+        notification("textDocument/publishDiagnostics", Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]))
+    }
+
+    // Handles a message from the client. Returns the messages to send back: the response to a
+    // request, and any notifications.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let id = message.get("id");
+        let params = message.get("params");
+        let method = message.get("method").as_str().unwrap_or("");
+        let uri = params.get("textDocument").get("uri").as_str().unwrap_or("").to_string();
+
+        let result = match method {
+            "initialize" => Ok(Json::object([
+                ("capabilities", Json::object([
+                    ("textDocumentSync", 1usize.into()),
+                    ("definitionProvider", true.into()),
+                    ("referencesProvider", true.into()),
+                    ("renameProvider", true.into()),
+                    ("hoverProvider", true.into()),
+                    ("completionProvider", Json::Object(Vec::new()))
+                ])),
+                ("serverInfo", Json::object([("name", "parsergen-lsp".into())]))
+            ])),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Json::Null)
+            },
+            "textDocument/didOpen" => {
+                let text = params.get("textDocument").get("text").as_str().unwrap_or("").to_string();
+                self.documents.retain(|(other, _)| *other != uri);
+                self.documents.push((uri.clone(), text));
+                return vec![self.publish(&uri)]
+            },
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges").as_array().unwrap_or(&[]);
+                if let Some(text) = changes.last().and_then(|change| change.get("text").as_str()) {
+                    match self.documents.iter_mut().find(|(other, _)| *other == uri) {
+                        Some((_, old)) => *old = text.to_string(),
+                        None => self.documents.push((uri.clone(), text.to_string()))
+                    }
+                }
+                return vec![self.publish(&uri)]
+            },
+            "textDocument/didClose" => {
+                self.documents.retain(|(other, _)| *other != uri);
+                return vec![self.publish(&uri)]
+            },
+            "textDocument/definition" => self.definition(&uri, params),
+            "textDocument/references" => self.references(&uri, params),
+            "textDocument/rename" => self.rename(&uri, params),
+            "textDocument/hover" => self.hover(&uri, params),
+            "textDocument/completion" => self.completion(&uri),
+            _ if id == &Json::Null => return Vec::new(),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method)))
+        };
+
+        // Notifications get no response.
+        if id == &Json::Null {
+            return Vec::new()
+        }
+
+        match result {
+            Ok(result) => vec![response(id, result)],
+            Err((code, message)) => vec![error_response(id, code, &message)]
+        }
+    }
+
+    // Reads the document for a request about the position in the parameters.
+    fn lookup(&self, uri: &str, params: &Json) -> RequestResult<Lookup<'_>> {
+        let text = self.text(uri).ok_or((INVALID_PARAMS, format!("'{}' is not open", uri)))?;
+        let offset = offset(text, params.get("position")).ok_or((INVALID_PARAMS, "invalid position".to_string()))?;
+
+        // Navigation only needs the grammar to be readable, not for its names to check out.
+        let grammar = grammar::parse(text).map_err(|error| (REQUEST_FAILED, error.message))?;
+        let occurrences = occurrences(&grammar);
+        let at = occurrences.iter().position(|occurrence| occurrence.span.start <= offset && offset <= occurrence.span.end);
+        Ok(Lookup { text, grammar, occurrences, at })
+    }
+
+    fn location(uri: &str, text: &str, span: Span) -> Json {
+        Json::object([("uri", uri.into()), ("range", range(text, span))])
+    }
+
+    fn definition(&self, uri: &str, params: &Json) -> RequestResult<Json> {
+        let Lookup { text, occurrences, at, .. } = match self.lookup(uri, params) {
+            Ok(found) => found,
+            Err((REQUEST_FAILED, _)) => return Ok(Json::Null),
+            Err(error) => return Err(error)
+        };
+
+        let Some(at) = at else { return Ok(Json::Null) };
+        Ok(occurrences.iter()
+            .find(|occurrence| occurrence.definition && occurrence.kind == occurrences[at].kind && occurrence.name == occurrences[at].name)
+            .map_or(Json::Null, |definition| Server::location(uri, text, definition.span)))
+    }
+
+    fn references(&self, uri: &str, params: &Json) -> RequestResult<Json> {
+        let Lookup { text, occurrences, at, .. } = match self.lookup(uri, params) {
+            Ok(found) => found,
+            Err((REQUEST_FAILED, _)) => return Ok(Json::Array(Vec::new())),
+            Err(error) => return Err(error)
+        };
+
+        let Some(at) = at else { return Ok(Json::Array(Vec::new())) };
+        let include_declaration = params.get("context").get("includeDeclaration").as_bool().unwrap_or(true);
+        let mut spans: Vec<Span> = occurrences.iter()
+            .filter(|occurrence| occurrence.kind == occurrences[at].kind && occurrence.name == occurrences[at].name)
+            .filter(|occurrence| include_declaration || !occurrence.definition)
+            .map(|occurrence| occurrence.span)
+            .collect();
+        spans.sort_by_key(|span| span.start);
+        Ok(spans.into_iter().map(|span| Server::location(uri, text, span)).collect::<Vec<_>>().into())
+    }
+
+    fn rename(&self, uri: &str, params: &Json) -> RequestResult<Json> {
+        let Lookup { text, occurrences, at, .. } = self.lookup(uri, params)?;
+        let at = at.ok_or((REQUEST_FAILED, "there is no rule or symbol here".to_string()))?;
+        let new_name = params.get("newName").as_str().unwrap_or("");
+        let kind = occurrences[at].kind;
+        if !is_name(new_name, kind) {
+            let message = match kind {
+                NameKind::Rule => format!("'{}' is not a lower_name", new_name),
+                NameKind::Symbol => format!("'{}' is not an UPPER_NAME", new_name)
+            };
+            return Err((REQUEST_FAILED, message))
+        }
+        if occurrences.iter().any(|occurrence| occurrence.definition && occurrence.kind == kind && occurrence.name == new_name) {
+            return Err((REQUEST_FAILED, format!("'{}' already exists", new_name)))
+        }
+
+        let edits: Vec<Json> = occurrences.iter()
+            .filter(|occurrence| occurrence.kind == kind && occurrence.name == occurrences[at].name)
+            .map(|occurrence| Json::object([("range", range(text, occurrence.span)), ("newText", new_name.into())]))
+            .collect();
+        Ok(Json::object([("changes", Json::Object(vec![(uri.to_string(), edits.into())]))]))
+    }
+
+    fn hover(&self, uri: &str, params: &Json) -> RequestResult<Json> {
+        let Lookup { text, grammar, occurrences, at } = match self.lookup(uri, params) {
+            Ok(found) => found,
+            Err((REQUEST_FAILED, _)) => return Ok(Json::Null),
+            Err(error) => return Err(error)
+        };
+
+        let Some(occurrence) = at.map(|at| &occurrences[at]) else { return Ok(Json::Null) };
+        let value = match occurrence.kind {
+            NameKind::Rule => match grammar.rule(&occurrence.name) {
+                Some(rule) => format!("```pglsf\n{} = {};\n```", rule_head(rule), expr_text(&rule.expr)),
+                None => format!("rule `{}` is not defined", occurrence.name)
+            },
+            NameKind::Symbol => match grammar.symbol_index(&occurrence.name) {
+                Some(index) => format!("symbol `{}`, number {} of the declared symbols", occurrence.name, index),
+                None => format!("symbol `{}` is not declared", occurrence.name)
+            }
+        };
+
+        Ok(Json::object([
+            ("contents", Json::object([("kind", "markdown".into()), ("value", value.into())])),
+            ("range", range(text, occurrence.span))
+        ]))
+    }
+
+    fn completion(&self, uri: &str) -> RequestResult<Json> {
+        let Some(grammar) = self.text(uri).and_then(|text| grammar::parse(text).ok()) else { return Ok(Json::Array(Vec::new())) };
+
+        let mut items = Vec::new();
+        if let Symbols::Named(decls) = &grammar.symbols {
+            for decl in decls {
+                items.push(Json::object([("label", decl.name.as_str().into()), ("kind", CONSTANT.into())]));
+            }
+        }
+        for rule in &grammar.rules {
+            items.push(Json::object([
+                ("label", rule.name.as_str().into()),
+                ("kind", FUNCTION.into()),
+                ("detail", expr_text(&rule.expr).into())
+            ]));
+        }
+        Ok(items.into())
+    }
+}
+
+// Reads a message: headers, a blank line, and a body of Content-Length bytes. Returns None at the
+// end of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None)
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "a message has no Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body).map(Some).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+pub fn main() -> ExitCode {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::new();
+
+    loop {
+        let text = match read_message(&mut input) {
+            Ok(Some(text)) => text,
+            Ok(None) => return ExitCode::FAILURE,
+            Err(error) => {
+                eprintln!("parsergen-lsp: {}", error);
+                return ExitCode::FAILURE
+            }
+        };
+
+        let replies = match Json::parse(&text) {
+            Ok(message) if message.get("method").as_str() == Some("exit") => {
+                return if server.shut_down { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+            },
+            Ok(message) => server.handle(&message),
+            Err(message) => vec![error_response(&Json::Null, PARSE_ERROR, &message)]
+        };
+
+        for reply in replies {
+            if let Err(error) = write_message(&mut output, &reply) {
+                eprintln!("parsergen-lsp: {}", error);
+                return ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
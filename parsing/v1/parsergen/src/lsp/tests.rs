@@ -0,0 +1,159 @@
+use crate::grammar;
+use crate::json::Json;
+use crate::lsp::*;
+
+const SOURCE: &str = "symbols A, B;\ngrammar\n\troot = item *item;\n\titem = A | B;\n";
+const URI: &str = "file:///test.pglsf";
+
+fn open(server: &mut Server, text: &str) -> Vec<Json> {
+    let text_document = Json::object([("uri", URI.into()), ("text", text.into())]);
+    let message = Json::object([("method", "textDocument/didOpen".into()), ("params", Json::object([("textDocument", text_document)]))]);
+    server.handle(&message)
+}
+
+fn request(server: &mut Server, method: &str, line: usize, character: usize, extra: Vec<(String, Json)>) -> Json {
+    let mut params = vec![
+        ("textDocument".to_string(), Json::object([("uri", URI.into())])),
+        ("position".to_string(), Json::object([("line", line.into()), ("character", character.into())]))
+    ];
+    params.extend(extra);
+    let message = Json::object([("id", 1usize.into()), ("method", method.into()), ("params", Json::Object(params))]);
+    let mut replies = server.handle(&message);
+    assert_eq!(replies.len(), 1);
+    replies.pop().unwrap()
+}
+
+// The line of the start of a range, and the columns of its start and end.
+fn range_of(location: &Json) -> (u64, u64, u64) {
+    let range = location.get("range");
+    let start = range.get("start");
+    (start.get("line").as_u64().unwrap(), start.get("character").as_u64().unwrap(), range.get("end").get("character").as_u64().unwrap())
+}
+
+#[test]
+fn test_positions() {
+    let source = "ab\n\u{e9}\u{1F600}x\n";
+    assert_eq!(position(source, 3), Json::object([("line", 1usize.into()), ("character", 0usize.into())]));
+    assert_eq!(position(source, 9), Json::object([("line", 1usize.into()), ("character", 3usize.into())]));
+    assert_eq!(offset(source, &position(source, 9)), Some(9));
+    assert_eq!(offset(source, &Json::object([("line", 0usize.into()), ("character", 10usize.into())])), Some(2));
+    assert_eq!(offset(source, &Json::object([("line", 5usize.into()), ("character", 0usize.into())])), None);
+}
+
+#[test]
+fn test_diagnostics() {
+    assert!(diagnostics(None, SOURCE).is_empty());
+
+    let errors = diagnostics(None, "symbols A;\ngrammar\n\troot = C;\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].get("severity").as_u64(), Some(1));
+    assert_eq!(errors[0].get("message").as_str(), Some("symbol 'C' is not declared"));
+    assert_eq!(range_of(&errors[0]), (2, 8, 9));
+
+    let warnings = diagnostics(None, "symbols A;\ngrammar\n\troot = root A | A;\n");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].get("severity").as_u64(), Some(2));
+}
+
+#[test]
+fn test_open_publishes_diagnostics() {
+    let mut server = Server::new();
+    let replies = open(&mut server, "symbols A;\ngrammar\n\troot = B;\n");
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].get("method").as_str(), Some("textDocument/publishDiagnostics"));
+    assert_eq!(replies[0].get("params").get("uri").as_str(), Some(URI));
+    assert_eq!(replies[0].get("params").get("diagnostics").as_array().map(<[Json]>::len), Some(1));
+}
+
+#[test]
+fn test_definition_and_references() {
+    let mut server = Server::new();
+    open(&mut server, SOURCE);
+
+    // From the second use of item in root to its definition.
+    let definition = request(&mut server, "textDocument/definition", 2, 16, Vec::new());
+    assert_eq!(definition.get("result").get("uri").as_str(), Some(URI));
+    assert_eq!(range_of(definition.get("result")), (3, 1, 5));
+
+    // The symbol B, from its use.
+    let references = request(&mut server, "textDocument/references", 3, 12, Vec::new());
+    let locations: Vec<_> = references.get("result").as_array().unwrap().iter().map(range_of).collect();
+    assert_eq!(locations, vec![(0, 11, 12), (3, 12, 13)]);
+
+    let context = vec![("context".to_string(), Json::object([("includeDeclaration", false.into())]))];
+    let references = request(&mut server, "textDocument/references", 3, 1, context);
+    let locations: Vec<_> = references.get("result").as_array().unwrap().iter().map(range_of).collect();
+    assert_eq!(locations, vec![(2, 8, 12), (2, 14, 18)]);
+
+    let nothing = request(&mut server, "textDocument/definition", 1, 2, Vec::new());
+    assert_eq!(nothing.get("result"), &Json::Null);
+}
+
+#[test]
+fn test_template_occurrences() {
+    let grammar = grammar::parse("symbols A;\ngrammar\n\troot = list(A);\n\tlist(x) = x ?list(x);\n").unwrap();
+    let mut uses: Vec<(String, usize)> = occurrences(&grammar).into_iter()
+        .filter(|occurrence| occurrence.kind == NameKind::Rule && !occurrence.definition)
+        .map(|occurrence| (occurrence.name, occurrence.span.end - occurrence.span.start))
+        .collect();
+    uses.sort();
+    assert_eq!(uses, vec![("list".to_string(), 4), ("list".to_string(), 4)]);
+}
+
+#[test]
+fn test_rename() {
+    let mut server = Server::new();
+    open(&mut server, SOURCE);
+
+    let new_name = |name: &str| vec![("newName".to_string(), Json::string(name))];
+    let rename = request(&mut server, "textDocument/rename", 3, 2, new_name("element"));
+    let edits = rename.get("result").get("changes").get(URI).as_array().unwrap();
+    assert_eq!(edits.len(), 3);
+    assert!(edits.iter().all(|edit| edit.get("newText").as_str() == Some("element")));
+
+    let invalid = request(&mut server, "textDocument/rename", 3, 2, new_name("Element"));
+    assert_eq!(invalid.get("error").get("message").as_str(), Some("'Element' is not a lower_name"));
+
+    let taken = request(&mut server, "textDocument/rename", 0, 8, new_name("B"));
+    assert_eq!(taken.get("error").get("message").as_str(), Some("'B' already exists"));
+}
+
+#[test]
+fn test_hover_and_completion() {
+    let mut server = Server::new();
+    open(&mut server, SOURCE);
+
+    let hover = request(&mut server, "textDocument/hover", 2, 9, Vec::new());
+    assert_eq!(hover.get("result").get("contents").get("value").as_str(), Some("```pglsf\nitem = A | B;\n```"));
+
+    let completion = request(&mut server, "textDocument/completion", 2, 0, Vec::new());
+    let labels: Vec<_> = completion.get("result").as_array().unwrap().iter().map(|item| item.get("label").as_str().unwrap()).collect();
+    assert_eq!(labels, vec!["A", "B", "root", "item"]);
+}
+
+#[test]
+fn test_lifecycle() {
+    let mut server = Server::new();
+    let initialize = server.handle(&Json::object([("id", 0usize.into()), ("method", "initialize".into()), ("params", Json::Object(Vec::new()))]));
+    assert_eq!(initialize[0].get("result").get("capabilities").get("renameProvider"), &Json::Bool(true));
+
+    assert!(server.handle(&Json::object([("method", "initialized".into())])).is_empty());
+
+    let unknown = server.handle(&Json::object([("id", 1usize.into()), ("method", "workspace/unknown".into())]));
+    assert_eq!(unknown[0].get("error").get("code"), &Json::Number(-32601.0));
+
+    assert!(!server.shut_down);
+    server.handle(&Json::object([("id", 2usize.into()), ("method", "shutdown".into())]));
+    assert!(server.shut_down);
+}
+
+#[test]
+fn test_messages() {
+    let mut output = Vec::new();
+    write_message(&mut output, &Json::object([("id", 1usize.into())])).unwrap();
+    assert_eq!(output, b"Content-Length: 8\r\n\r\n{\"id\":1}");
+
+    let mut input = &b"Content-Length: 8\r\nContent-Type: x\r\n\r\n{\"id\":1}"[..];
+    assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("{\"id\":1}"));
+    assert_eq!(read_message(&mut input).unwrap(), None);
+}
@@ -3,15 +3,19 @@
 // This application will read a .pglsf file and output a .rs file
 // that compiles into a lib for parsing the specified language.
 #![allow(non_camel_case_types)]
+#![allow(clippy::upper_case_acronyms)]
 
-mod parse_machine;
-mod list;
+use std::process::ExitCode;
 
-//use parse_machine::ParseMachine;
-use parse_machine::ParseRule;
-use parse_machine::SymbolOrRule;
-use list::*;
+use parsergen::{actions, bench, coverage, descent, diagram, exporter, formatter, fuzzer, importer, lr, table, tester};
+use parsergen::grammar::transform;
+//use parsergen::parse_machine::ParseMachine;
+use parsergen::parse_machine::ParseRule;
+use parsergen::parse_machine::Stacks;
+use parsergen::parse_machine::SymbolOrRule;
 
+// The symbols and rules of pglsf.pglsf, written by hand until the generator can produce them.
+#[allow(dead_code)]
 #[derive(Copy, Clone)]
 enum Symbol {
     ULETTER_A,
@@ -101,6 +105,7 @@ enum Symbol {
     COLON,
 }
 
+#[allow(dead_code)]
 #[derive(Copy, Clone)]
 enum Rule {
     ROOT,
@@ -166,10 +171,10 @@ enum Rule {
 }
 
 impl ParseRule<Symbol, Rule> for Rule {
-    fn root() -> Rule { Rule::ROOT }
-
-    fn execute(&self, stack: List<SymbolOrRule<Symbol, Rule>>) -> Vec<List<SymbolOrRule<Symbol, Rule>>> {
-        let mut result: Vec<List<SymbolOrRule<Symbol, Rule>>> = Vec::new();
+    fn execute<S>(&self, _stacks: &mut S, _stack: S::Stack) -> Vec<S::Stack>
+        where S: Stacks<SymbolOrRule<Symbol, Rule>>
+    {
+        let result: Vec<S::Stack> = Vec::new();
 
         //match (self, stack.state()) {
             //(ROOT, NonEmptyList(head, tail)) => (),
@@ -186,6 +191,40 @@ impl ParseRule<Symbol, Rule> for Rule {
     }
 }
 
-fn main() {
-
+const USAGE: &str = "\
+usage: parsergen test <grammar.pglsf> <tests> [--bless]
+       parsergen fuzz <grammar.pglsf> [--count N] [--depth D] [--seed S] [--weight rule.N=W]... [--invalid]
+       parsergen coverage <grammar.pglsf> <corpus> [--html <report.html>]
+       parsergen diagram <grammar.pglsf> <out_dir>
+       parsergen fmt <grammar.pglsf>... [--check]
+       parsergen actions <grammar.pglsf> [<out.rs>]
+       parsergen table <grammar.pglsf>
+       parsergen lr <grammar.pglsf> [--canonical]
+       parsergen descent <grammar.pglsf> [<out.rs>]
+       parsergen transform <grammar.pglsf> bnf|no-epsilon|no-units|cnf|gnf
+       parsergen import --from ebnf|abnf|peg <grammar> [--symbols] [<out.pglsf>]
+       parsergen export --to tree-sitter|antlr4|ebnf <grammar.pglsf> [<out>]
+       parsergen bench <grammar.pglsf> [--count N] [--depth D] [--seed S]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("test") => tester::main(&args[1..]),
+        Some("fuzz") => fuzzer::main(&args[1..]),
+        Some("coverage") => coverage::main(&args[1..]),
+        Some("diagram") => diagram::main(&args[1..]),
+        Some("fmt") => formatter::main(&args[1..]),
+        Some("actions") => actions::main(&args[1..]),
+        Some("table") => table::main(&args[1..]),
+        Some("lr") => lr::main(&args[1..]),
+        Some("descent") => descent::main(&args[1..]),
+        Some("transform") => transform::main(&args[1..]),
+        Some("import") => importer::main(&args[1..]),
+        Some("export") => exporter::main(&args[1..]),
+        Some("bench") => bench::main(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
 }
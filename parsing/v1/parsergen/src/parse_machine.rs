@@ -1,7 +1,9 @@
-mod discard;
+pub mod discard;
 
 use std::marker::PhantomData;
 use discard::HasDiscardFunc;
+use crate::arena::{Arena, ArenaList, EmptyArenaList, NonEmptyArenaList};
+use crate::chain::Chain;
 use crate::list::*;
 // comment_text = +(whitespace | letter);
 //
@@ -15,14 +17,96 @@ use crate::list::*;
 // comment_text = first comment_text_star1 rest;
 // comment_text_star1 = ?((whitespace | letter) comment_text_star1);
 
+// Where a parse machine keeps the stacks of its branches: on the heap, with an Rc per node, or in
+// an arena that the machine owns.
+pub trait Stacks<T>
+    where T: Copy
+{
+    type Stack: Clone + Default;
+
+    fn cons(&mut self, item: T, tail: Self::Stack) -> Self::Stack;
+
+    // The top of the stack and the rest of it, or None if the stack is empty.
+    fn pop(&self, stack: &Self::Stack) -> Option<(T, Self::Stack)>;
+
+    // Frees the nodes of every stack, once none is left. Nodes on the heap are freed as their stacks
+    // are dropped, so by default there is nothing to do.
+    fn clear(&mut self) {}
+}
+
+// Keeps stacks as Lists.
+#[derive(Default)]
+pub struct Heap;
+
+impl<T> Stacks<T> for Heap
+    where T: Copy
+{
+    type Stack = List<T>;
+
+    fn cons(&mut self, item: T, tail: List<T>) -> List<T> {
+        List::cons(item, tail)
+    }
+
+    fn pop(&self, stack: &List<T>) -> Option<(T, List<T>)> {
+        match stack.state() {
+            NonEmptyList(&head, tail) => Some((head, tail)),
+            EmptyList => None
+        }
+    }
+}
+
+// Keeps stacks in the arena, where the nodes of dead branches stay until the machine is reset.
+impl<T> Stacks<T> for Arena<T>
+    where T: Copy
+{
+    type Stack = ArenaList<T>;
+
+    fn cons(&mut self, item: T, tail: ArenaList<T>) -> ArenaList<T> {
+        ArenaList::cons(self, item, tail)
+    }
+
+    fn pop(&self, stack: &ArenaList<T>) -> Option<(T, ArenaList<T>)> {
+        match stack.state(self) {
+            NonEmptyArenaList(&head, tail) => Some((head, tail)),
+            EmptyArenaList => None
+        }
+    }
+
+    fn clear(&mut self) {
+        Arena::clear(self)
+    }
+}
+
 pub trait ParseRule<SymbolType, RuleType>
     where SymbolType: Copy, RuleType: Copy
 {
-    fn root() -> RuleType;
-    fn execute(&self, stack: List<SymbolOrRule<SymbolType, RuleType>>) -> Vec<List<SymbolOrRule<SymbolType, RuleType>>>;
+    // The stacks the rule expands to when it is on top of a stack, whose tail is given. New stacks
+    // are made with stacks.cons, so that the rule works wherever the machine keeps them.
+    fn execute<S>(&self, stacks: &mut S, stack: S::Stack) -> Vec<S::Stack>
+        where S: Stacks<SymbolOrRule<SymbolType, RuleType>>;
+
+    // Executes the rule knowing that the next input symbol is lookahead, which lets it leave out
+    // the stacks that cannot read it. By default none are left out.
+    fn predict<S>(&self, stacks: &mut S, stack: S::Stack, _lookahead: SymbolType) -> Vec<S::Stack>
+        where S: Stacks<SymbolOrRule<SymbolType, RuleType>>
+    {
+        self.execute(stacks, stack)
+    }
+
+    // Whether executing the rule always gives exactly one stack. Such rules are expanded as soon as
+    // they reach the top of a stack, without waiting for the next input; by default none are.
+    fn expands_once(&self) -> bool {
+        false
+    }
+
+    // Whether the rule is returned among the parsed symbols when it is executed. Rules that are not
+    // still expand as usual; they just leave no trace.
+    fn is_recorded(&self) -> bool {
+        true
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum SymbolOrRule<SymbolType, RuleType>
     where SymbolType: Copy, RuleType: Copy
 {
@@ -30,14 +114,26 @@ pub enum SymbolOrRule<SymbolType, RuleType>
     Rule(RuleType)
 }
 
-pub struct ParseMachine<SymbolType, RuleType>
-    where SymbolType: Copy, RuleType: Copy
+pub struct ParseMachine<SymbolType, RuleType, S = Heap>
+    where SymbolType: Copy, RuleType: Copy, S: Stacks<SymbolOrRule<SymbolType, RuleType>>
 {
+    root: RuleType,
+
     // The branches currently being considered by the parse machine.
     // Starts with a length of 1, but grows whenever the parser encounters ambiguous parsings.
     // Multiple branches indicate that the parse machine is in a disambiguating state. Over time,
     // these ambiguities will be resolved as more symbols are passed and branches hit dead ends.
-    branches: Vec<ParseBranch<SymbolType, RuleType>>,
+    branches: Vec<ParseBranch<SymbolType, RuleType, S>>,
+
+    // Where the stacks of the branches are kept.
+    stacks: S,
+
+    // Whether the parse machine has accepted or rejected its input. It reads nothing more once it has.
+    terminal: bool,
+
+    // The symbols the machine could have read where it rejected its input with an error, once each.
+    // Empty until then.
+    expected: Vec<SymbolType>,
 
     // Phantom data is neccessary since Vec is invariant on SymbolType and RuleType.
     phantom1: PhantomData<SymbolType>,
@@ -72,106 +168,163 @@ pub enum ReadResult<SymbolType, RuleType>
     Rejected{reason: RejectReason},
    
     // The parse machine has processed the input successfully. Always check result and symbols. Symbols may be empty if the parser is disambiguating.
-    Processed{result: ProcessResult, symbols: Vec<SymbolOrRule<SymbolType, RuleType>>}
+    // The symbols are those parsed since the last result, in order. They are a chain, so that the branch hands them
+    // out as they are instead of copying them.
+    Processed{result: ProcessResult, symbols: Chain<SymbolOrRule<SymbolType, RuleType>>}
+}
+
+// What a parser offers to the code that feeds it input, so that the ParseMachine and other engines
+// built from the same grammar, such as the LR engine, can stand in for each other.
+pub trait Parser<SymbolType, RuleType>
+    where SymbolType: Copy, RuleType: Copy
+{
+    fn read(&mut self, input: SymbolType) -> ReadResult<SymbolType, RuleType>;
+    fn finish(&mut self) -> ReadResult<SymbolType, RuleType>;
+}
+
+// Lends a parser out, e.g. to parse one input with a machine that is then reset for the next.
+impl<SymbolType, RuleType, P> Parser<SymbolType, RuleType> for &mut P
+    where SymbolType: Copy, RuleType: Copy, P: Parser<SymbolType, RuleType>
+{
+    fn read(&mut self, input: SymbolType) -> ReadResult<SymbolType, RuleType> {
+        P::read(self, input)
+    }
+
+    fn finish(&mut self) -> ReadResult<SymbolType, RuleType> {
+        P::finish(self)
+    }
+}
+
+impl<SymbolType, RuleType, S> Parser<SymbolType, RuleType> for ParseMachine<SymbolType, RuleType, S>
+    where SymbolType: Copy + Eq, RuleType: Copy, RuleType: ParseRule<SymbolType, RuleType>, S: Stacks<SymbolOrRule<SymbolType, RuleType>>
+{
+    fn read(&mut self, input: SymbolType) -> ReadResult<SymbolType, RuleType> {
+        ParseMachine::read(self, input)
+    }
+
+    fn finish(&mut self) -> ReadResult<SymbolType, RuleType> {
+        ParseMachine::finish(self)
+    }
 }
 
 impl<SymbolType, RuleType> ParseMachine<SymbolType, RuleType>
-    where SymbolType: Copy + Eq, SymbolType: 'static, RuleType: Copy, RuleType: 'static, RuleType: ParseRule<SymbolType, RuleType>
+    where SymbolType: Copy + Eq, RuleType: Copy, RuleType: ParseRule<SymbolType, RuleType>
 {
-    pub fn new() {
+    pub fn new(root: RuleType) -> Self {
+        ParseMachine::with_stacks(root, Heap)
+    }
+}
+
+impl<SymbolType, RuleType, S> ParseMachine<SymbolType, RuleType, S>
+    where SymbolType: Copy + Eq, RuleType: Copy, RuleType: ParseRule<SymbolType, RuleType>, S: Stacks<SymbolOrRule<SymbolType, RuleType>>
+{
+    // A parse machine that keeps its stacks in the given place, e.g. an Arena it then owns.
+    pub fn with_stacks(root: RuleType, mut stacks: S) -> Self {
         // Create a new parse machine. Its branches will contain one branch containing the root rule on the stack.
         ParseMachine{
-            branches: Vec::from([ParseBranch::<SymbolType, RuleType>::new()]),
+            root,
+            branches: Vec::from([ParseBranch::new(&mut stacks, root)]),
+            stacks,
+            terminal: false,
+            expected: Vec::new(),
             phantom1: PhantomData,
             phantom2: PhantomData
-        };
+        }
+    }
+
+    // Starts over on a new input. Every stack of the old input is freed, and an arena keeps its
+    // memory for the new one.
+    pub fn reset(&mut self) {
+        self.branches.clear();
+        self.stacks.clear();
+        self.branches.push(ParseBranch::new(&mut self.stacks, self.root));
+        self.terminal = false;
+        self.expected.clear();
+    }
+
+    // Where the machine keeps its stacks, e.g. to see how many nodes an arena holds.
+    pub fn stacks(&self) -> &S {
+        &self.stacks
+    }
+
+    // The symbols that could have come instead of the input the machine rejected with an error, e.g.
+    // to say what was expected there. Empty unless the input was rejected that way.
+    pub fn expected(&self) -> &[SymbolType] {
+        &self.expected
     }
 
     pub fn read(&mut self, input: SymbolType) -> ReadResult<SymbolType, RuleType>
-    {        
-        let mut num_accepted_branches: usize = 0;
-
-        let mut branches_to_add: Vec<List<SymbolOrRule<SymbolType, RuleType>>> = Vec::new();
-
-        for branch in &mut self.branches {
-
-            // Skip this branch if it is dead.
-            if !branch.alive { continue }
-            
-            // Handle the case where we have a symbol on top of the stack.
-            match branch.stack.state() {
-                NonEmptyList(&head, tail) => match head {
-                    SymbolOrRule::Symbol(symbol) =>
-                        if input == symbol {
-
-                            // The symbol matched so we pop it off the stack.
-                            branch.stack = tail;
-                            continue
-                        } else {
-
-                            // The branch hit a symbol it could not parse, so it should be considered dead.
-                            branch.alive = false;
-                            continue
-                        },
-                    SymbolOrRule::Rule(_) => ()
-                },
-                EmptyList => {
+    {
+        if self.terminal {
+            return ReadResult::Rejected{ reason: RejectReason::AlreadyTerminal }
+        }
 
-                    // The branch is alive and empty--this means it is accepted.
-                    num_accepted_branches += 1;
-                    continue
-                }
-            };
-
-            // Handle the case where we have a rule on top of the stack.
-            let (resulting_branches, result) = match std::mem::take(&mut branch.stack).state() {
-                NonEmptyList(head, tail) => {
-                    (
-                        // resulting_branches
-                        match head {
-                            SymbolOrRule::Symbol(_) => None,
-                            SymbolOrRule::Rule(rule) => Some(rule.execute(tail))
-                        },
+        // The stacks as they were before the input, to tell what could have come instead of it.
+        let before: Vec<S::Stack> = self.branches.iter().map(|branch| branch.stack.clone()).collect();
 
-                        // result
-                        Some(head.clone())
-                    )
-                },
+        // Advance every branch past the input. Branches forked along the way are appended to the
+        // end, so they are advanced by this same loop.
+        let mut i = 0;
+        while i < self.branches.len() {
+            let forks = self.branches[i].advance(&mut self.stacks, input);
+            self.branches.extend(forks);
+            i += 1;
+        }
 
-                // If the branch has accepted, its stack will be empty.
-                EmptyList => (None, None)
-            };
-
-            // The first resulting branch replaces this branch, while the remaining branches
-            // are queued to be added to the parse machine.
-            if let Some(resulting_branches) = resulting_branches {
-                match resulting_branches.split_first() {
-                    Some((first, rest)) => {
-                        branch.stack = first.clone();
-                        branches_to_add.extend_from_slice(rest);
-                    },
-                    None => ()
-                }
-            }
+        // Prune dead branches, keeping the rest in the order they were forked in, so that the same
+        // input always leaves the same branches in the same places. Accepted branches won't be
+        // pruned because they are still alive.
+        self.branches.discard_stable(|branch| !branch.alive);
+        if self.branches.is_empty() {
+            self.expected = Self::first_symbols(&mut self.stacks, before);
+        }
 
-            if let Some(result) = result {
-                branch.parsed = List::cons(result, std::mem::take(&mut branch.parsed));
-            }
+        let num_accepted_branches = self.branches.iter().filter(|branch| self.stacks.pop(&branch.stack).is_none()).count();
+        self.conclude(num_accepted_branches)
+    }
 
-            if branch.alive && branch.stack.is_empty() {
-                num_accepted_branches += 1;
-            }
-        };
+    // Signals the end of the input. Branches that can reach an empty stack without reading any more
+    // symbols are accepted, and all others die.
+    pub fn finish(&mut self) -> ReadResult<SymbolType, RuleType>
+    {
+        if self.terminal {
+            return ReadResult::Rejected{ reason: RejectReason::AlreadyTerminal }
+        }
 
-        // Prune dead branches. Accepted branches won't be pruned because they are still alive.
-        self.branches.discard(|branch| !branch.alive);
+        let before: Vec<S::Stack> = self.branches.iter().map(|branch| branch.stack.clone()).collect();
+        let mut finished_branches = Vec::new();
+        for branch in std::mem::take(&mut self.branches) {
+            branch.finish_into(&mut self.stacks, &mut finished_branches);
+        }
 
-        // Add the branches that were created from branching rules.
-        for stack in branches_to_add {
-            self.branches.push(ParseBranch::from_stack(stack));
+        self.branches = finished_branches;
+        if self.branches.is_empty() {
+            self.expected = Self::first_symbols(&mut self.stacks, before);
         }
+        let num_accepted_branches = self.branches.len();
+        self.conclude(num_accepted_branches)
+    }
 
-        match (self.branches.len(), num_accepted_branches) {
+    // The symbols that can come first on any of the stacks, once each, in the order of the stacks.
+    // Every way each rule expands is followed, not only those a lookahead would predict.
+    fn first_symbols(stacks: &mut S, tops: Vec<S::Stack>) -> Vec<SymbolType> {
+        let mut symbols = Vec::new();
+        let mut pending: Vec<S::Stack> = tops.into_iter().rev().collect();
+
+        while let Some(stack) = pending.pop() {
+            match stacks.pop(&stack) {
+                Some((SymbolOrRule::Symbol(symbol), _)) if !symbols.contains(&symbol) => symbols.push(symbol),
+                Some((SymbolOrRule::Rule(rule), tail)) => pending.extend(rule.execute(stacks, tail).into_iter().rev()),
+                _ => ()
+            }
+        }
+
+        symbols
+    }
+
+    fn conclude(&mut self, num_accepted_branches: usize) -> ReadResult<SymbolType, RuleType>
+    {
+        let result = match (self.branches.len(), num_accepted_branches) {
 
             // Will return Processed when there is only one branch alive.
             (1, num_accepted_branches) => if let Some(branch) = self.branches.get_mut(0) {
@@ -181,7 +334,7 @@ impl<SymbolType, RuleType> ParseMachine<SymbolType, RuleType>
                     result: if num_accepted_branches == 1 { ProcessResult::Accepted } else { ProcessResult::Awaiting },
 
                     // Empty the branch's parsed symbols into the result enum. It will be empty upon the next read.
-                    symbols: std::mem::take(&mut branch.parsed).reverse_into_vec()
+                    symbols: branch.take_parsed()
                 }
             } else { panic!() },
 
@@ -192,43 +345,158 @@ impl<SymbolType, RuleType> ParseMachine<SymbolType, RuleType>
             (n, m) if n == m => ReadResult::Rejected { reason: RejectReason::Ambiguous },
 
             // There are multiple branches, so the parse machine cannot make a decision. It must await input for disambiguating.
-            _ => ReadResult::Processed{ result: ProcessResult::Awaiting, symbols: vec![] }
-        }
+            _ => ReadResult::Processed{ result: ProcessResult::Awaiting, symbols: Chain::EMPTY }
+        };
+
+        // Both rejecting and accepting put the parse machine in a terminal state.
+        self.terminal = !matches!(result, ReadResult::Processed{ result: ProcessResult::Awaiting, .. });
+        result
     }
 }
 
-struct ParseBranch<SymbolType, RuleType>
-    where SymbolType: Copy, RuleType: Copy
+// How many parsed items a branch gathers before joining them onto its chain. Joining a run takes
+// O(log n) allocations, as appending a single item would, so runs spread that cost over their items.
+const RUN_LENGTH: usize = 32;
+
+struct ParseBranch<SymbolType, RuleType, S>
+    where SymbolType: Copy, RuleType: Copy, S: Stacks<SymbolOrRule<SymbolType, RuleType>>
 {
-    stack: List<SymbolOrRule<SymbolType, RuleType>>,
-    parsed: List<SymbolOrRule<SymbolType, RuleType>>,
+    stack: S::Stack,
+
+    // The symbols and rules parsed since the last result, in order: those in parsed, then those in
+    // run. Forks share parsed with the branch they were forked from, and copy run, which is short.
+    parsed: Chain<SymbolOrRule<SymbolType, RuleType>>,
+    run: Vec<SymbolOrRule<SymbolType, RuleType>>,
     alive: bool
 }
 
-impl<SymbolType, RuleType> ParseBranch<SymbolType, RuleType>
-    where SymbolType: Copy, RuleType: Copy, RuleType: ParseRule<SymbolType, RuleType>
+impl<SymbolType, RuleType, S> ParseBranch<SymbolType, RuleType, S>
+    where SymbolType: Copy + Eq, RuleType: Copy, RuleType: ParseRule<SymbolType, RuleType>, S: Stacks<SymbolOrRule<SymbolType, RuleType>>
 {
-    fn new() -> Self {
+    fn new(stacks: &mut S, root: RuleType) -> Self {
         Self{
-            stack: List::cons(SymbolOrRule::Rule(RuleType::root()), List::EMPTY),
-            parsed: List::EMPTY,
+            stack: stacks.cons(SymbolOrRule::Rule(root), S::Stack::default()),
+            parsed: Chain::EMPTY,
+            run: Vec::new(),
             alive: true
         }
     }
 
-    fn from_stack(stack: List<SymbolOrRule<SymbolType, RuleType>>) -> Self {
+    // A branch that continues from the given stack with the symbols parsed so far by this one.
+    fn fork(&self, stack: S::Stack) -> Self {
         Self{
-            stack: stack,
-            parsed: List::EMPTY,
+            stack,
+            parsed: self.parsed.clone(),
+            run: self.run.clone(),
             alive: true
         }
     }
 
-    fn clone(&self) -> Self {
-        Self{
-            stack: self.stack.clone(),
-            parsed: self.parsed.clone(),
-            alive: self.alive
+    // Adds an item to the end of the parsed symbols.
+    fn append(&mut self, item: SymbolOrRule<SymbolType, RuleType>) {
+        self.run.push(item);
+        if self.run.len() == RUN_LENGTH {
+            let run = self.run.drain(..).collect();
+            self.parsed = Chain::concat(std::mem::take(&mut self.parsed), run);
+        }
+    }
+
+    // Adds an executed rule to the parsed symbols, if it is recorded at all.
+    fn record(&mut self, rule: RuleType) {
+        if rule.is_recorded() {
+            self.append(SymbolOrRule::Rule(rule));
+        }
+    }
+
+    // Empties the parsed symbols into a chain.
+    fn take_parsed(&mut self) -> Chain<SymbolOrRule<SymbolType, RuleType>> {
+        let run = self.run.drain(..).collect();
+        Chain::concat(std::mem::take(&mut self.parsed), run)
+    }
+
+    // Expands rules on top of the stack until a symbol is on top, then matches it against the input.
+    // Rules that expand to several stacks fork the branch; the forks are returned and still have to
+    // be advanced past the input themselves.
+    fn advance(&mut self, stacks: &mut S, input: SymbolType) -> Vec<Self> {
+        let mut forks = Vec::new();
+
+        loop {
+            match stacks.pop(&self.stack) {
+                Some((SymbolOrRule::Symbol(symbol), tail)) => {
+                    if input == symbol {
+
+                        // The symbol matched so we pop it off the stack.
+                        self.stack = tail;
+                        self.append(SymbolOrRule::Symbol(symbol));
+                        self.settle(stacks);
+                    } else {
+
+                        // The branch hit a symbol it could not parse, so it should be considered dead.
+                        self.alive = false;
+                    }
+                    break
+                },
+                Some((SymbolOrRule::Rule(rule), tail)) => {
+                    self.record(rule);
+                    let mut expanded = rule.predict(stacks, tail, input).into_iter();
+
+                    // The first resulting stack replaces this branch's stack, while the remaining
+                    // stacks become new branches.
+                    match expanded.next() {
+                        Some(first) => {
+                            self.stack = first;
+                            forks.extend(expanded.map(|stack| self.fork(stack)));
+                        },
+                        None => {
+
+                            // The rule expanded to nothing, so there is no way to continue.
+                            self.alive = false;
+                            break
+                        }
+                    }
+                },
+                None => {
+
+                    // The branch was accepted before this input, so it cannot take any more.
+                    self.alive = false;
+                    break
+                }
+            }
+        }
+
+        forks
+    }
+
+    // Expands rules on top of the stack for as long as each expands to exactly one stack. A branch
+    // that needs nothing more to finish becomes empty, and so accepted, right away. Other rules are
+    // left for advance, which knows the next input.
+    fn settle(&mut self, stacks: &mut S) {
+        while let Some((SymbolOrRule::Rule(rule), tail)) = stacks.pop(&self.stack) {
+            if !rule.expands_once() { break }
+
+            self.stack = rule.execute(stacks, tail).pop().unwrap();
+            self.record(rule);
+        }
+    }
+
+    // Expands rules until every way of continuing this branch either has an empty stack, and is
+    // pushed onto finished, or needs another symbol, and is dropped.
+    fn finish_into(self, stacks: &mut S, finished: &mut Vec<Self>) {
+        let mut pending = vec![self];
+
+        while let Some(mut branch) = pending.pop() {
+            match stacks.pop(&branch.stack) {
+                Some((SymbolOrRule::Rule(rule), tail)) => {
+                    branch.record(rule);
+                    let expanded = rule.execute(stacks, tail);
+                    pending.extend(expanded.into_iter().rev().map(|stack| branch.fork(stack)));
+                },
+                Some((SymbolOrRule::Symbol(_), _)) => (),
+                None => finished.push(branch)
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -1,21 +1,45 @@
 pub trait HasDiscardFunc<T> {
-    // Discards any elements from self that match the predicate.
-    // Does not guarantee preservation of order.
-    fn discard<F>(&mut self, pred: F) where F: FnMut(&T) -> bool;
+    // Removes the elements for which the predicate is true, and returns them. Neither the elements
+    // kept nor those returned are guaranteed to stay in order.
+    fn discard<F>(&mut self, pred: F) -> Vec<T> where F: FnMut(&T) -> bool;
+
+    // Like discard, but the elements kept and those returned both stay in order.
+    fn discard_stable<F>(&mut self, pred: F) -> Vec<T> where F: FnMut(&T) -> bool;
+
+    // Like discard_stable, but the elements are removed as the iterator comes to them, without
+    // collecting them first. Elements it has not come to when it is dropped are kept.
+    fn drain_discarded<F>(&mut self, pred: F) -> impl Iterator<Item = T> where F: FnMut(&T) -> bool;
 }
 
 impl<T> HasDiscardFunc<T> for Vec<T> {
-    fn discard<F>(&mut self, mut pred: F) 
+    fn discard<F>(&mut self, mut pred: F) -> Vec<T>
         where F: FnMut(&T) -> bool
     {
-        let mut j = 0;
-        for i in 0..self.len() {
+        let mut discarded = Vec::new();
+        let mut i = 0;
+        while i < self.len() {
             if pred(&self[i]) {
-                self.swap(i, j);
-                j += 1;
+                discarded.push(self.swap_remove(i));
+            } else {
+                i += 1;
             }
         }
 
-        self.truncate(j);
+        discarded
+    }
+
+    fn discard_stable<F>(&mut self, pred: F) -> Vec<T>
+        where F: FnMut(&T) -> bool
+    {
+        self.drain_discarded(pred).collect()
+    }
+
+    fn drain_discarded<F>(&mut self, mut pred: F) -> impl Iterator<Item = T>
+        where F: FnMut(&T) -> bool
+    {
+        self.extract_if(.., move |element| pred(element))
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,71 @@
+use crate::parse_machine::discard::*;
+use crate::rng::Rng;
+
+// A value and its position, so that equal values can still be told apart.
+type Element = (usize, usize);
+
+// Random vectors of elements. Values are small so that some are discarded and some kept.
+fn samples(seed: u64) -> impl Iterator<Item = Vec<Element>> {
+    let mut rng = Rng::new(seed);
+    (0..500).map(move |_| {
+        let len = rng.below(20);
+        (0..len).map(|position| (rng.below(4), position)).collect()
+    })
+}
+
+fn is_odd(element: &Element) -> bool {
+    element.0 % 2 == 1
+}
+
+// What is kept and what is discarded, in order.
+fn partition(elements: &[Element]) -> (Vec<Element>, Vec<Element>) {
+    elements.iter().partition(|element| !is_odd(element))
+}
+
+#[test]
+fn test_discard() {
+    for elements in samples(1) {
+        let (mut kept, mut discarded) = partition(&elements);
+        let mut remaining = elements.clone();
+        let mut removed = remaining.discard(is_odd);
+
+        // The same elements end up on each side, in whatever order.
+        for side in [&mut kept, &mut discarded, &mut remaining, &mut removed] {
+            side.sort();
+        }
+        assert_eq!((remaining, removed), (kept, discarded));
+    }
+}
+
+#[test]
+fn test_discard_stable() {
+    for elements in samples(2) {
+        let mut remaining = elements.clone();
+        let removed = remaining.discard_stable(is_odd);
+        assert_eq!((remaining, removed), partition(&elements));
+    }
+}
+
+#[test]
+fn test_drain_discarded() {
+    for elements in samples(3) {
+        let mut remaining = elements.clone();
+        let mut calls = 0;
+        let removed: Vec<_> = remaining.drain_discarded(|element| {
+            calls += 1;
+            is_odd(element)
+        }).collect();
+        assert_eq!((remaining, removed), partition(&elements));
+        assert_eq!(calls, elements.len());
+
+        // Dropping the iterator early keeps what it has not come to yet.
+        let mut remaining = elements.clone();
+        let first = remaining.drain_discarded(is_odd).next();
+        let mut expected = elements.clone();
+        if let Some(position) = elements.iter().position(is_odd) {
+            expected.remove(position);
+        }
+        assert_eq!(first, elements.iter().copied().find(is_odd));
+        assert_eq!(remaining, expected);
+    }
+}
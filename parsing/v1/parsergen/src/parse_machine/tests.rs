@@ -0,0 +1,281 @@
+use crate::parse_machine::*;
+use crate::grammar::program::Program;
+use crate::grammar::{read, Expr, ExprKind, Grammar};
+use crate::rng::Rng;
+use crate::tester::{input_symbols, parse, parse_with, run, Outcome};
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// root = 'a' 'b' | 'a' 'c'; single = 'a' 'b'; long = 70*'a' 'b' | 70*'a' 'c';
+// prefixed = 'a' counted; counted = 'b' | 'c';
+#[derive(Copy, Clone)]
+enum TestRule {
+    Root,
+    Single,
+    Long,
+    Prefixed,
+    Counted
+}
+
+// How many times counted has been executed.
+static COUNTED: AtomicUsize = AtomicUsize::new(0);
+
+impl ParseRule<char, TestRule> for TestRule {
+    fn execute<S>(&self, stacks: &mut S, stack: S::Stack) -> Vec<S::Stack>
+        where S: Stacks<SymbolOrRule<char, TestRule>>
+    {
+        let mut push = |symbols: &[char]| symbols.iter().rev().fold(stack.clone(), |stack, &symbol| stacks.cons(SymbolOrRule::Symbol(symbol), stack));
+        match self {
+            TestRule::Root => vec![push(&['a', 'b']), push(&['a', 'c'])],
+            TestRule::Single => vec![push(&['a', 'b'])],
+            TestRule::Long => vec![push(&[['a'; 70].as_slice(), &['b']].concat()), push(&[['a'; 70].as_slice(), &['c']].concat())],
+            TestRule::Prefixed => {
+                let counted = stacks.cons(SymbolOrRule::Rule(TestRule::Counted), stack);
+                vec![stacks.cons(SymbolOrRule::Symbol('a'), counted)]
+            },
+            TestRule::Counted => {
+                COUNTED.fetch_add(1, Ordering::SeqCst);
+                vec![push(&['b']), push(&['c'])]
+            }
+        }
+    }
+
+    fn expands_once(&self) -> bool {
+        matches!(self, TestRule::Single | TestRule::Prefixed)
+    }
+}
+
+// The symbols of a result as text, with rules in parentheses.
+fn text(result: ReadResult<char, TestRule>) -> (bool, String) {
+    match result {
+        ReadResult::Processed { result, symbols } => (
+            matches!(result, ProcessResult::Accepted),
+            symbols.iter().map(|item| match item {
+                SymbolOrRule::Symbol(symbol) => symbol.to_string(),
+                SymbolOrRule::Rule(TestRule::Root) => "(root)".to_string(),
+                SymbolOrRule::Rule(TestRule::Single) => "(single)".to_string(),
+                SymbolOrRule::Rule(TestRule::Long) => "(long)".to_string(),
+                SymbolOrRule::Rule(TestRule::Prefixed) => "(prefixed)".to_string(),
+                SymbolOrRule::Rule(TestRule::Counted) => "(counted)".to_string()
+            }).collect()
+        ),
+        ReadResult::Rejected { .. } => panic!()
+    }
+}
+
+#[test]
+fn test_read_symbols_in_order() {
+    let mut machine = ParseMachine::new(TestRule::Single);
+    assert_eq!(text(machine.read('a')), (false, "(single)a".to_string()));
+    assert_eq!(text(machine.read('b')), (true, "b".to_string()));
+}
+
+#[test]
+fn test_read_shared_prefix() {
+    // Both branches parse (root)a before they part, and only the one left hands it out.
+    let mut machine = ParseMachine::new(TestRule::Root);
+    assert_eq!(text(machine.read('a')), (false, String::new()));
+    assert_eq!(text(machine.read('c')), (true, "(root)ac".to_string()));
+}
+
+#[test]
+fn test_read_long_shared_prefix() {
+    // The prefix spans several runs of parsed items, which have to be handed out in order.
+    let mut machine = ParseMachine::new(TestRule::Long);
+    for _ in 0..70 {
+        assert_eq!(text(machine.read('a')), (false, String::new()));
+    }
+    assert_eq!(text(machine.read('b')), (true, format!("(long){}b", "a".repeat(70))));
+}
+
+#[test]
+fn test_read_expected() {
+    let mut machine = ParseMachine::new(TestRule::Root);
+    machine.read('a');
+    assert!(machine.expected().is_empty());
+    assert!(matches!(machine.read('d'), ReadResult::Rejected { reason: RejectReason::Error }));
+    assert_eq!(machine.expected(), ['b', 'c']);
+}
+
+#[test]
+fn test_expected_with_program() {
+    // Prediction leaves out the alternatives that cannot read D, so the machine has to look past it.
+    let program = Program::compile(&read("symbols A, B, C, D;\ngrammar\n\troot = A (B | C);\n").unwrap()).unwrap();
+    let mut machine = program.machine();
+    machine.read(0);
+    assert!(matches!(machine.read(3), ReadResult::Rejected { reason: RejectReason::Error }));
+    assert_eq!(machine.expected(), [1, 2]);
+
+    let mut machine = program.arena_machine();
+    machine.read(0);
+    assert!(matches!(machine.finish(), ReadResult::Rejected { reason: RejectReason::Error }));
+    assert_eq!(machine.expected(), [1, 2]);
+    machine.reset();
+    assert!(machine.expected().is_empty());
+}
+
+#[test]
+fn test_read_executes_rules_once() {
+    // counted reaches the top of the stack after 'a', but expands two ways, so it waits for the
+    // next input rather than being executed then as well.
+    let mut machine = ParseMachine::new(TestRule::Prefixed);
+    assert_eq!(text(machine.read('a')), (false, "(prefixed)a".to_string()));
+    assert_eq!(text(machine.read('c')), (true, "(counted)c".to_string()));
+    assert_eq!(COUNTED.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn test_send_machine() {
+    // A machine can be moved to another thread halfway through its input, and its symbols back.
+    let mut machine = ParseMachine::new(TestRule::Root);
+    assert_eq!(text(machine.read('a')), (false, String::new()));
+    let result = std::thread::spawn(move || machine.read('b')).join().unwrap();
+    assert_eq!(text(result), (true, "(root)ab".to_string()));
+}
+
+#[test]
+fn test_reset_arena_machine() {
+    // The arena machine reads as the heap machine does, and starts over once reset.
+    let mut machine = ParseMachine::with_stacks(TestRule::Root, Arena::new());
+    assert_eq!(text(machine.read('a')), (false, String::new()));
+    assert_eq!(text(machine.read('c')), (true, "(root)ac".to_string()));
+    assert!(matches!(machine.read('c'), ReadResult::Rejected { reason: RejectReason::AlreadyTerminal }));
+    assert!(!machine.stacks().is_empty());
+
+    machine.reset();
+    assert_eq!(machine.stacks().len(), 1);
+    assert_eq!(text(machine.read('a')), (false, String::new()));
+    assert_eq!(text(machine.read('b')), (true, "(root)ab".to_string()));
+}
+
+const SYMBOLS: [&str; 3] = ["A", "B", "C"];
+const RULES: [&str; 3] = ["root", "item", "part"];
+
+// A random expression for a rule of a small grammar, as pglsf source. Rules are referred to less
+// often than symbols, so that not too many of the grammars are left-recursive.
+fn random_expr(rng: &mut Rng, depth: usize) -> String {
+    match rng.below(if depth == 0 { 4 } else { 10 }) {
+        0..=2 => SYMBOLS[rng.below(SYMBOLS.len())].to_string(),
+        3 => RULES[rng.below(RULES.len())].to_string(),
+        4 | 5 => format!("({} {})", random_expr(rng, depth - 1), random_expr(rng, depth - 1)),
+        6 => format!("({} | {})", random_expr(rng, depth - 1), random_expr(rng, depth - 1)),
+        7 => format!("?({})", random_expr(rng, depth - 1)),
+        8 => format!("*({})", random_expr(rng, depth - 1)),
+        _ => format!("+({})", random_expr(rng, depth - 1))
+    }
+}
+
+fn random_grammar(rng: &mut Rng) -> String {
+    let mut source = format!("symbols {};\ngrammar\n", SYMBOLS.join(", "));
+    for rule in RULES {
+        source += &format!("\t{} = {};\n", rule, random_expr(rng, 3));
+    }
+    source
+}
+
+// Decides whether a grammar matches an input the slow way: by finding every end that every rule can
+// reach from every start, until no more are found. Nothing about the grammar is assumed, not even
+// that it is not left-recursive.
+struct Recogniser<'a> {
+    grammar: &'a Grammar,
+    input: &'a [usize],
+
+    // For each rule and start, the ends found so far.
+    ends: Vec<Vec<BTreeSet<usize>>>
+}
+
+impl Recogniser<'_> {
+    fn matches(grammar: &Grammar, input: &[usize]) -> bool {
+        let mut recogniser = Recogniser { grammar, input, ends: vec![vec![BTreeSet::new(); input.len() + 1]; grammar.rules.len()] };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, rule) in grammar.rules.iter().enumerate() {
+                for start in 0..=input.len() {
+                    let ends = recogniser.expr_ends(&rule.expr, start);
+                    if ends != recogniser.ends[i][start] {
+                        recogniser.ends[i][start] = ends;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        let root = grammar.rules.iter().position(|rule| rule.name == grammar.root().unwrap().name).unwrap();
+        recogniser.ends[root][0].contains(&input.len())
+    }
+
+    fn expr_ends(&self, expr: &Expr, start: usize) -> BTreeSet<usize> {
+        match &expr.kind {
+            ExprKind::Symbol(name) => match self.input.get(start) == self.grammar.symbol_index(name).as_ref() {
+                true => BTreeSet::from([start + 1]),
+                false => BTreeSet::new()
+            },
+            ExprKind::Rule(name) => self.ends[self.grammar.rules.iter().position(|rule| &rule.name == name).unwrap()][start].clone(),
+            ExprKind::Seq(items) => items.iter().fold(BTreeSet::from([start]), |starts, item| {
+                starts.iter().flat_map(|&start| self.expr_ends(item, start)).collect()
+            }),
+            ExprKind::Union(items) => items.iter().flat_map(|item| self.expr_ends(item, start)).collect(),
+            ExprKind::Opt(inner) => self.expr_ends(inner, start).into_iter().chain([start]).collect(),
+            ExprKind::Star(inner) => self.repeat_ends(inner, BTreeSet::from([start])),
+            ExprKind::Plus(inner) => self.repeat_ends(inner, self.expr_ends(inner, start)),
+            _ => unreachable!("the grammars have no bytes, ranges or templates")
+        }
+    }
+
+    // The ends of repeating inner any number of times after the given ends.
+    fn repeat_ends(&self, inner: &Expr, mut ends: BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut pending: Vec<usize> = ends.iter().copied().collect();
+        while let Some(start) = pending.pop() {
+            for end in self.expr_ends(inner, start) {
+                if ends.insert(end) {
+                    pending.push(end);
+                }
+            }
+        }
+        ends
+    }
+}
+
+// Every input of up to max_len symbols.
+fn all_inputs(max_len: usize) -> Vec<Vec<usize>> {
+    let mut inputs = vec![Vec::new()];
+    let mut last = vec![Vec::new()];
+    for _ in 0..max_len {
+        last = last.iter()
+            .flat_map(|input: &Vec<usize>| (0..SYMBOLS.len()).map(move |symbol| [input.as_slice(), &[symbol]].concat()))
+            .collect();
+        inputs.extend(last.iter().cloned());
+    }
+    inputs
+}
+
+#[test]
+fn test_machine_matches_recogniser() {
+    let mut rng = Rng::new(0);
+    let inputs = all_inputs(4);
+    let mut compiled = 0;
+    for _ in 0..300 {
+        let source = random_grammar(&mut rng);
+        let grammar = read(&source).unwrap();
+
+        // Grammars the machine cannot run, e.g. left-recursive ones, are rejected when compiled.
+        let Ok(program) = Program::compile(&grammar) else { continue };
+        compiled += 1;
+
+        // The arena machine is reset for each input rather than made anew, as the tester does.
+        let mut arena = program.arena_machine();
+        for input in &inputs {
+            let names: Vec<&str> = input.iter().map(|&symbol| SYMBOLS[symbol]).collect();
+            let symbols = input_symbols(&program, &names.join(" ")).unwrap();
+            let accepted = matches!(run(&program, &symbols), Outcome::Accept(_) | Outcome::Ambiguous);
+            assert_eq!(accepted, Recogniser::matches(&grammar, input), "{}input: {}", source, names.join(" "));
+
+            arena.reset();
+            assert!(parse_with(&mut arena, &symbols) == parse(&program, &symbols), "{}input: {}", source, names.join(" "));
+        }
+    }
+    assert!(compiled >= 100, "only {} of the grammars compiled", compiled);
+}
@@ -0,0 +1,59 @@
+// rng.rs
+//
+// A small seeded random number generator (xorshift64*), so that generated inputs can be reproduced
+// from their seed without depending on an outside crate.
+//
+
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero, and nearby seeds should not give similar streams.
+        let mut rng = Rng { state: seed ^ 0x9E37_79B9_7F4A_7C15 };
+        if rng.state == 0 {
+            rng.state = 1;
+        }
+        rng.next_u64();
+        rng
+    }
+
+    // A seed taken from the clock, for runs that do not ask for a particular one.
+    pub fn clock_seed() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // A number in 0..n. n must not be zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    // True with a probability of numerator / denominator.
+    pub fn chance(&mut self, numerator: usize, denominator: usize) -> bool {
+        self.below(denominator) < numerator
+    }
+
+    // An index into weights, chosen with probability proportional to its weight. The weights must
+    // not all be zero.
+    pub fn weighted(&mut self, weights: &[u32]) -> usize {
+        let total: u64 = weights.iter().map(|&weight| weight as u64).sum();
+        let mut pick = self.next_u64() % total;
+        for (i, &weight) in weights.iter().enumerate() {
+            if pick < weight as u64 {
+                return i
+            }
+            pick -= weight as u64;
+        }
+        unreachable!()
+    }
+}
@@ -0,0 +1,118 @@
+// table.rs
+//
+// parsergen table <grammar.pglsf>
+//
+// Prints the prediction table of a grammar: for every choice in its rules, which way the next
+// symbol leads to. A choice is LL(1) when every symbol leads at most one way; the ParseMachine then
+// takes that way without forking. The rules with choices that are not are listed at the end, with
+// the symbols that leave more than one way open, since for those the machine still forks.
+//
+// Ranges are left out, as every symbol of a range leads to a way of its own.
+//
+
+use std::process::ExitCode;
+
+use crate::grammar::lookahead::Decision;
+use crate::grammar::program::{Node, Program};
+use crate::grammar;
+
+// E.g. "union: A -> 1, B -> 2 | 3". Ways are numbered for unions, and named for the rest.
+fn describe(program: &Program, decision: &Decision) -> String {
+    let (kind, ways): (&str, Vec<String>) = match &program.nodes()[decision.node as usize] {
+        Node::Union(ways) => ("union", (1..=ways.len()).map(|i| i.to_string()).collect()),
+        Node::Opt(_, _) => ("optional", vec!["present".to_string(), "absent".to_string()]),
+        Node::Star(_, _) => ("repetition", vec!["taken".to_string(), "empty".to_string()]),
+        Node::Repeat(_) => ("repetition", vec!["again".to_string(), "stop".to_string()]),
+        _ => unreachable!("only unions, optionals and repetitions are described")
+    };
+
+    // Every symbol that leads anywhere, in order, with the ways it leads to.
+    let mut rows: Vec<(u32, Vec<&str>)> = Vec::new();
+    for (way, symbols) in ways.iter().zip(&decision.alternatives) {
+        for symbol in symbols.iter() {
+            match rows.iter_mut().find(|(other, _)| *other == symbol) {
+                Some((_, ways)) => ways.push(way),
+                None => rows.push((symbol, vec![way]))
+            }
+        }
+    }
+    rows.sort_by_key(|(symbol, _)| *symbol);
+
+    let rows: Vec<String> = rows.iter()
+        .map(|(symbol, ways)| format!("{} -> {}", program.symbol_name(*symbol), ways.join(" | ")))
+        .collect();
+    match rows.is_empty() {
+        true => format!("{}: no symbol leads anywhere", kind),
+        false => format!("{}: {}", kind, rows.join(", "))
+    }
+}
+
+// The table, one line per choice, followed by the rules that are not LL(1).
+pub fn report(program: &Program, path: &str, source: &str) -> String {
+    let decisions: Vec<&Decision> = program.decisions().iter()
+        .filter(|decision| !matches!(program.nodes()[decision.node as usize], Node::Range(_)))
+        .collect();
+    let deterministic = decisions.iter().filter(|decision| decision.is_deterministic()).count();
+
+    let mut report = format!("{}: {} of {} choices are LL(1)\n", path, deterministic, decisions.len());
+    let mut forking: Vec<usize> = Vec::new();
+    for decision in &decisions {
+        let (line, col) = program.span(decision.node).line_col(source);
+        let rule = decision.rule;
+        report.push_str(&format!("{}:{}:{}: rule '{}', {}\n", path, line, col, program.rules()[rule].name, describe(program, decision)));
+
+        if !decision.is_deterministic() && !forking.contains(&rule) {
+            forking.push(rule);
+        }
+    }
+
+    if !forking.is_empty() {
+        report.push_str("\nrules that still fork:\n");
+        for rule in forking {
+            let mut conflicts = Vec::new();
+            for decision in decisions.iter().filter(|decision| decision.rule == rule) {
+                for symbol in decision.conflicts().iter() {
+                    let name = program.symbol_name(symbol);
+                    if !conflicts.contains(&name) {
+                        conflicts.push(name);
+                    }
+                }
+            }
+            report.push_str(&format!("\t{} (on {})\n", program.rules()[rule].name, conflicts.join(", ")));
+        }
+    }
+
+    report
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let grammar_path = match args {
+        [grammar_path] => grammar_path.as_str(),
+        _ => {
+            eprintln!("usage: parsergen table <grammar.pglsf>");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let (grammar, source) = match grammar::load(grammar_path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    match Program::compile(&grammar) {
+        Ok(program) => {
+            print!("{}", report(&program, grammar_path, &source));
+            ExitCode::SUCCESS
+        },
+        Err(error) => {
+            eprintln!("{}", error.describe(grammar_path, &source));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
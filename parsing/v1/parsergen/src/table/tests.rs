@@ -0,0 +1,33 @@
+use crate::table::*;
+use crate::grammar::read;
+
+const GRAMMAR: &str = "\
+symbols A, B, C;
+grammar
+	root = *item C;
+	item = A B | A C | B ?C;
+";
+
+#[test]
+fn test_decisions() {
+    let program = Program::compile(&read(GRAMMAR).unwrap()).unwrap();
+    let deterministic: Vec<(&str, bool)> = program.decisions().iter()
+        .map(|decision| (program.rules()[decision.rule].name.as_str(), decision.is_deterministic()))
+        .collect();
+    assert_eq!(deterministic, vec![("root", true), ("root", true), ("item", false), ("item", false)]);
+}
+
+#[test]
+fn test_report() {
+    let program = Program::compile(&read(GRAMMAR).unwrap()).unwrap();
+    assert_eq!(report(&program, "items.pglsf", GRAMMAR), "\
+items.pglsf: 2 of 4 choices are LL(1)
+items.pglsf:3:9: rule 'root', repetition: A -> taken, B -> taken, C -> empty
+items.pglsf:3:9: rule 'root', repetition: A -> again, B -> again, C -> stop
+items.pglsf:4:9: rule 'item', union: A -> 1 | 2, B -> 3
+items.pglsf:4:23: rule 'item', optional: A -> absent, B -> absent, C -> present | absent
+
+rules that still fork:
+\titem (on A, C)
+");
+}
@@ -0,0 +1,409 @@
+// tester.rs
+//
+// parsergen test <grammar.pglsf> <tests> [--bless]
+//
+// Runs test cases through a ParseMachine built from a grammar. <tests> is a test file or a
+// directory of them. A test file holds any number of cases:
+//
+//     === name of the case
+//     input
+//     ---
+//     expected outcome
+//
+// For grammars with named symbols, the input is the symbol names separated by whitespace. For
+// binary grammars, the input is the text between the name and the ---, without its final newline.
+// The expected outcome is one of:
+//
+//     accept              the input is accepted
+//     reject 3            the input is rejected at symbol 3, counting from 0
+//     ambiguous           the input can be parsed more than one way
+//     (root (rule A) B)   the input is accepted with this tree
+//
+// A failing case that is rejected also lists the symbols that could have come where it was. With
+// --bless, the expected outcomes of failing cases are rewritten with the actual ones.
+//
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use crate::grammar::program::{Program, ProgramRule, ProgramSymbolOrRule};
+use crate::grammar;
+use crate::parse_machine::{Parser, ProcessResult, ReadResult, RejectReason};
+use crate::tree::Tree;
+
+const TREE_WIDTH: usize = 80;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Accept(Tree),
+    Reject(usize),
+    Ambiguous
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expected {
+    Accept,
+    Tree(Tree),
+    Reject(usize),
+    Ambiguous
+}
+
+impl Expected {
+    pub fn parse(text: &str) -> Result<Expected, String> {
+        let text = text.trim();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words.as_slice() {
+            ["accept"] => Ok(Expected::Accept),
+            ["ambiguous"] => Ok(Expected::Ambiguous),
+            ["reject", offset] => offset.parse()
+                .map(Expected::Reject)
+                .map_err(|_| format!("'{}' is not an offset", offset)),
+            _ if text.starts_with('(') => Tree::parse(text).map(Expected::Tree),
+            _ => Err(format!("expected accept, reject <offset>, ambiguous or a tree, found '{}'", text))
+        }
+    }
+
+    pub fn matches(&self, outcome: &Outcome) -> bool {
+        match (self, outcome) {
+            (Expected::Accept, Outcome::Accept(_)) => true,
+            (Expected::Tree(expected), Outcome::Accept(actual)) => expected == actual,
+            (Expected::Reject(expected), Outcome::Reject(actual)) => expected == actual,
+            (Expected::Ambiguous, Outcome::Ambiguous) => true,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expected::Accept => write!(f, "accept"),
+            Expected::Tree(tree) => write!(f, "{}", tree.pretty(TREE_WIDTH)),
+            Expected::Reject(offset) => write!(f, "reject {}", offset),
+            Expected::Ambiguous => write!(f, "ambiguous")
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Accept(tree) => write!(f, "{}", tree.pretty(TREE_WIDTH)),
+            Outcome::Reject(offset) => write!(f, "reject {}", offset),
+            Outcome::Ambiguous => write!(f, "ambiguous")
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TestCase {
+    pub name: String,
+    pub input: String,
+    pub expected: String
+}
+
+// The cases of a test file, along with any text before the first case.
+#[derive(Clone, Debug)]
+pub struct TestFile {
+    pub preamble: String,
+    pub cases: Vec<TestCase>
+}
+
+impl TestFile {
+    pub fn parse(text: &str) -> Result<TestFile, String> {
+        let mut preamble = String::new();
+        let mut cases: Vec<TestCase> = Vec::new();
+
+        // Whether the lines being read belong to the input or the expected outcome of the last case.
+        let mut in_input = false;
+
+        for line in text.lines() {
+            if let Some(name) = line.strip_prefix("=== ") {
+                if in_input {
+                    return Err(format!("case '{}' has no --- line", cases.last().unwrap().name))
+                }
+                cases.push(TestCase { name: name.trim().to_string(), input: String::new(), expected: String::new() });
+                in_input = true;
+                continue
+            }
+
+            match cases.last_mut() {
+                None => {
+                    preamble.push_str(line);
+                    preamble.push('\n');
+                },
+                Some(case) if in_input => if line == "---" {
+                    // The newline before the --- is not part of the input.
+                    case.input.pop();
+                    in_input = false;
+                } else {
+                    case.input.push_str(line);
+                    case.input.push('\n');
+                },
+                Some(case) => {
+                    case.expected.push_str(line);
+                    case.expected.push('\n');
+                }
+            }
+        }
+
+        if in_input {
+            return Err(format!("case '{}' has no --- line", cases.last().unwrap().name))
+        }
+
+        Ok(TestFile { preamble, cases })
+    }
+}
+
+impl fmt::Display for TestFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.preamble)?;
+        for (i, case) in self.cases.iter().enumerate() {
+            writeln!(f, "=== {}", case.name)?;
+            writeln!(f, "{}", case.input)?;
+            writeln!(f, "---")?;
+            writeln!(f, "{}", case.expected.trim())?;
+            if i + 1 < self.cases.len() {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Turns the input of a case into the symbols of the program.
+pub fn input_symbols(program: &Program, input: &str) -> Result<Vec<u32>, String> {
+    if program.is_binary() {
+        Ok(input.bytes().map(|byte| byte as u32).collect())
+    } else {
+        input.split_whitespace()
+            .map(|name| program.symbol(name).ok_or_else(|| format!("'{}' is not a symbol of the grammar", name)))
+            .collect()
+    }
+}
+
+// Runs the symbols through a fresh parse machine. Returns the symbols of the accepted parse, or
+// the outcome when the input is not accepted.
+pub fn parse<'a>(program: &'a Program, input: &[u32]) -> Result<Vec<ProgramSymbolOrRule<'a>>, Outcome> {
+    parse_with(program.machine(), input)
+}
+
+// Like parse, with any parser for the program.
+pub fn parse_with<'a>(mut machine: impl Parser<u32, ProgramRule<'a>>, input: &[u32]) -> Result<Vec<ProgramSymbolOrRule<'a>>, Outcome> {
+    let mut parsed = Vec::new();
+    let mut accepted = false;
+
+    for (offset, &symbol) in input.iter().enumerate() {
+        match machine.read(symbol) {
+
+            // Every branch is accepted, so none can read the rest of the input, if there is any.
+            ReadResult::Rejected{ reason: RejectReason::Ambiguous } if offset + 1 < input.len() => return Err(Outcome::Reject(offset + 1)),
+            ReadResult::Rejected{ reason: RejectReason::Ambiguous } => return Err(Outcome::Ambiguous),
+            ReadResult::Rejected{ .. } => return Err(Outcome::Reject(offset)),
+            ReadResult::Processed{ result, symbols } => {
+                parsed.extend(symbols.iter().copied());
+                accepted = matches!(result, ProcessResult::Accepted);
+            }
+        }
+    }
+
+    if !accepted {
+        match machine.finish() {
+            ReadResult::Rejected{ reason: RejectReason::Ambiguous } => return Err(Outcome::Ambiguous),
+            ReadResult::Rejected{ .. } => return Err(Outcome::Reject(input.len())),
+            ReadResult::Processed{ symbols, .. } => parsed.extend(symbols.iter().copied())
+        }
+    }
+
+    Ok(parsed)
+}
+
+pub fn run(program: &Program, input: &[u32]) -> Outcome {
+    match parse(program, input) {
+        Ok(parsed) => match program.tree(&parsed) {
+            Some(tree) => Outcome::Accept(tree),
+            None => panic!("the parse machine returned unbalanced rule calls")
+        },
+        Err(outcome) => outcome
+    }
+}
+
+// The names of the symbols that could have come where a fresh parse machine rejects the input
+// with an error. Empty if it does not.
+pub fn expected_names(program: &Program, input: &[u32]) -> Vec<String> {
+    let mut machine = program.machine();
+    let rejected = input.iter().any(|&symbol| matches!(machine.read(symbol), ReadResult::Rejected{ .. }));
+    if !rejected {
+        machine.finish();
+    }
+    machine.expected().iter().map(|&symbol| program.symbol_name(symbol)).collect()
+}
+
+// A line diff of expected against actual, with - for lines only in expected and + for lines only
+// in actual.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+
+    // lengths[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            result.push_str(&format!("  {}\n", a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            result.push_str(&format!("- {}\n", a[i]));
+            i += 1;
+        } else {
+            result.push_str(&format!("+ {}\n", b[j]));
+            j += 1;
+        }
+    }
+
+    result
+}
+
+// The test files at the path, in a stable order.
+pub fn test_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()])
+    }
+
+    let mut files = Vec::new();
+    let entries = fs::read_dir(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    for entry in entries {
+        let entry = entry.map_err(|error| format!("{}: {}", path.display(), error))?;
+        let entry_path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue
+        }
+        if entry_path.is_dir() {
+            files.extend(test_files(&entry_path)?);
+        } else {
+            files.push(entry_path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+struct Totals {
+    passed: usize,
+    failed: usize
+}
+
+// Runs one test file, printing a line per case and a diff per failure. Returns whether blessing
+// changed any expected outcomes.
+fn run_file(program: &Program, file: &mut TestFile, path: &Path, bless: bool, totals: &mut Totals) -> bool {
+    let mut changed = false;
+
+    for case in &mut file.cases {
+        let result = Expected::parse(&case.expected)
+            .and_then(|expected| input_symbols(program, &case.input).map(|input| (expected, input)));
+
+        let (expected, input) = match result {
+            Ok(result) => result,
+            Err(message) => {
+                println!("FAIL {}: {}: {}", path.display(), case.name, message);
+                totals.failed += 1;
+                continue
+            }
+        };
+
+        let outcome = run(program, &input);
+        if expected.matches(&outcome) {
+            println!("ok   {}: {}", path.display(), case.name);
+            totals.passed += 1;
+        } else if bless {
+            println!("bless {}: {}", path.display(), case.name);
+            case.expected = outcome.to_string();
+            changed = true;
+            totals.passed += 1;
+        } else {
+            println!("FAIL {}: {}", path.display(), case.name);
+            print!("{}", diff(&expected.to_string(), &outcome.to_string()));
+            let names = expected_names(program, &input);
+            if !names.is_empty() {
+                println!("expected one of: {}", names.join(", "));
+            }
+            totals.failed += 1;
+        }
+    }
+
+    changed
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let bless = args.iter().any(|arg| arg == "--bless");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--bless").collect();
+    let (grammar_path, tests_path) = match paths.as_slice() {
+        [grammar_path, tests_path] => (grammar_path.as_str(), Path::new(tests_path.as_str())),
+        _ => {
+            eprintln!("usage: parsergen test <grammar.pglsf> <tests> [--bless]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let program = match grammar::load(grammar_path).and_then(|(grammar, source)| {
+        Program::compile(&grammar).map_err(|error| error.describe(grammar_path, &source))
+    }) {
+        Ok(program) => program,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let files = match test_files(tests_path) {
+        Ok(files) => files,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE
+        }
+    };
+
+    let mut totals = Totals { passed: 0, failed: 0 };
+    for path in files {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| TestFile::parse(&text));
+
+        let mut file = match parsed {
+            Ok(file) => file,
+            Err(message) => {
+                println!("FAIL {}: {}", path.display(), message);
+                totals.failed += 1;
+                continue
+            }
+        };
+
+        if run_file(&program, &mut file, &path, bless, &mut totals) {
+            if let Err(error) = fs::write(&path, file.to_string()) {
+                eprintln!("{}: {}", path.display(), error);
+                return ExitCode::FAILURE
+            }
+        }
+    }
+
+    println!("{} passed; {} failed", totals.passed, totals.failed);
+    if totals.failed == 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,152 @@
+use crate::tester::*;
+use crate::grammar;
+
+const FILE: &str = "\
+# Cases for the pair grammar.
+
+=== two pairs
+A B A B
+---
+(root (pair A B) (pair A B))
+
+=== dangling
+A B A
+---
+reject 3
+";
+
+#[test]
+fn test_parse_test_file() {
+    let file = TestFile::parse(FILE).unwrap();
+    assert_eq!(file.preamble, "# Cases for the pair grammar.\n\n");
+    assert_eq!(file.cases.len(), 2);
+    assert_eq!(file.cases[1].name, "dangling");
+    assert_eq!(file.cases[1].input, "A B A");
+    assert_eq!(Expected::parse(&file.cases[1].expected), Ok(Expected::Reject(3)));
+    assert_eq!(file.to_string(), FILE);
+}
+
+#[test]
+fn test_parse_expected() {
+    assert_eq!(Expected::parse("accept\n"), Ok(Expected::Accept));
+    assert_eq!(Expected::parse("ambiguous"), Ok(Expected::Ambiguous));
+    assert!(Expected::parse("reject").is_err());
+    assert!(Expected::parse("(root (pair A B)").is_err());
+}
+
+#[test]
+fn test_diff() {
+    assert_eq!(diff("a\nb\nc", "a\nc\nd"), "  a\n- b\n  c\n+ d\n");
+}
+
+const GRAMMAR: &str = "symbols A, B;\ngrammar\n\troot = pair *pair;\n\tpair = A B;\n";
+
+#[test]
+fn test_expected_names() {
+    let program = Program::compile(&grammar::read(GRAMMAR).unwrap()).unwrap();
+    let names = |input: &str| expected_names(&program, &input_symbols(&program, input).unwrap());
+    assert_eq!(names("A A"), ["B"]);
+    assert_eq!(names("A B B"), ["A"]);
+    assert_eq!(names("A B A"), ["B"]);
+    assert!(names("A B").is_empty());
+}
+
+// A directory for the files of one test, removed with everything in it when it is dropped.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Writes a grammar and a test file into a fresh directory, and returns it with their paths.
+fn write_files(name: &str, grammar: &str, tests: &str) -> (TempDir, String, String) {
+    let dir = TempDir(std::env::temp_dir().join(format!("parsergen-{}-{}", name, std::process::id())));
+    fs::create_dir_all(&dir.0).unwrap();
+    let grammar_path = dir.0.join("pair.pglsf");
+    let tests_path = dir.0.join("pair.test");
+    fs::write(&grammar_path, grammar).unwrap();
+    fs::write(&tests_path, tests).unwrap();
+    (dir, grammar_path.display().to_string(), tests_path.display().to_string())
+}
+
+#[test]
+fn test_main_reject_offset() {
+    let (_dir, grammar_path, tests_path) = write_files("reject", GRAMMAR, FILE);
+    assert_eq!(main(&[grammar_path.clone(), tests_path.clone()]), ExitCode::SUCCESS);
+
+    let (_dir, grammar_path, tests_path) = write_files("wrong-offset", GRAMMAR, &FILE.replace("reject 3", "reject 2"));
+    assert_eq!(main(&[grammar_path, tests_path]), ExitCode::FAILURE);
+}
+
+#[test]
+fn test_main_bless() {
+    let wrong = FILE.replace("reject 3", "accept");
+    let (_dir, grammar_path, tests_path) = write_files("bless", GRAMMAR, &wrong);
+    assert_eq!(main(&[grammar_path.clone(), tests_path.clone()]), ExitCode::FAILURE);
+    assert_eq!(fs::read_to_string(&tests_path).unwrap(), wrong);
+
+    assert_eq!(main(&[grammar_path.clone(), tests_path.clone(), "--bless".to_string()]), ExitCode::SUCCESS);
+    assert_eq!(fs::read_to_string(&tests_path).unwrap(), FILE);
+    assert_eq!(main(&[grammar_path, tests_path]), ExitCode::SUCCESS);
+}
+
+const AMBIGUOUS_GRAMMAR: &str = "symbols A, B;\ngrammar\n\troot = first | second;\n\tfirst = A;\n\tsecond = A;\n";
+
+const AMBIGUOUS_FILE: &str = "\
+=== ambiguous
+A
+---
+ambiguous
+
+=== left over
+A B
+---
+reject 1
+";
+
+#[test]
+fn test_main_bless_ambiguous() {
+    // Input that ends where every branch is accepted stays ambiguous, and --bless leaves it as it
+    // is. Input left over after that point is rejected at the first symbol left over.
+    let (_dir, grammar_path, tests_path) = write_files("ambiguous", AMBIGUOUS_GRAMMAR, AMBIGUOUS_FILE);
+    assert_eq!(main(&[grammar_path.clone(), tests_path.clone()]), ExitCode::SUCCESS);
+    assert_eq!(main(&[grammar_path, tests_path.clone(), "--bless".to_string()]), ExitCode::SUCCESS);
+    assert_eq!(fs::read_to_string(&tests_path).unwrap(), AMBIGUOUS_FILE);
+}
+
+#[test]
+fn test_pglsf_smoke() {
+    let (grammar, _) = grammar::load("../languages/pglsf.pglsf").unwrap();
+    let program = Program::compile(&grammar).unwrap();
+    let text = |source: &str| {
+        let names: Vec<String> = source.chars().map(pglsf_name).collect();
+        input_symbols(&program, &names.join(" ")).unwrap()
+    };
+
+    let accepted = text("symbols\n\tA, B;\ngrammar\n\troot = A *(B | root);\n");
+    let rejected = text("symbols\n\tA;\ngrammar\n\troot = A\n");
+    assert!(matches!(run(&program, &accepted), Outcome::Accept(_)));
+    assert_eq!(run(&program, &rejected), Outcome::Reject(rejected.len()));
+}
+
+// The name pglsf.pglsf gives a character of the texts above.
+fn pglsf_name(c: char) -> String {
+    let name = match c {
+        'A'..='Z' => return format!("ULETTER_{}", c),
+        'a'..='z' => return format!("LETTER_{}", c.to_ascii_uppercase()),
+        ' ' => "SPACE",
+        '\t' => "TAB",
+        '\n' => "NEWLINE",
+        '(' => "LEFT_PAREN",
+        ')' => "RIGHT_PAREN",
+        '*' => "ASTERISK",
+        ',' => "COMMA",
+        ';' => "SEMICOLON",
+        '=' => "EQUAL_SIGN",
+        '|' => "PIPE",
+        _ => panic!("no name for {:?}", c)
+    };
+    name.to_string()
+}
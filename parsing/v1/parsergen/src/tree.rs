@@ -0,0 +1,157 @@
+// tree.rs
+//
+// A parse tree written as an S-expression: (rule child child ...), where the children are nested
+// rules or symbol names. A child captured by a label in the grammar is written after the label, as
+// in (rule name:(rule_name ...) EQUAL_SIGN body:(rule_expr ...)).
+//
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Tree {
+    Node(String, Vec<Tree>),
+    Leaf(String),
+
+    // A labelled child.
+    Field(String, Box<Tree>)
+}
+
+impl Tree {
+    pub fn push(&mut self, child: Tree) {
+        match self {
+            Tree::Node(_, children) => children.push(child),
+            Tree::Leaf(_) | Tree::Field(_, _) => panic!()
+        }
+    }
+
+    // The first child with the label.
+    pub fn field<'a>(&'a self, label: &'a str) -> Option<&'a Tree> {
+        self.fields(label).next()
+    }
+
+    // Every child with the label, in order.
+    pub fn fields<'a>(&'a self, label: &'a str) -> impl Iterator<Item = &'a Tree> {
+        let children = match self {
+            Tree::Node(_, children) => children.as_slice(),
+            Tree::Leaf(_) | Tree::Field(_, _) => &[]
+        };
+        children.iter().filter_map(move |child| match child {
+            Tree::Field(name, tree) if name == label => Some(tree.as_ref()),
+            _ => None
+        })
+    }
+
+    // Writes the tree on several lines, putting a node on one line when it fits within the width.
+    pub fn pretty(&self, width: usize) -> String {
+        let mut result = String::new();
+        self.pretty_into(&mut result, 0, width);
+        result
+    }
+
+    fn pretty_into(&self, result: &mut String, indent: usize, width: usize) {
+        let compact = self.to_string();
+        match self {
+            Tree::Node(name, children) if indent + compact.len() > width && !children.is_empty() => {
+                result.push('(');
+                result.push_str(name);
+                for child in children {
+                    result.push('\n');
+                    result.push_str(&" ".repeat(indent + 2));
+                    child.pretty_into(result, indent + 2, width);
+                }
+                result.push(')');
+            },
+            Tree::Field(label, tree) => {
+                result.push_str(label);
+                result.push(':');
+                tree.pretty_into(result, indent, width);
+            },
+            _ => result.push_str(&compact)
+        }
+    }
+
+    // Reads a tree from an S-expression.
+    pub fn parse(text: &str) -> Result<Tree, String> {
+        let mut tokens = tokenize(text).into_iter().peekable();
+        let tree = parse_tree(&mut tokens)?;
+        match tokens.next() {
+            None => Ok(tree),
+            Some(token) => Err(format!("unexpected '{}' after the end of the tree", token))
+        }
+    }
+}
+
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tree::Leaf(name) => write!(f, "{}", name),
+            Tree::Field(label, tree) => write!(f, "{}:{}", label, tree),
+            Tree::Node(name, children) => {
+                write!(f, "({}", name)?;
+                for child in children {
+                    write!(f, " {}", child)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        let is_paren = c == '(' || c == ')';
+        if c.is_whitespace() || is_paren {
+            if let Some(s) = start.take() {
+                tokens.push(&text[s..i]);
+            }
+            if is_paren {
+                tokens.push(&text[i..i + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+
+    if let Some(s) = start {
+        tokens.push(&text[s..]);
+    }
+
+    tokens
+}
+
+fn parse_tree<'a, I>(tokens: &mut std::iter::Peekable<I>) -> Result<Tree, String>
+    where I: Iterator<Item = &'a str>
+{
+    match tokens.next() {
+        Some("(") => {
+            let name = match tokens.next() {
+                Some("(") | Some(")") | None => return Err("expected a rule name after '('".to_string()),
+                Some(name) => name.to_string()
+            };
+
+            let mut children = Vec::new();
+            loop {
+                match tokens.peek() {
+                    Some(&")") => {
+                        tokens.next();
+                        return Ok(Tree::Node(name, children))
+                    },
+                    Some(_) => children.push(parse_tree(tokens)?),
+                    None => return Err(format!("missing ')' for '{}'", name))
+                }
+            }
+        },
+        Some(")") => Err("unexpected ')'".to_string()),
+        Some(label) if label.ends_with(':') => {
+            let tree = parse_tree(tokens)?;
+            Ok(Tree::Field(label[..label.len() - 1].to_string(), Box::new(tree)))
+        },
+        Some(name) => match name.split_once(':') {
+            Some((label, name)) => Ok(Tree::Field(label.to_string(), Box::new(Tree::Leaf(name.to_string())))),
+            None => Ok(Tree::Leaf(name.to_string()))
+        },
+        None => Err("expected a tree".to_string())
+    }
+}